@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rs_lru::{Cache, LRUCache};
+
+const LEN: u32 = 10_000;
+
+// 50 keys spread over the cache, then the same batch with every fifth key missing and every
+// fifth one repeated
+fn batches() -> [(&'static str, Vec<u32>); 2] {
+   let distinct: Vec<_> = (0..50).map(|i| i * 97 % LEN).collect();
+   let mixed = distinct
+      .iter()
+      .enumerate()
+      .map(|(i, &k)| match i % 5 {
+         0 => k + LEN,
+         1 => distinct[0],
+         _ => k,
+      })
+      .collect();
+   [("distinct", distinct), ("mixed", mixed)]
+}
+
+fn bench_get_many(c: &mut Criterion) {
+   let mut cache = LRUCache::with_capacity(LEN as usize);
+   for k in 0..LEN {
+      cache.insert(k, k);
+   }
+   let mut group = c.benchmark_group("lookup 50 keys");
+   for (name, keys) in batches() {
+      group.bench_with_input(BenchmarkId::new("get loop", name), &keys, |b, keys| {
+         b.iter(|| {
+            for k in keys {
+               black_box(cache.get(k));
+            }
+         })
+      });
+      group.bench_with_input(BenchmarkId::new("get_many", name), &keys, |b, keys| {
+         b.iter(|| {
+            black_box(cache.get_many(keys));
+         })
+      });
+   }
+   group.finish();
+}
+
+criterion_group!(benches, bench_get_many);
+criterion_main!(benches);
@@ -0,0 +1,427 @@
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, NonNullNode};
+use crate::lru::{LRUCache, PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+struct Item<K, V> {
+   key: K,
+   value: V,
+   // lives in `t2` rather than `t1`
+   frequent: bool,
+}
+
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// An adaptive replacement cache (Megiddo and Modha). Entries seen once live in `T1` and entries
+/// seen at least twice in `T2`, both LRU ordered. The keys recently evicted from each are
+/// remembered in the ghost lists `B1` and `B2`, and a miss that hits a ghost list moves the
+/// target size `p` of `T1` towards the side that would have kept the key. No thresholds need
+/// tuning: the split between recency and frequency follows the workload.
+///
+/// ```
+/// use rs_lru::{ArcCache, Cache};
+///
+/// let mut cache = ArcCache::with_capacity(2);
+/// cache.insert(1, "a");
+/// cache.get(&1);
+/// cache.insert(2, "b");
+/// cache.insert(3, "c");
+/// // 2 was only seen once and made room, 1 is frequent
+/// assert_eq!(cache.get(&2), None);
+/// assert_eq!(cache.get(&1), Some(&"a"));
+/// // coming back while remembered in B1 favours recency
+/// cache.insert(2, "b");
+/// assert_eq!(cache.p(), 1);
+/// ```
+pub struct ArcCache<K, V> {
+   map: KeyMap<Item<K, V>>,
+   t1: List<Item<K, V>>,
+   t2: List<Item<K, V>>,
+   b1: LRUCache<K, ()>,
+   b2: LRUCache<K, ()>,
+   cap: usize,
+   p: usize,
+}
+
+impl<K: Hash + Eq, V> ArcCache<K, V> {
+   /// Creates a cache holding at most `cap` entries and remembering as many evicted keys. A
+   /// capacity of zero caches nothing: every new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self {
+         map: HashMap::new(),
+         t1: List::new(),
+         t2: List::new(),
+         b1: LRUCache::unbounded(),
+         b2: LRUCache::unbounded(),
+         cap,
+         p: 0,
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   /// Returns the current target size of `T1`, between zero and the capacity.
+   pub fn p(&self) -> usize {
+      self.p
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   /// Returns the sizes of (`T1`, `T2`, `B1`, `B2`).
+   pub fn list_lens(&self) -> (usize, usize, usize, usize) {
+      (self.t1.len(), self.t2.len(), self.b1_len(), self.b2_len())
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns the value without counting an access.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. The ghost list keeps a
+   /// copy of its key. A cache with a capacity of zero hands back every new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V>
+   where
+      K: Clone,
+   {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return PushResult::Replaced(self.overwrite(node, v));
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      match self.admit(k, v) {
+         Some((victim, remembered)) => {
+            if remembered {
+               self.remember(victim.key.clone(), victim.frequent);
+            }
+            PushResult::Evicted(victim.key, victim.value)
+         }
+         None => PushResult::Fit,
+      }
+   }
+
+   /// Drops every entry and the remembered keys, and resets `p`.
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.t1.clear();
+      self.t2.clear();
+      self.b1.clear();
+      self.b2.clear();
+      self.p = 0;
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      self.t1.validate();
+      self.t2.validate();
+      let (t1, t2, b1, b2) = self.list_lens();
+      assert!(t1 + t2 <= self.cap);
+      assert!(t1 + b1 <= self.cap);
+      assert!(t1 + t2 + b1 + b2 <= 2 * self.cap);
+      assert!(self.p <= self.cap);
+      assert_eq!(self.map.len(), t1 + t2);
+      for (list, frequent) in [(&self.t1, false), (&self.t2, true)] {
+         let mut cur = list.begin_node();
+         while let Some(node) = cur {
+            let item = unsafe { &node.as_ref().element };
+            assert_eq!(item.frequent, frequent);
+            assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+            assert!(!self.b1.contains_key(&item.key) && !self.b2.contains_key(&item.key));
+            cur = list.next_node(node);
+         }
+      }
+   }
+
+   fn b1_len(&self) -> usize {
+      self.b1.len()
+   }
+
+   fn b2_len(&self) -> usize {
+      self.b2.len()
+   }
+
+   // Case I of the paper: any hit makes the entry frequent
+   fn hit(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      if unsafe { node.as_ref().element.frequent } {
+         self.t2.move_to_front(node);
+      } else {
+         self
+            .t2
+            .splice_front(self.t2.begin_node(), &mut self.t1, node);
+         unsafe { node.as_mut().element.frequent = true };
+      }
+   }
+
+   fn overwrite(&mut self, mut node: NonNullNode<Item<K, V>>, v: V) -> V {
+      let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+      self.hit(node);
+      ret
+   }
+
+   // Cases II to IV of the paper: adapt `p`, make room and link the new key. Returns the entry
+   // evicted for it and whether the caller remembers its key in a ghost list.
+   fn admit(&mut self, k: K, v: V) -> Option<(Item<K, V>, bool)> {
+      let (b1, b2) = (self.b1_len(), self.b2_len());
+      let (frequent, victim) = if self.b1.remove(&k).is_some() {
+         // case II: T1 was too small
+         self.p = self.cap.min(self.p + (b2 / b1).max(1));
+         (true, self.replace(false))
+      } else if self.b2.remove(&k).is_some() {
+         // case III: T2 was too small
+         self.p = self.p.saturating_sub((b1 / b2).max(1));
+         (true, self.replace(true))
+      } else {
+         // case IV: a key seen for the first time
+         let t1 = self.t1.len();
+         let victim = if t1 + b1 >= self.cap {
+            if t1 < self.cap {
+               self.b1.pop_lru();
+               self.replace(false)
+            } else {
+               let node = self.t1.end_node().unwrap();
+               Some((self.unlink(node), false))
+            }
+         } else if t1 + self.t2.len() + b1 + b2 >= self.cap {
+            if t1 + self.t2.len() + b1 + b2 >= 2 * self.cap {
+               self.b2.pop_lru();
+            }
+            self.replace(false)
+         } else {
+            None
+         };
+         (false, victim)
+      };
+      let item = Item {
+         key: k,
+         value: v,
+         frequent,
+      };
+      let node = if frequent {
+         self.t2.push_front(item);
+         self.t2.begin_node()
+      } else {
+         self.t1.push_front(item);
+         self.t1.begin_node()
+      }
+      .unwrap();
+      self.map.insert(KeyRef(node), node);
+      victim
+   }
+
+   // REPLACE of the paper: evict the LRU entry of T1 or T2, for the caller to demote its key
+   fn replace(&mut self, in_b2: bool) -> Option<(Item<K, V>, bool)> {
+      let t1 = self.t1.len();
+      let node = if t1 > 0 && (t1 > self.p || (in_b2 && t1 == self.p)) {
+         self.t1.end_node()
+      } else {
+         self.t2.end_node()
+      };
+      Some((self.unlink(node?), true))
+   }
+
+   // Demote an evicted key to the ghost list of the side it was evicted from
+   fn remember(&mut self, k: K, frequent: bool) {
+      if frequent {
+         self.b2.insert(k, ());
+      } else {
+         self.b1.insert(k, ());
+      }
+   }
+
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      key_ref::forget(&mut self.map, node);
+      if unsafe { node.as_ref().element.frequent } {
+         self.t2.remove_node(node)
+      } else {
+         self.t1.remove_node(node)
+      }
+   }
+}
+
+// SAFETY: the map only points into `t1` and `t2`, both owned by the cache
+unsafe impl<K: Send, V: Send> Send for ArcCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for ArcCache<K, V> {}
+
+impl<K: Hash + Eq, V> Default for ArcCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for ArcCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      self.hit(node);
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Inserting a cached key counts as a hit. A miss runs the adaptation and replacement of the
+   /// paper before the entry is admitted.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return Some(self.overwrite(node, v));
+      }
+      if self.cap == 0 {
+         return None;
+      }
+      if let Some((victim, true)) = self.admit(k, v) {
+         self.remember(victim.key, victim.frequent);
+      }
+      None
+   }
+
+   /// Removes the entry without remembering it in a ghost list.
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      Some(self.unlink(node).value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      ArcCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      ArcCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      ArcCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      ArcCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self
+         .t2
+         .iter()
+         .chain(self.t1.iter())
+         .for_each(|item| f(&item.key, &item.value))
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for ArcCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      ArcCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_hit_in_t1_and_t2() {
+      let mut cache = ArcCache::with_capacity(4);
+      cache.insert(1, 1);
+      cache.insert(2, 2);
+      assert_eq!(cache.list_lens(), (2, 0, 0, 0));
+      // a hit in T1 moves the entry to T2
+      assert_eq!(cache.get(&1), Some(&1));
+      assert_eq!(cache.list_lens(), (1, 1, 0, 0));
+      // a hit in T2 keeps it there
+      assert_eq!(cache.insert(1, 10), Some(1));
+      assert_eq!(cache.list_lens(), (1, 1, 0, 0));
+      assert_eq!(cache.peek(&1), Some(&10));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_ghost_hits_adapt_p() {
+      let mut cache = ArcCache::with_capacity(2);
+      cache.insert(1, 1);
+      cache.insert(2, 2);
+      cache.get(&2);
+      // T1 = [1], T2 = [2]: the miss pushes 1 to B1
+      cache.insert(3, 3);
+      assert_eq!(cache.list_lens(), (1, 1, 1, 0));
+      assert_eq!(cache.p(), 0);
+      // case II: 1 comes back from B1 and p grows, T1 is not above it so T2 gives up 2
+      cache.insert(1, 1);
+      cache.validate();
+      assert_eq!(cache.p(), 1);
+      assert_eq!(cache.list_lens(), (1, 1, 0, 1));
+      assert!(cache.b2.contains_key(&2));
+      // case III: 2 comes back from B2, p shrinks and T1 gives up 3
+      cache.insert(2, 2);
+      cache.validate();
+      assert_eq!(cache.p(), 0);
+      assert_eq!(cache.list_lens(), (0, 2, 1, 0));
+      assert!(cache.b1.contains_key(&3));
+      // a new key with T1 empty takes the LRU of T2
+      cache.insert(4, 4);
+      cache.validate();
+      assert_eq!(cache.list_lens(), (1, 1, 1, 1));
+      assert!(cache.b2.contains_key(&1));
+      assert_eq!(cache.peek(&2), Some(&2));
+   }
+
+   #[test]
+   fn test_miss_with_full_t1() {
+      let mut cache = ArcCache::with_capacity(2);
+      cache.insert(1, 1);
+      cache.insert(2, 2);
+      // |T1| = c: the LRU of T1 is dropped without being remembered
+      cache.insert(3, 3);
+      assert_eq!(cache.list_lens(), (2, 0, 0, 0));
+      assert!(!cache.contains_key(&1));
+      cache.validate();
+      assert_eq!(cache.remove(&2), Some(2));
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(ArcCache::with_capacity(0).insert(1, 1), None);
+   }
+
+   #[test]
+   fn test_random_invariants() {
+      for cap in [1, 2, 5, 16] {
+         let mut cache = ArcCache::with_capacity(cap);
+         let mut seed = cap as u32;
+         let rounds = if cfg!(miri) { 200 } else { 5000 };
+         for _ in 0..rounds {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            // a skewed key space: a few hot keys and a long cold tail
+            let k = match (seed >> 20) % 4 {
+               0 | 1 => (seed >> 8) % 4,
+               _ => (seed >> 8) % 64,
+            };
+            match (seed >> 4) % 5 {
+               0 | 1 => {
+                  cache.get(&k);
+               }
+               2 | 3 => {
+                  cache.insert(k, k);
+               }
+               _ => {
+                  cache.remove(&k);
+               }
+            }
+            cache.validate();
+         }
+      }
+   }
+}
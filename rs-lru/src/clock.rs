@@ -0,0 +1,142 @@
+use crate::policy::{Entries, EvictionPolicy, Handle, PolicyCache};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// CLOCK: a hit only sets the entry's referenced bit, and the victim is the first entry from the
+/// front without one. The referenced entries passed on the way lose their bit and go to the
+/// back, as does every new entry.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Clock;
+
+impl EvictionPolicy for Clock {
+   /// The referenced bit.
+   type Meta = bool;
+
+   fn on_insert<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, bool>, h: Handle<'a>) {
+      entries.move_to_back(h);
+   }
+
+   fn on_hit<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, bool>, h: Handle<'a>) {
+      *entries.meta_mut(h) = true;
+   }
+
+   fn choose_victim<'a, K, V>(
+      &mut self,
+      entries: &mut Entries<'a, K, V, bool>,
+   ) -> Option<Handle<'a>> {
+      let mut hand = entries.front()?;
+      // every bit cleared sends the sweep one step on, so it stops within a round
+      while *entries.meta(hand) {
+         *entries.meta_mut(hand) = false;
+         entries.move_to_back(hand);
+         hand = entries.front()?;
+      }
+      Some(hand)
+   }
+}
+
+/// A CLOCK (second chance) cache. The entries form a ring swept by a hand: a hit only sets the
+/// entry's referenced bit, nothing is relinked, and eviction advances the hand, clearing bits,
+/// until it reaches an entry that was not referenced since the last sweep. New entries join the
+/// ring right behind the hand, so they are the last ones the next sweep looks at. The front of
+/// the list is the hand: the sweep moves the entries it passes to the back.
+///
+/// ```
+/// use rs_lru::{Cache, ClockCache};
+///
+/// let mut cache = ClockCache::with_capacity(2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// cache.get(&1);
+/// // 1 gets a second chance, 2 does not
+/// cache.insert(3, "c");
+/// assert_eq!(cache.get(&2), None);
+/// assert_eq!(cache.get(&1), Some(&"a"));
+/// ```
+pub type ClockCache<K, V> = PolicyCache<K, V, Clock>;
+
+impl<K: Hash + Eq, V> PolicyCache<K, V, Clock> {
+   /// Returns whether the entry was referenced since the hand last passed it.
+   pub fn is_referenced<Q>(&self, k: &Q) -> Option<bool>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.meta(k).copied()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::Cache;
+
+   #[test]
+   fn test_second_chance() {
+      let mut cache = ClockCache::with_capacity(3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+      }
+      cache.get(&1);
+      cache.get(&3);
+      // the hand passes 1, takes 2
+      cache.insert(4, 4);
+      cache.validate();
+      assert!(!cache.contains_key(&2));
+      assert_eq!(cache.is_referenced(&1), Some(false));
+      assert_eq!(cache.is_referenced(&3), Some(true));
+      // the hand clears 3 and wraps to 1, which already used its second chance
+      cache.insert(5, 5);
+      assert!(!cache.contains_key(&1));
+      assert_eq!(cache.is_referenced(&3), Some(false));
+      // 4 joined right behind the hand, so the sweep came back to it only now
+      cache.insert(6, 6);
+      assert!(!cache.contains_key(&4));
+      assert!(cache.contains_key(&3) && cache.contains_key(&5));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_full_wrap() {
+      let mut cache = ClockCache::with_capacity(3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+      }
+      // overwrites set the bit too
+      for i in 1..=3 {
+         assert_eq!(cache.insert(i, i * 10), Some(i));
+      }
+      // a whole sweep clears every bit and comes back to where it started
+      assert_eq!(cache.pop_victim(), Some((1, 10)));
+      cache.validate();
+      assert_eq!(cache.is_referenced(&2), Some(false));
+      assert_eq!(cache.is_referenced(&3), Some(false));
+      assert_eq!(cache.pop_victim(), Some((2, 20)));
+   }
+
+   #[test]
+   fn test_remove_around_the_hand() {
+      let mut cache = ClockCache::with_capacity(3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+      }
+      // removing the entry under the hand moves the hand on
+      assert_eq!(cache.remove(&1), Some(1));
+      cache.validate();
+      cache.get(&2);
+      cache.insert(4, 4);
+      cache.insert(5, 5);
+      assert!(!cache.contains_key(&3));
+      cache.validate();
+      for k in [2, 4, 5] {
+         assert!(cache.remove(&k).is_some());
+         cache.validate();
+      }
+      assert!(cache.is_empty());
+      assert_eq!(cache.pop_victim(), None);
+      cache.insert(6, 6);
+      cache.validate();
+      assert_eq!(cache.pop_victim(), Some((6, 6)));
+      assert_eq!(ClockCache::with_capacity(0).insert(1, 1), None);
+   }
+}
@@ -0,0 +1,90 @@
+//! The map key shared by the caches that keep their entries in a [`List`](crate::List): a pointer
+//! to the node, hashed and compared through the key stored in it, so every key is stored once.
+
+use crate::list::NonNullNode;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A list element that carries the key of its entry.
+pub(crate) trait Keyed {
+   type Key;
+
+   fn key(&self) -> &Self::Key;
+}
+
+/// Maps a key to the node holding it. The map reads its keys through the nodes, so clear it
+/// before the lists owning them.
+pub(crate) type KeyMap<T> = HashMap<KeyRef<T>, NonNullNode<T>>;
+
+/// Removes the map entry of `node`, while the node and with it the key are still alive.
+pub(crate) fn forget<T: Keyed>(map: &mut KeyMap<T>, node: NonNullNode<T>)
+where
+   T::Key: Hash + Eq,
+{
+   map.remove(KeyWrapper::from_ref(unsafe { node.as_ref().element.key() }));
+}
+
+/// Points at a node whose element holds the key. The node must outlive the map entry, so remove
+/// it from the map before the node is dropped.
+pub(crate) struct KeyRef<T>(pub(crate) NonNullNode<T>);
+
+impl<T: Keyed> KeyRef<T> {
+   fn key(&self) -> &T::Key {
+      unsafe { self.0.as_ref().element.key() }
+   }
+}
+
+impl<T: Keyed> Eq for KeyRef<T> where T::Key: Eq {}
+
+impl<T: Keyed> PartialEq for KeyRef<T>
+where
+   T::Key: PartialEq,
+{
+   fn eq(&self, other: &Self) -> bool {
+      self.key().eq(other.key())
+   }
+}
+
+impl<T: Keyed> Hash for KeyRef<T>
+where
+   T::Key: Hash,
+{
+   fn hash<H: Hasher>(&self, state: &mut H) {
+      self.key().hash(state)
+   }
+}
+
+impl<T: Keyed, Q: ?Sized> Borrow<KeyWrapper<Q>> for KeyRef<T>
+where
+   T::Key: Borrow<Q>,
+{
+   fn borrow(&self) -> &KeyWrapper<Q> {
+      KeyWrapper::from_ref(self.key().borrow())
+   }
+}
+
+// Lets the map be probed with any `Q` that the stored key can be borrowed as
+#[repr(transparent)]
+pub(crate) struct KeyWrapper<Q: ?Sized>(Q);
+
+impl<Q: ?Sized> KeyWrapper<Q> {
+   pub(crate) fn from_ref(key: &Q) -> &Self {
+      // KeyWrapper is repr(transparent), so the cast keeps the layout
+      unsafe { &*(key as *const Q as *const KeyWrapper<Q>) }
+   }
+}
+
+impl<Q: ?Sized + Eq> Eq for KeyWrapper<Q> {}
+
+impl<Q: ?Sized + PartialEq> PartialEq for KeyWrapper<Q> {
+   fn eq(&self, other: &Self) -> bool {
+      self.0.eq(&other.0)
+   }
+}
+
+impl<Q: ?Sized + Hash> Hash for KeyWrapper<Q> {
+   fn hash<H: Hasher>(&self, state: &mut H) {
+      self.0.hash(state)
+   }
+}
@@ -0,0 +1,379 @@
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, NonNullNode};
+use crate::lru::{PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+struct Item<K, V> {
+   key: K,
+   value: V,
+   freq: u32,
+}
+
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// A least frequently used cache. Every entry sits in the bucket of its access count, most
+/// recently used first, so `get` and `insert` move it to the next bucket in O(1) and the victim
+/// is the least recently used entry of the lowest bucket. Both reads and writes count as uses.
+/// Only `remove` and `pop_lfu` can take longer: when they empty the lowest bucket, finding the
+/// next one looks at every bucket.
+///
+/// ```
+/// use rs_lru::{Cache, LFUCache};
+///
+/// let mut cache = LFUCache::with_capacity(2);
+/// cache.insert("hot", 1);
+/// cache.get(&"hot");
+/// cache.insert("a", 0);
+/// // "a" was used once, "hot" twice
+/// cache.insert("b", 0);
+/// assert!(!cache.contains_key(&"a"));
+/// assert_eq!(cache.get(&"hot"), Some(&1));
+/// ```
+pub struct LFUCache<K, V> {
+   map: KeyMap<Item<K, V>>,
+   buckets: HashMap<u32, List<Item<K, V>>>,
+   // lowest access count with a bucket, meaningless while the cache is empty
+   min_freq: u32,
+   cap: usize,
+}
+
+impl<K: Hash + Eq, V> LFUCache<K, V> {
+   /// Creates a cache holding at most `cap` entries. A capacity of zero caches nothing: every
+   /// new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self {
+         map: HashMap::new(),
+         buckets: HashMap::new(),
+         min_freq: 0,
+         cap,
+      }
+   }
+
+   /// Creates a cache that never evicts on insert. Its `capacity()` is `usize::MAX`.
+   pub fn unbounded() -> Self {
+      Self::with_capacity(usize::MAX)
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns the value without counting a use.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Returns how many times the entry has been used, its insertion included.
+   pub fn frequency(&self, k: &K) -> Option<u32> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { node.as_ref().element.freq })
+   }
+
+   /// Removes and returns the entry that would be evicted next. Emptying the lowest bucket makes
+   /// it O(number of distinct frequencies).
+   pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+      let item = self.evict()?;
+      self.find_min_freq();
+      Some((item.key, item.value))
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. A cache with a capacity
+   /// of zero hands back every new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         let mut node = node;
+         let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+         self.bump(node);
+         return PushResult::Replaced(ret);
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let mut evicted = PushResult::Fit;
+      if self.map.len() >= self.cap {
+         if let Some(victim) = self.evict() {
+            evicted = PushResult::Evicted(victim.key, victim.value);
+         }
+      }
+      let bucket = self.buckets.entry(1).or_default();
+      bucket.push_front(Item {
+         key: k,
+         value: v,
+         freq: 1,
+      });
+      let node = bucket.begin_node().unwrap();
+      self.map.insert(KeyRef(node), node);
+      // no count is lower, so the bucket the victim may have emptied needs no search
+      self.min_freq = 1;
+      evicted
+   }
+
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.buckets.clear();
+      self.min_freq = 0;
+   }
+
+   /// Asserts that the map and the buckets describe the same entries and that `min_freq` is the
+   /// lowest bucket.
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      let mut count = 0;
+      for (&freq, bucket) in &self.buckets {
+         bucket.validate();
+         assert!(!bucket.is_empty(), "empty bucket {} was kept", freq);
+         assert!(freq >= self.min_freq);
+         let mut cur = bucket.begin_node();
+         while let Some(node) = cur {
+            let item = unsafe { &node.as_ref().element };
+            assert_eq!(item.freq, freq);
+            assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+            count += 1;
+            cur = bucket.next_node(node);
+         }
+      }
+      assert_eq!(count, self.map.len());
+      assert!(self.map.len() <= self.cap);
+      if !self.map.is_empty() {
+         assert!(self.buckets.contains_key(&self.min_freq));
+      }
+   }
+
+   // Move the entry to the front of the next bucket, dropping its old bucket if it empties
+   fn bump(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      let freq = unsafe { node.as_ref().element.freq };
+      let Some(next) = freq.checked_add(1) else {
+         // a saturated count only refreshes recency
+         self.buckets.get_mut(&freq).unwrap().move_to_front(node);
+         return;
+      };
+      let mut src = self.buckets.remove(&freq).unwrap();
+      let dst = self.buckets.entry(next).or_default();
+      dst.splice_front(dst.begin_node(), &mut src, node);
+      unsafe { node.as_mut().element.freq = next };
+      if !src.is_empty() {
+         self.buckets.insert(freq, src);
+      } else if self.min_freq == freq {
+         self.min_freq = next;
+      }
+   }
+
+   // Unlink the victim, leaving `min_freq` to the caller
+   fn evict(&mut self) -> Option<Item<K, V>> {
+      let node = self.buckets.get(&self.min_freq)?.end_node()?;
+      Some(self.unlink(node))
+   }
+
+   // Point `min_freq` at the lowest bucket again if an unlink emptied it
+   fn find_min_freq(&mut self) {
+      if !self.buckets.contains_key(&self.min_freq) {
+         self.min_freq = self.buckets.keys().copied().min().unwrap_or(0);
+      }
+   }
+
+   // Unlink the entry and drop its bucket if it empties, even the one `min_freq` names
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      let freq = unsafe { node.as_ref().element.freq };
+      key_ref::forget(&mut self.map, node);
+      let bucket = self.buckets.get_mut(&freq).unwrap();
+      let item = bucket.remove_node(node);
+      if bucket.is_empty() {
+         self.buckets.remove(&freq);
+      }
+      item
+   }
+}
+
+// SAFETY: the map only points into the buckets, which the cache owns
+unsafe impl<K: Send, V: Send> Send for LFUCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LFUCache<K, V> {}
+
+impl<K: Hash + Eq, V> Default for LFUCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for LFUCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      self.bump(node);
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   /// Emptying the lowest bucket makes it O(number of distinct frequencies), like `pop_lfu`.
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      let item = self.unlink(node);
+      self.find_min_freq();
+      Some(item.value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      LFUCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      LFUCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      LFUCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      LFUCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self
+         .buckets
+         .values()
+         .flat_map(|bucket| bucket.iter())
+         .for_each(|item| f(&item.key, &item.value))
+   }
+}
+
+impl<K: Hash + Eq, V> CachePush<K, V> for LFUCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      LFUCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_leetcode_sequence() {
+      let mut cache = LFUCache::with_capacity(2);
+      cache.insert(1, 1);
+      cache.insert(2, 2);
+      assert_eq!(cache.get(&1), Some(&1));
+      // 2 has the lowest count
+      cache.insert(3, 3);
+      assert_eq!(cache.get(&2), None);
+      assert_eq!(cache.get(&3), Some(&3));
+      // 1 and 3 tie at two uses, 1 was used longer ago
+      cache.insert(4, 4);
+      assert_eq!(cache.get(&1), None);
+      assert_eq!(cache.get(&3), Some(&3));
+      assert_eq!(cache.get(&4), Some(&4));
+      cache.validate();
+      assert_eq!(cache.frequency(&3), Some(3));
+      assert_eq!(cache.frequency(&4), Some(2));
+   }
+
+   #[test]
+   fn test_random_against_model() {
+      // (key, value, uses, last use)
+      let mut model: Vec<(u32, u32, u32, u64)> = Vec::new();
+      let mut cache = LFUCache::with_capacity(8);
+      let mut seed = 17u32;
+      // keep the run short under Miri
+      let rounds = if cfg!(miri) { 300 } else { 3000 };
+      for tick in 0..rounds {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 20;
+         let pos = model.iter().position(|e| e.0 == k);
+         match (seed >> 8) % 3 {
+            0 => {
+               let expected = pos.map(|i| {
+                  model[i].2 += 1;
+                  model[i].3 = tick;
+                  model[i].1
+               });
+               assert_eq!(cache.get(&k).copied(), expected);
+            }
+            1 => {
+               let v = tick as u32;
+               let expected = match pos {
+                  Some(i) => {
+                     model[i].2 += 1;
+                     model[i].3 = tick;
+                     Some(mem::replace(&mut model[i].1, v))
+                  }
+                  None => {
+                     if model.len() == 8 {
+                        let victim = (0..model.len())
+                           .min_by_key(|&i| (model[i].2, model[i].3))
+                           .unwrap();
+                        model.swap_remove(victim);
+                     }
+                     model.push((k, v, 1, tick));
+                     None
+                  }
+               };
+               assert_eq!(cache.insert(k, v), expected);
+            }
+            _ => {
+               let expected = pos.map(|i| model.swap_remove(i).1);
+               assert_eq!(cache.remove(&k), expected);
+            }
+         }
+         cache.validate();
+         assert_eq!(cache.len(), model.len());
+         for e in &model {
+            assert_eq!(cache.frequency(&e.0), Some(e.2));
+         }
+      }
+   }
+
+   #[test]
+   fn test_pop_and_clear() {
+      let mut cache = LFUCache::unbounded();
+      for i in 0..5 {
+         cache.insert(i, i * 10);
+      }
+      for _ in 0..3 {
+         cache.get(&0);
+      }
+      cache.get(&4);
+      assert_eq!(cache.pop_lfu(), Some((1, 10)));
+      assert_eq!(cache.remove(&2), Some(20));
+      assert_eq!(cache.remove(&3), Some(30));
+      cache.validate();
+      // only the used entries are left
+      assert_eq!(cache.pop_lfu(), Some((4, 40)));
+      assert_eq!(cache.peek(&0), Some(&0));
+      assert_eq!(cache.frequency(&0), Some(4));
+      cache.validate();
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(cache.pop_lfu(), None);
+
+      let mut zero = LFUCache::with_capacity(0);
+      assert_eq!(zero.insert(1, 1), None);
+      assert!(zero.is_empty());
+   }
+}
@@ -1,12 +1,285 @@
+use std::borrow::Borrow;
 use std::hash::Hash;
 
-mod list;
+pub mod arc;
+pub mod clock;
+mod key_ref;
+pub mod lfu;
+pub mod lirs;
+pub mod list;
+pub mod loading;
 pub mod lru;
 pub mod lru_k;
+pub mod metered;
+pub mod mru;
+pub mod policy;
+pub mod random;
+pub mod slru;
+pub mod testing;
+pub mod tiny_lfu;
+pub mod two_q;
+pub mod two_tier;
+pub mod write;
 
+pub use arc::ArcCache;
+pub use clock::ClockCache;
+pub use lfu::LFUCache;
+pub use lirs::LirsCache;
+pub use list::List;
+pub use loading::LoadingCache;
+pub use lru::{LRUCache, PushResult};
+pub use lru_k::{EvictionPreference, LRUkCache, LruK, Segment};
+pub use metered::{CacheMetrics, MeteredCache};
+pub use mru::MruCache;
+pub use policy::{EvictionPolicy, FifoCache, PolicyCache};
+pub use random::RandomCache;
+pub use slru::SlruCache;
+pub use tiny_lfu::TinyLfuCache;
+pub use two_q::TwoQCache;
+pub use two_tier::TwoTierCache;
+pub use write::{WriteCache, WriteMode, WriteSink};
+
+/// The operations shared by every cache in this crate.
+///
+/// The trait is object safe, so caches picked at runtime can be held as `Box<dyn Cache<K, V>>`.
+/// Keep it that way: generic methods belong in an extension trait.
+///
+/// `&mut C` and `Box<C>` implement the trait by forwarding to `C`, so functions can take
+/// `impl Cache<K, V>` by value and callers still hand in borrows. With the trait in scope this
+/// changes method resolution on a `&mut` binding: a `&self` method is found on the forwarding
+/// impl before autoderef reaches the inherent method of the same name. On a cache keyed by
+/// `String` that means `r.contains_key("a")` expects a `&String` and fails to compile, while
+/// `(*r).contains_key("a")` calls the inherent, borrowing method:
+///
+/// ```
+/// use rs_lru::{Cache, LRUCache};
+///
+/// let mut cache: LRUCache<String, u32> = LRUCache::with_capacity(2);
+/// cache.insert(String::from("a"), 1);
+/// let r = &mut cache;
+/// assert!((*r).contains_key("a"));
+/// assert!(r.contains_key(&String::from("a")));
+/// ```
 pub trait Cache<K: Hash + Eq, V> {
    fn get(&mut self, k: &K) -> Option<&V>;
    fn insert(&mut self, k: K, v: V) -> Option<V>;
    fn remove(&mut self, k: &K) -> Option<V>;
-   fn is_emtpy(&self) -> bool;
+
+   /// Returns whether `k` is cached, without counting an access.
+   fn contains_key(&self, k: &K) -> bool;
+
+   /// Returns the number of cached entries.
+   fn len(&self) -> usize;
+
+   /// Returns the most entries the cache holds at once, `usize::MAX` for unbounded caches.
+   fn capacity(&self) -> usize;
+
+   /// Drops every entry.
+   fn clear(&mut self);
+
+   /// Calls `f` on every cached entry without counting an access. The order is up to the
+   /// implementation. Taking a `dyn` closure keeps the trait object safe.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V));
+
+   fn is_empty(&self) -> bool {
+      self.len() == 0
+   }
+
+   #[deprecated(note = "use `is_empty`")]
+   fn is_emtpy(&self) -> bool {
+      self.is_empty()
+   }
+}
+
+/// Combinators written purely in terms of [`Cache`], available on every implementation,
+/// including `dyn Cache<K, V>`.
+///
+/// ```
+/// use rs_lru::{CacheExt, LRUCache};
+///
+/// let mut cache = LRUCache::with_capacity(2);
+/// assert_eq!(cache.get_or_insert_with(1, || "one"), Some(&"one"));
+/// // a hit leaves the closure alone
+/// assert_eq!(cache.get_or_insert_with(1, || unreachable!()), Some(&"one"));
+/// ```
+pub trait CacheExt<K: Hash + Eq, V>: Cache<K, V> {
+   /// Returns the cached value, inserting the one `f` builds on a miss. Returns `None` if the
+   /// cache declined to keep the new entry, e.g. because its capacity is zero.
+   fn get_or_insert_with<F: FnOnce() -> V>(&mut self, k: K, f: F) -> Option<&V>
+   where
+      K: Clone,
+   {
+      if !self.contains_key(&k) {
+         self.insert(k.clone(), f());
+      }
+      self.get(&k)
+   }
+
+   /// Like [`get_or_insert_with`](CacheExt::get_or_insert_with), but the value is built
+   /// fallibly. An error is returned as is and leaves the cache untouched.
+   fn try_get_or_insert_with<E, F: FnOnce() -> Result<V, E>>(
+      &mut self,
+      k: K,
+      f: F,
+   ) -> Result<Option<&V>, E>
+   where
+      K: Clone,
+   {
+      if !self.contains_key(&k) {
+         self.insert(k.clone(), f()?);
+      }
+      Ok(self.get(&k))
+   }
+
+   /// Inserts every pair in order. Later pairs may evict earlier ones.
+   fn insert_all<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+      for (k, v) in entries {
+         self.insert(k, v);
+      }
+   }
+
+   /// Returns a clone of the cached value, counting the access like `get`.
+   fn get_cloned(&mut self, k: &K) -> Option<V>
+   where
+      V: Clone,
+   {
+      self.get(k).cloned()
+   }
+
+   /// Inserts the value and returns the one it replaced, the same as `insert`.
+   fn replace(&mut self, k: K, v: V) -> Option<V> {
+      self.insert(k, v)
+   }
+}
+
+impl<K: Hash + Eq, V, C: Cache<K, V> + ?Sized> CacheExt<K, V> for C {}
+
+/// Lookups by a borrowed form of the key, such as `&str` for a cache keyed by `String`, so
+/// generic code does not have to build an owned key per lookup. It is a separate trait, with the
+/// borrowed type as a parameter, to keep [`Cache`] object safe.
+///
+/// ```
+/// use rs_lru::{Cache, CacheLookup, LRUCache, LRUkCache};
+///
+/// fn total<C: CacheLookup<String, u64, str>>(cache: &mut C, words: &[&str]) -> u64 {
+///    words.iter().filter_map(|w| cache.get_borrowed(w).copied()).sum()
+/// }
+///
+/// let mut lru = LRUCache::with_capacity(4);
+/// let mut lru_k = LRUkCache::with_capacity_freq(4, 2);
+/// for (word, n) in [("a", 1), ("b", 2)] {
+///    lru.insert(String::from(word), n);
+///    lru_k.insert(String::from(word), n);
+/// }
+/// assert_eq!(total(&mut lru, &["a", "b", "c"]), 3);
+/// assert_eq!(total(&mut lru_k, &["b", "b"]), 4);
+/// assert_eq!(lru_k.remove_borrowed("b"), Some(2));
+/// assert!(!lru_k.contains_key_borrowed("b"));
+/// ```
+pub trait CacheLookup<K: Hash + Eq + Borrow<Q>, V, Q: Hash + Eq + ?Sized>: Cache<K, V> {
+   fn get_borrowed(&mut self, k: &Q) -> Option<&V>;
+   fn remove_borrowed(&mut self, k: &Q) -> Option<V>;
+   fn contains_key_borrowed(&self, k: &Q) -> bool;
+}
+
+/// Caches that hand back the entry an insert evicted, for adapters that move it somewhere else
+/// instead of dropping it.
+pub trait CachePush<K: Hash + Eq, V>: Cache<K, V> {
+   /// Inserts like [`Cache::insert`], reporting what happened. A new entry the cache declines to
+   /// keep comes back as [`PushResult::Evicted`].
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V>;
+}
+
+macro_rules! forward_cache {
+   () => {
+      fn get(&mut self, k: &K) -> Option<&V> {
+         (**self).get(k)
+      }
+
+      fn insert(&mut self, k: K, v: V) -> Option<V> {
+         (**self).insert(k, v)
+      }
+
+      fn remove(&mut self, k: &K) -> Option<V> {
+         (**self).remove(k)
+      }
+
+      fn contains_key(&self, k: &K) -> bool {
+         (**self).contains_key(k)
+      }
+
+      fn len(&self) -> usize {
+         (**self).len()
+      }
+
+      fn capacity(&self) -> usize {
+         (**self).capacity()
+      }
+
+      fn clear(&mut self) {
+         (**self).clear()
+      }
+
+      fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+         (**self).for_each_entry(f)
+      }
+
+      fn is_empty(&self) -> bool {
+         (**self).is_empty()
+      }
+   };
+}
+
+impl<K: Hash + Eq, V, C: Cache<K, V> + ?Sized> Cache<K, V> for Box<C> {
+   forward_cache!();
+}
+
+impl<K: Hash + Eq, V, C: Cache<K, V> + ?Sized> Cache<K, V> for &mut C {
+   forward_cache!();
+}
+
+macro_rules! forward_cache_lookup {
+   () => {
+      fn get_borrowed(&mut self, k: &Q) -> Option<&V> {
+         (**self).get_borrowed(k)
+      }
+
+      fn remove_borrowed(&mut self, k: &Q) -> Option<V> {
+         (**self).remove_borrowed(k)
+      }
+
+      fn contains_key_borrowed(&self, k: &Q) -> bool {
+         (**self).contains_key_borrowed(k)
+      }
+   };
+}
+
+impl<K, V, Q, C> CacheLookup<K, V, Q> for Box<C>
+where
+   K: Hash + Eq + Borrow<Q>,
+   Q: Hash + Eq + ?Sized,
+   C: CacheLookup<K, V, Q> + ?Sized,
+{
+   forward_cache_lookup!();
+}
+
+impl<K, V, Q, C> CacheLookup<K, V, Q> for &mut C
+where
+   K: Hash + Eq + Borrow<Q>,
+   Q: Hash + Eq + ?Sized,
+   C: CacheLookup<K, V, Q> + ?Sized,
+{
+   forward_cache_lookup!();
+}
+
+impl<K: Hash + Eq, V, C: CachePush<K, V> + ?Sized> CachePush<K, V> for Box<C> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      (**self).push(k, v)
+   }
+}
+
+impl<K: Hash + Eq, V, C: CachePush<K, V> + ?Sized> CachePush<K, V> for &mut C {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      (**self).push(k, v)
+   }
 }
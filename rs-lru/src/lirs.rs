@@ -0,0 +1,662 @@
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, NonNullNode};
+use crate::lru::{PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+   Lir,
+   // resident HIR
+   Hir,
+   // non-resident HIR, only its key is kept
+   Ghost,
+}
+
+// A position in the stack or one of the queues, pointing back at the entry
+type Link<K, V> = NonNullNode<NonNullNode<Item<K, V>>>;
+
+struct Item<K, V> {
+   key: K,
+   value: Option<V>,
+   state: State,
+   stack: Option<Link<K, V>>,
+   // the position in `queue` for resident HIR entries, in `ghosts` for non-resident ones
+   queue: Option<Link<K, V>>,
+}
+
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// A LIRS cache (Jiang and Zhang). Entries are ranked by their inter-reference recency, the
+/// number of other keys requested between their last two requests, rather than by recency alone.
+/// Keys with a low one hold the LIR status and are never evicted; the few slots left hold HIR
+/// entries in a FIFO queue, and only they are evicted. The stack S records recency over LIR
+/// entries, resident HIR entries and the keys of recently evicted HIR entries, and an HIR key
+/// requested again while still in S takes the LIR status from the least recent LIR entry.
+///
+/// Loops slightly larger than the cache, where LRU misses every time, keep most of their keys
+/// resident. S keeps at most `capacity()` non-resident keys.
+///
+/// ```
+/// use rs_lru::{Cache, LirsCache};
+///
+/// let mut cache = LirsCache::with_capacities(2, 1);
+/// let mut hits = 0;
+/// for _ in 0..10 {
+///    for k in 0..4 {
+///       match cache.get(&k) {
+///          Some(_) => hits += 1,
+///          None => {
+///             cache.insert(k, k);
+///          }
+///       }
+///    }
+/// }
+/// // an LRU cache of three entries would not hit once
+/// assert!(hits >= 18);
+/// ```
+pub struct LirsCache<K, V> {
+   map: KeyMap<Item<K, V>>,
+   // owns the entries, in no particular order
+   entries: List<Item<K, V>>,
+   // S, most recent first
+   stack: List<NonNullNode<Item<K, V>>>,
+   // Q, the resident HIR entries, the back is evicted first
+   queue: List<NonNullNode<Item<K, V>>>,
+   // the non-resident entries, oldest at the back
+   ghosts: List<NonNullNode<Item<K, V>>>,
+   lir_len: usize,
+   lir_cap: usize,
+   hir_cap: usize,
+}
+
+impl<K: Hash + Eq, V> LirsCache<K, V> {
+   /// Creates a cache holding at most `cap` entries, 1% of them (at least one) for HIR entries.
+   /// A capacity of zero caches nothing: every new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      let hir = if cap == 0 { 0 } else { (cap / 100).max(1) };
+      Self::with_capacities(cap - hir, hir)
+   }
+
+   /// Creates a cache holding at most `lir` LIR entries and `hir` resident HIR entries.
+   ///
+   /// # Panics
+   ///
+   /// Panics if `hir` is zero while `lir` is not: a miss needs an HIR slot to land in.
+   pub fn with_capacities(lir: usize, hir: usize) -> Self {
+      assert!(
+         hir > 0 || lir == 0,
+         "hir capacity must be at least one, got lir {} and hir {}",
+         lir,
+         hir
+      );
+      Self {
+         map: HashMap::new(),
+         entries: List::new(),
+         stack: List::new(),
+         queue: List::new(),
+         ghosts: List::new(),
+         lir_len: 0,
+         lir_cap: lir,
+         hir_cap: hir,
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.lir_cap + self.hir_cap
+   }
+
+   /// Returns the (LIR, HIR) capacities.
+   pub fn capacities(&self) -> (usize, usize) {
+      (self.lir_cap, self.hir_cap)
+   }
+
+   /// Returns the number of resident entries.
+   pub fn len(&self) -> usize {
+      self.lir_len + self.queue.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.len() == 0
+   }
+
+   pub fn lir_len(&self) -> usize {
+      self.lir_len
+   }
+
+   pub fn hir_len(&self) -> usize {
+      self.queue.len()
+   }
+
+   /// Returns the number of entries in the stack S, non-resident ones included.
+   pub fn stack_len(&self) -> usize {
+      self.stack.len()
+   }
+
+   /// Returns the number of non-resident keys remembered.
+   pub fn ghost_len(&self) -> usize {
+      self.ghosts.len()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.resident(k).is_some()
+   }
+
+   /// Returns whether the entry holds the LIR status, `None` if it is not resident.
+   pub fn is_lir(&self, k: &K) -> Option<bool> {
+      let node = self.resident(k)?;
+      Some(unsafe { node.as_ref().element.state } == State::Lir)
+   }
+
+   /// Returns the value without counting a request.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      let node = self.resident(k)?;
+      unsafe { node.as_ref().element.value.as_ref() }
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. If S still holds the
+   /// victim, a copy of its key stays behind. A cache with a capacity of zero hands back every
+   /// new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V>
+   where
+      K: Clone,
+   {
+      if let Some(node) = self.resident(&k) {
+         return PushResult::Replaced(self.overwrite(node, v));
+      }
+      if self.capacity() == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let victim = self
+         .hir_victim()
+         .map(|node| unsafe { node.as_ref().element.key.clone() });
+      match victim.zip(self.admit(k, v)) {
+         Some((key, value)) => PushResult::Evicted(key, value),
+         None => PushResult::Fit,
+      }
+   }
+
+   /// Drops every entry and the remembered keys.
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.stack.clear();
+      self.queue.clear();
+      self.ghosts.clear();
+      self.entries.clear();
+      self.lir_len = 0;
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      for list in [&self.stack, &self.queue, &self.ghosts] {
+         list.validate();
+      }
+      self.entries.validate();
+      assert_eq!(self.map.len(), self.entries.len());
+      assert!(self.lir_len <= self.lir_cap);
+      assert!(self.queue.len() <= self.hir_cap);
+      assert!(self.ghosts.len() <= self.capacity());
+      let mut lir = 0;
+      let mut cur = self.entries.begin_node();
+      while let Some(node) = cur {
+         let item = unsafe { &node.as_ref().element };
+         assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+         match item.state {
+            State::Lir => {
+               lir += 1;
+               assert!(item.stack.is_some() && item.queue.is_none());
+            }
+            State::Hir => assert!(item.queue.is_some()),
+            State::Ghost => assert!(item.stack.is_some() && item.queue.is_some()),
+         }
+         assert_eq!(item.value.is_none(), item.state == State::Ghost);
+         cur = self.entries.next_node(node);
+      }
+      assert_eq!(lir, self.lir_len);
+      // every position points at an entry that points back at it
+      let lists = [
+         (&self.stack, None),
+         (&self.queue, Some(State::Hir)),
+         (&self.ghosts, Some(State::Ghost)),
+      ];
+      for (list, state) in lists {
+         let mut cur = list.begin_node();
+         while let Some(link) = cur {
+            let item = unsafe { &link.as_ref().element.as_ref().element };
+            match state {
+               None => assert_eq!(item.stack, Some(link)),
+               Some(state) => {
+                  assert_eq!(item.state, state);
+                  assert_eq!(item.queue, Some(link));
+               }
+            }
+            cur = list.next_node(link);
+         }
+      }
+      if let Some(bottom) = self.stack.end_node() {
+         let item = unsafe { &bottom.as_ref().element.as_ref().element };
+         assert_eq!(item.state, State::Lir, "the bottom of S must be LIR");
+      }
+   }
+
+   fn resident(&self, k: &K) -> Option<NonNullNode<Item<K, V>>> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      match unsafe { node.as_ref().element.state } {
+         State::Ghost => None,
+         _ => Some(node),
+      }
+   }
+
+   // A request for a resident entry
+   fn touch(&mut self, node: NonNullNode<Item<K, V>>) {
+      let (state, stack) = unsafe { (node.as_ref().element.state, node.as_ref().element.stack) };
+      match (state, stack) {
+         (State::Lir, _) => {
+            let bottom = self.stack.end_node() == stack;
+            self.stack_to_top(node);
+            if bottom {
+               self.prune();
+            }
+         }
+         // its last two requests were closer than the least recent LIR entry's, or the LIR set
+         // has room after a removal
+         (State::Hir, Some(_)) => self.promote(node),
+         (State::Hir, None) if self.lir_len < self.lir_cap => self.promote(node),
+         (State::Hir, None) => {
+            self.stack_to_top(node);
+            let link = unsafe { node.as_ref().element.queue.unwrap() };
+            self.queue.move_to_front(link);
+            // without any LIR entry below it, it is the bottom of S
+            self.prune();
+         }
+         (State::Ghost, _) => unreachable!("touched a non-resident entry"),
+      }
+   }
+
+   // Give an HIR entry the LIR status, handing it over from the least recent LIR entry when the
+   // LIR set is full
+   fn promote(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      self.dequeue(node);
+      unsafe { node.as_mut().element.state = State::Lir };
+      self.lir_len += 1;
+      self.stack_to_top(node);
+      if self.lir_len > self.lir_cap {
+         self.prune();
+         let mut bottom = unsafe { self.stack.end_node().unwrap().as_ref().element };
+         self.leave_stack(bottom);
+         unsafe { bottom.as_mut().element.state = State::Hir };
+         self.lir_len -= 1;
+         self.enqueue(bottom);
+         self.prune();
+      }
+   }
+
+   // Pop HIR entries off the bottom of S until an LIR entry is there, forgetting the
+   // non-resident ones
+   fn prune(&mut self) {
+      while let Some(link) = self.stack.end_node() {
+         let node = unsafe { link.as_ref().element };
+         match unsafe { node.as_ref().element.state } {
+            State::Lir => break,
+            State::Hir => self.leave_stack(node),
+            State::Ghost => {
+               self.unlink(node);
+            }
+         }
+      }
+   }
+
+   fn overwrite(&mut self, mut node: NonNullNode<Item<K, V>>, v: V) -> V {
+      let ret = unsafe { node.as_mut().element.value.replace(v) };
+      self.touch(node);
+      ret.expect("a resident entry holds a value")
+   }
+
+   // A miss: make room and link the key, or bring its ghost back. Returns the value of the entry
+   // evicted for it.
+   fn admit(&mut self, k: K, v: V) -> Option<V> {
+      let cold = self.lir_len < self.lir_cap;
+      // may forget `k` itself if it is the oldest ghost, it is new then
+      let evicted = self.hir_victim().map(|node| self.evict_hir(node));
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         let mut node = node;
+         unsafe { node.as_mut().element.value = Some(v) };
+         if cold {
+            self.dequeue(node);
+            unsafe { node.as_mut().element.state = State::Lir };
+            self.lir_len += 1;
+            self.stack_to_top(node);
+         } else {
+            self.promote(node);
+         }
+         return evicted;
+      }
+      self.entries.push_front(Item {
+         key: k,
+         value: Some(v),
+         state: if cold { State::Lir } else { State::Hir },
+         stack: None,
+         queue: None,
+      });
+      let node = self.entries.begin_node().unwrap();
+      self.map.insert(KeyRef(node), node);
+      if cold {
+         self.lir_len += 1;
+      }
+      self.stack_to_top(node);
+      self.enqueue(node);
+      self.prune();
+      evicted
+   }
+
+   // The entry a miss evicts: the back of Q, once the LIR set and Q are full
+   fn hir_victim(&self) -> Option<NonNullNode<Item<K, V>>> {
+      if self.lir_len < self.lir_cap || self.queue.len() < self.hir_cap {
+         return None;
+      }
+      let link = self.queue.end_node()?;
+      Some(unsafe { link.as_ref().element })
+   }
+
+   // Evict a resident HIR entry, remembering its key if it is still in S, and return its value
+   fn evict_hir(&mut self, mut node: NonNullNode<Item<K, V>>) -> V {
+      if unsafe { node.as_ref().element.stack.is_none() } {
+         return self.unlink(node).value.unwrap();
+      }
+      self.dequeue(node);
+      let value = unsafe {
+         node.as_mut().element.state = State::Ghost;
+         node.as_mut().element.value.take()
+      };
+      self.enqueue(node);
+      if self.ghosts.len() > self.capacity() {
+         let oldest = unsafe { self.ghosts.end_node().unwrap().as_ref().element };
+         self.unlink(oldest);
+      }
+      value.unwrap()
+   }
+
+   fn stack_to_top(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      match unsafe { node.as_ref().element.stack } {
+         Some(link) => self.stack.move_to_front(link),
+         None => {
+            self.stack.push_front(node);
+            unsafe { node.as_mut().element.stack = self.stack.begin_node() };
+         }
+      }
+   }
+
+   fn leave_stack(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      if let Some(link) = unsafe { node.as_mut().element.stack.take() } {
+         self.stack.remove_node(link);
+      }
+   }
+
+   // Push the entry to the front of Q or of the ghosts, depending on its state
+   fn enqueue(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      let list = match unsafe { node.as_ref().element.state } {
+         State::Hir => &mut self.queue,
+         State::Ghost => &mut self.ghosts,
+         State::Lir => return,
+      };
+      list.push_front(node);
+      let link = list.begin_node();
+      unsafe { node.as_mut().element.queue = link };
+   }
+
+   fn dequeue(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      let Some(link) = (unsafe { node.as_mut().element.queue.take() }) else {
+         return;
+      };
+      match unsafe { node.as_ref().element.state } {
+         State::Hir => self.queue.remove_node(link),
+         _ => self.ghosts.remove_node(link),
+      };
+   }
+
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      self.dequeue(node);
+      self.leave_stack(node);
+      if unsafe { node.as_ref().element.state } == State::Lir {
+         self.lir_len -= 1;
+      }
+      key_ref::forget(&mut self.map, node);
+      self.entries.remove_node(node)
+   }
+}
+
+// SAFETY: the map, the stack and the queues only point into `entries`, owned by the cache
+unsafe impl<K: Send, V: Send> Send for LirsCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LirsCache<K, V> {}
+
+impl<K: Hash + Eq, V> Default for LirsCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for LirsCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let node = self.resident(k)?;
+      self.touch(node);
+      unsafe { node.as_ref().element.value.as_ref() }
+   }
+
+   /// Replacing the value of a resident key counts as a request for it. Inserting a new key is
+   /// a miss: it evicts the back of Q once the cache is full.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      if let Some(node) = self.resident(&k) {
+         return Some(self.overwrite(node, v));
+      }
+      if self.capacity() == 0 {
+         return None;
+      }
+      self.admit(k, v);
+      None
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let node = self.resident(k)?;
+      let item = self.unlink(node);
+      self.prune();
+      item.value
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      LirsCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      LirsCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      LirsCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      LirsCache::clear(self)
+   }
+
+   /// Ghost entries are skipped.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      for item in self.entries.iter() {
+         if let Some(value) = &item.value {
+            f(&item.key, value);
+         }
+      }
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for LirsCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      LirsCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   // Requests `k`, loading it on a miss, and reports whether it hit
+   fn access(cache: &mut LirsCache<char, u32>, k: char) -> bool {
+      if cache.get(&k).is_some() {
+         return true;
+      }
+      cache.insert(k, k as u32);
+      false
+   }
+
+   // The keys of S from top to bottom, non-resident ones in lowercase
+   fn stack(cache: &LirsCache<char, u32>) -> String {
+      cache
+         .stack
+         .iter()
+         .map(|node| {
+            let item = unsafe { &node.as_ref().element };
+            match item.state {
+               State::Ghost => item.key.to_ascii_lowercase(),
+               _ => item.key,
+            }
+         })
+         .collect()
+   }
+
+   fn queue(cache: &LirsCache<char, u32>) -> String {
+      let keys = cache.queue.iter();
+      keys
+         .map(|node| unsafe { node.as_ref().element.key })
+         .collect()
+   }
+
+   #[test]
+   fn test_paper_trace() {
+      // the setting of the paper's walkthrough: two LIR slots and one HIR slot
+      let mut cache = LirsCache::with_capacities(2, 1);
+      let steps = [
+         // the LIR set fills first
+         ('A', false, "A", ""),
+         ('D', false, "DA", ""),
+         // then misses become resident HIR entries
+         ('B', false, "BDA", "B"),
+         // and push out the back of Q, which stays in S as a non-resident entry
+         ('C', false, "CbDA", "C"),
+         // b comes back while in S: it takes the LIR status from A at the bottom
+         ('B', false, "BcD", "A"),
+         // a resident HIR entry out of S stays HIR, but enters S again
+         ('A', true, "ABcD", "A"),
+         // D is the bottom: moving it up prunes c off the bottom of S
+         ('D', true, "DAB", "A"),
+         // A in S again is promoted, B is demoted and pruned
+         ('A', true, "AD", "B"),
+         ('E', false, "EAD", "E"),
+         ('B', false, "BeAD", "B"),
+      ];
+      for (i, (k, hit, s, q)) in steps.into_iter().enumerate() {
+         assert_eq!(access(&mut cache, k), hit, "step {}", i + 1);
+         cache.validate();
+         assert_eq!(stack(&cache), s, "S after step {}", i + 1);
+         assert_eq!(queue(&cache), q, "Q after step {}", i + 1);
+      }
+      assert_eq!(cache.is_lir(&'A'), Some(true));
+      assert_eq!(cache.is_lir(&'E'), None);
+      assert_eq!(
+         (cache.lir_len(), cache.hir_len(), cache.ghost_len()),
+         (2, 1, 1)
+      );
+   }
+
+   #[test]
+   fn test_loop() {
+      // a loop one key larger than the cache: LRU misses every request
+      let mut cache = LirsCache::with_capacity(10);
+      let mut lru = crate::LRUCache::with_capacity(10);
+      let (mut hits, mut lru_hits) = (0, 0);
+      for _ in 0..10 {
+         for k in 0..11 {
+            if cache.get(&k).is_some() {
+               hits += 1;
+            } else {
+               cache.insert(k, k);
+            }
+            if lru.get(&k).is_some() {
+               lru_hits += 1;
+            } else {
+               lru.insert(k, k);
+            }
+            cache.validate();
+         }
+      }
+      assert_eq!(lru_hits, 0);
+      // the nine LIR entries hit on every pass after the first
+      assert!(hits >= 9 * 9, "{} hits", hits);
+   }
+
+   #[test]
+   fn test_random_invariants() {
+      let rounds = if cfg!(miri) { 300 } else { 20000 };
+      for (lir, hir) in [(1, 1), (3, 2), (8, 1), (0, 1)] {
+         let mut cache = LirsCache::with_capacities(lir, hir);
+         let mut seed = 7u32;
+         for _ in 0..rounds {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let k = (seed >> 16) % 24;
+            match (seed >> 8) % 8 {
+               0..=3 => {
+                  if cache.get(&k).is_none() {
+                     cache.insert(k, k);
+                  }
+               }
+               4 | 5 => {
+                  cache.insert(k, k + 1);
+               }
+               6 => {
+                  cache.remove(&k);
+               }
+               _ => assert_eq!(cache.get(&k).is_some(), cache.contains_key(&k)),
+            }
+            assert!(cache.len() <= cache.capacity());
+            cache.validate();
+         }
+      }
+   }
+
+   #[test]
+   fn test_remove_and_clear() {
+      let mut cache = LirsCache::with_capacities(2, 1);
+      for k in ['A', 'B', 'C', 'D'] {
+         access(&mut cache, k);
+      }
+      assert_eq!(stack(&cache), "DcBA");
+      // removing the bottom LIR entry prunes S down to B
+      assert_eq!(cache.remove(&'A'), Some('A' as u32));
+      cache.validate();
+      assert_eq!(stack(&cache), "DcB");
+      // non-resident keys cannot be removed, nor found
+      assert_eq!(cache.remove(&'C'), None);
+      assert_eq!(cache.peek(&'C'), None);
+      // the LIR set has room again, so the next miss is LIR right away
+      assert!(!access(&mut cache, 'E'));
+      assert_eq!(cache.is_lir(&'E'), Some(true));
+      assert_eq!(cache.insert('E', 0), Some('E' as u32));
+      cache.clear();
+      cache.validate();
+      assert!(cache.is_empty());
+      assert_eq!(cache.stack_len(), 0);
+      assert_eq!(LirsCache::with_capacity(0).insert(1, 1), None);
+   }
+
+   #[test]
+   #[should_panic(expected = "hir capacity must be at least one")]
+   fn test_no_hir_slot() {
+      LirsCache::<u8, u8>::with_capacities(4, 0);
+   }
+}
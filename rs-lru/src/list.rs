@@ -1,6 +1,19 @@
+//! A doubly linked list whose nodes never move once allocated.
+//!
+//! Besides the usual collection API, the crate's caches keep raw node handles (`NonNullNode`)
+//! in their maps and relink those nodes in O(1). The handle based functions are crate-private:
+//! they trust the caller that every handle is a live node of the list it is passed to, which is
+//! what the caches guarantee by removing a node from their maps before freeing it. Every
+//! `unsafe` block below relies on that invariant plus the list's own: `head`, `tail`, `len` and
+//! the `prev`/`next` links always describe the same chain of `Box`-allocated nodes owned by the
+//! list.
+
 #![allow(dead_code)]
 
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
 use std::marker::PhantomData;
+use std::mem;
 use std::ptr::NonNull;
 
 pub(crate) type NonNullNode<T> = NonNull<Node<T>>;
@@ -21,7 +34,36 @@ impl<T> Node<T> {
    }
 }
 
-pub(crate) struct List<T> {
+/// A doubly linked list.
+///
+/// ```
+/// use rs_lru::List;
+///
+/// let mut list: List<i32> = (1..=3).collect();
+/// list.push_front(0);
+/// if let Some(back) = list.back_mut() {
+///    *back = 30;
+/// }
+/// assert_eq!(list.pop_back(), Some(30));
+/// assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+///
+/// let mut tail = list.split_off(1);
+/// tail.push_back(3);
+/// list.append(&mut tail);
+/// assert_eq!(format!("{:?}", list), "[0, 1, 2, 3]");
+/// ```
+///
+/// The list is `Send` and `Sync` whenever `T` is, so it cannot smuggle a non-thread-safe element
+/// to another thread:
+///
+/// ```compile_fail
+/// use rs_lru::List;
+/// use std::rc::Rc;
+///
+/// let list: List<Rc<i32>> = List::new();
+/// std::thread::spawn(move || drop(list));
+/// ```
+pub struct List<T> {
    head: Option<NonNullNode<T>>,
    tail: Option<NonNullNode<T>>,
    len: usize,
@@ -51,6 +93,7 @@ impl<T> List<T> {
             self.tail = Some(node);
          }
          Some(mut tail) => {
+            // SAFETY: `node` was just allocated and `tail` is a live node of this list
             unsafe {
                node.as_mut().prev = Some(tail);
                tail.as_mut().next = Some(node);
@@ -70,6 +113,7 @@ impl<T> List<T> {
             self.tail = Some(node);
          }
          Some(mut head) => {
+            // SAFETY: `node` was just allocated and `head` is a live node of this list
             unsafe {
                node.as_mut().next = Some(head);
                head.as_mut().prev = Some(node);
@@ -82,11 +126,11 @@ impl<T> List<T> {
 
    pub fn pop_front(&mut self) -> Option<T> {
       if let Some(e) = self.head {
-         let ele = unsafe {
-            let node_guard = Box::from_raw(e.as_ptr());
-            self.head = e.as_ref().next;
-            node_guard.element
-         };
+         // SAFETY: the head is a live node owned by this list, reclaiming its box unlinks it
+         // the link is read through the box, once it owns the node the old pointer is stale
+         let node = unsafe { Box::from_raw(e.as_ptr()) };
+         self.head = node.next;
+         let ele = node.element;
          // Prevent dangling pointer
          self.check_head();
          self.len -= 1;
@@ -97,11 +141,11 @@ impl<T> List<T> {
 
    pub fn pop_back(&mut self) -> Option<T> {
       if let Some(e) = self.tail {
-         let ele = unsafe {
-            let node_guard = Box::from_raw(e.as_ptr());
-            self.tail = e.as_ref().prev;
-            node_guard.element
-         };
+         // SAFETY: the tail is a live node owned by this list, reclaiming its box unlinks it
+         // the link is read through the box, once it owns the node the old pointer is stale
+         let node = unsafe { Box::from_raw(e.as_ptr()) };
+         self.tail = node.prev;
+         let ele = node.element;
          // Prevent dangling pointer
          self.check_tail();
          self.len -= 1;
@@ -114,37 +158,419 @@ impl<T> List<T> {
       self.len
    }
 
-   pub fn begin_node(&self) -> Option<NonNullNode<T>> {
+   /// Drops every element.
+   pub fn clear(&mut self) {
+      while self.pop_back().is_some() {}
+   }
+
+   /// Moves every element of `other` to the back of this list in O(1), leaving `other` empty.
+   /// Appending a list to itself cannot be expressed, the two borrows would alias.
+   pub fn append(&mut self, other: &mut List<T>) {
+      match (self.tail, other.head) {
+         (None, _) => mem::swap(self, other),
+         (Some(_), None) => {}
+         (Some(mut tail), Some(mut head)) => {
+            // SAFETY: both ends are live nodes, `other` gives up its chain below
+            unsafe {
+               tail.as_mut().next = Some(head);
+               head.as_mut().prev = Some(tail);
+            }
+            self.tail = other.tail.take();
+            other.head = None;
+            self.len += mem::replace(&mut other.len, 0);
+         }
+      }
+   }
+
+   /// Splits the list in two at `at`: the returned list holds the elements from index `at` on.
+   /// Walks from whichever end is closer to the split point.
+   ///
+   /// # Panics
+   ///
+   /// Panics if `at > len`.
+   pub fn split_off(&mut self, at: usize) -> List<T> {
+      assert!(at <= self.len, "cannot split off at a nonexistent index");
+      if at == 0 {
+         return mem::take(self);
+      }
+      if at == self.len {
+         return List::new();
+      }
+      let node = self.node_at(at).unwrap();
+      self.split_before(node, self.len - at)
+   }
+
+   pub fn get(&self, idx: usize) -> Option<&T> {
+      let node = self.node_at(idx)?;
+      // SAFETY: the node is live for as long as `self` is borrowed
+      unsafe { Some(&(*node.as_ptr()).element) }
+   }
+
+   pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+      let node = self.node_at(idx)?;
+      // SAFETY: the node is live and `self` is borrowed mutably
+      unsafe { Some(&mut (*node.as_ptr()).element) }
+   }
+
+   /// Inserts `ele` so that it ends up at index `idx`.
+   ///
+   /// # Panics
+   ///
+   /// Panics if `idx > len`.
+   pub fn insert_at(&mut self, idx: usize, ele: T) {
+      assert!(idx <= self.len, "insertion index is out of bounds");
+      match self.node_at(idx) {
+         Some(node) => {
+            self.insert_before(node, ele);
+         }
+         None => self.push_back(ele),
+      }
+   }
+
+   pub fn remove_at(&mut self, idx: usize) -> Option<T> {
+      let node = self.node_at(idx)?;
+      Some(self.remove_node(node))
+   }
+
+   // The node at `idx`, walking from whichever end is closer
+   fn node_at(&self, idx: usize) -> Option<NonNullNode<T>> {
+      if idx >= self.len {
+         return None;
+      }
+      if idx <= self.len / 2 {
+         let mut node = self.head?;
+         for _ in 0..idx {
+            node = self.next_node(node)?;
+         }
+         Some(node)
+      } else {
+         let mut node = self.tail?;
+         for _ in idx + 1..self.len {
+            node = self.prev_node(node)?;
+         }
+         Some(node)
+      }
+   }
+
+   /// Splits off `node` and everything after it into a new list. Counting the moved elements
+   /// walks them, so this is O(moved).
+   pub(crate) fn split_off_at_node(&mut self, node: NonNullNode<T>) -> List<T> {
+      let mut moved = 1;
+      let mut cur = node;
+      while let Some(next) = self.next_node(cur) {
+         moved += 1;
+         cur = next;
+      }
+      self.split_before(node, moved)
+   }
+
+   // Cut the links in front of `node`, which starts a suffix of `moved` elements
+   fn split_before(&mut self, mut node: NonNullNode<T>, moved: usize) -> List<T> {
+      // SAFETY: `node` and its predecessor are live nodes of this list
+      let prev = unsafe { node.as_mut().prev.take() };
+      match prev {
+         Some(mut prev) => unsafe { prev.as_mut().next = None },
+         None => self.head = None,
+      }
+      let split = List {
+         head: Some(node),
+         tail: mem::replace(&mut self.tail, prev),
+         len: moved,
+         marker: PhantomData,
+      };
+      self.len -= moved;
+      split
+   }
+
+   /// Keeps only the elements accepted by `f`, visiting them front to back.
+   pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+      self.extract_if(|ele| !f(ele)).for_each(drop);
+   }
+
+   /// Removes consecutive elements that `same` reports as duplicates, like `Vec::dedup_by`:
+   /// `same(a, b)` is passed the later element `a` and the earlier element `b` it is compared
+   /// against, and `a` is dropped when it returns true.
+   pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same: F) {
+      let Some(mut kept) = self.head else {
+         return;
+      };
+      let mut cur = self.next_node(kept);
+      while let Some(node) = cur {
+         cur = self.next_node(node);
+         // SAFETY: two distinct live nodes of this list, borrowed only for the call
+         let dup = unsafe { same(&mut (*node.as_ptr()).element, &mut (*kept.as_ptr()).element) };
+         if dup {
+            drop(self.remove_node(node));
+         } else {
+            kept = node;
+         }
+      }
+   }
+
+   /// Removes consecutive equal elements, keeping the first of each run.
+   pub fn dedup(&mut self)
+   where
+      T: PartialEq,
+   {
+      self.dedup_by(|a, b| a == b);
+   }
+
+   /// Lazily removes and yields the elements accepted by `f`, front to back. As with std, the
+   /// elements not yet visited stay in the list when the iterator is dropped early.
+   pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> ExtractIf<'_, T, F> {
+      ExtractIf {
+         cur: self.head,
+         list: self,
+         pred: f,
+      }
+   }
+
+   /// Drops every element past the first `len`. Does nothing if the list is not longer than that.
+   pub fn truncate(&mut self, len: usize) {
+      if len < self.len {
+         drop(self.split_off(len));
+      }
+   }
+
+   /// Removes up to `n` elements from the front, yielding them front to back. Whatever the
+   /// iterator did not yield is still removed when it is dropped.
+   pub fn drain_front(&mut self, n: usize) -> DrainFront<'_, T> {
+      DrainFront {
+         remaining: n.min(self.len),
+         list: self,
+      }
+   }
+
+   /// Removes up to `n` elements from the back, yielding them back to front. Whatever the
+   /// iterator did not yield is still removed when it is dropped.
+   pub fn drain_back(&mut self, n: usize) -> DrainBack<'_, T> {
+      DrainBack {
+         remaining: n.min(self.len),
+         list: self,
+      }
+   }
+
+   /// Moves every element of `other` to the front of this list in O(1), leaving `other` empty.
+   pub fn prepend(&mut self, other: &mut List<T>) {
+      other.append(self);
+      mem::swap(self, other);
+   }
+
+   /// Reverses the order of the elements in place by swapping every node's links, O(n) and
+   /// without allocating. Node handles stay valid.
+   pub fn reverse(&mut self) {
+      let mut cur = self.head;
+      while let Some(mut node) = cur {
+         // SAFETY: every node on the chain is live and owned by this list
+         unsafe {
+            let node = node.as_mut();
+            mem::swap(&mut node.prev, &mut node.next);
+            // the old `next` now sits in `prev`
+            cur = node.prev;
+         }
+      }
+      mem::swap(&mut self.head, &mut self.tail);
+   }
+
+   /// Moves the first `n` elements to the back, keeping their order. `n` wraps around modulo
+   /// `len`, so rotating by `len` or any multiple of it is a no-op. Walks at most half the list
+   /// and never allocates.
+   pub fn rotate_left(&mut self, n: usize) {
+      if self.len == 0 {
+         return;
+      }
+      let n = n % self.len;
+      if n != 0 {
+         let mut back = self.split_off(n);
+         self.prepend(&mut back);
+      }
+   }
+
+   /// Moves the last `n` elements to the front, keeping their order. Wraps like `rotate_left`.
+   pub fn rotate_right(&mut self, n: usize) {
+      if self.len == 0 {
+         return;
+      }
+      self.rotate_left(self.len - n % self.len);
+   }
+
+   /// Sorts the list with a stable bottom-up merge sort, O(n log n) comparisons. Nodes are
+   /// relinked in place, so no element moves in memory and node handles stay valid.
+   pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut cmp: F) {
+      if self.len < 2 {
+         return;
+      }
+      let mut width = 1;
+      loop {
+         // merge neighbouring runs of `width` nodes, following only the `next` links of the
+         // unmerged part and rebuilding both links of the merged part behind it
+         let mut rest = self.head.take();
+         let mut tail: Option<NonNullNode<T>> = None;
+         let mut merges = 0;
+         while let Some(start) = rest {
+            merges += 1;
+            let mut left = Some(start);
+            let mut left_len = 0;
+            let mut right = Some(start);
+            while left_len < width {
+               left_len += 1;
+               right = right.and_then(|node| self.next_node(node));
+               if right.is_none() {
+                  break;
+               }
+            }
+            let mut right_len = width;
+            while left_len > 0 || (right_len > 0 && right.is_some()) {
+               let take_left = match (left, right) {
+                  _ if left_len == 0 => false,
+                  (Some(l), Some(r)) if right_len > 0 => {
+                     // SAFETY: both runs only hold live nodes of this list
+                     let (l, r) = unsafe { (&l.as_ref().element, &r.as_ref().element) };
+                     cmp(l, r) != Ordering::Greater
+                  }
+                  _ => true,
+               };
+               let mut node = if take_left {
+                  left_len -= 1;
+                  let node = left.unwrap();
+                  left = self.next_node(node);
+                  node
+               } else {
+                  right_len -= 1;
+                  let node = right.unwrap();
+                  right = self.next_node(node);
+                  node
+               };
+               // SAFETY: `node` and `tail` are live, and the successor of `node` was read above
+               unsafe {
+                  match tail {
+                     Some(mut tail) => tail.as_mut().next = Some(node),
+                     None => self.head = Some(node),
+                  }
+                  node.as_mut().prev = tail;
+               }
+               tail = Some(node);
+            }
+            rest = right;
+         }
+         // SAFETY: the merged chain ends in a live node
+         unsafe { tail.unwrap().as_mut().next = None };
+         self.tail = tail;
+         if merges <= 1 {
+            return;
+         }
+         width *= 2;
+      }
+   }
+
+   /// Sorts the list by the key `f` extracts, stable like `sort_by`.
+   pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+      self.sort_by(|a, b| f(a).cmp(&f(b)));
+   }
+
+   pub(crate) fn begin_node(&self) -> Option<NonNullNode<T>> {
       self.head
    }
 
-   pub fn end_node(&self) -> Option<NonNullNode<T>> {
+   pub(crate) fn end_node(&self) -> Option<NonNullNode<T>> {
       self.tail
    }
 
+   pub(crate) fn next_node(&self, node: NonNullNode<T>) -> Option<NonNullNode<T>> {
+      // SAFETY: the caller passes a live node of this list
+      unsafe { node.as_ref().next }
+   }
+
+   pub(crate) fn prev_node(&self, node: NonNullNode<T>) -> Option<NonNullNode<T>> {
+      // SAFETY: the caller passes a live node of this list
+      unsafe { node.as_ref().prev }
+   }
+
    pub fn front(&self) -> Option<&T> {
       let node = self.begin_node()?;
+      // SAFETY: the head is live for as long as `self` is borrowed
       unsafe { Some(&node.as_ref().element) }
    }
 
    pub fn back(&self) -> Option<&T> {
       let node = self.end_node()?;
+      // SAFETY: the tail is live for as long as `self` is borrowed
       unsafe { Some(&node.as_ref().element) }
    }
 
-   pub fn splice_back(
+   pub fn front_mut(&mut self) -> Option<&mut T> {
+      let mut node = self.begin_node()?;
+      // SAFETY: the head is live and `self` is borrowed mutably
+      unsafe { Some(&mut node.as_mut().element) }
+   }
+
+   pub fn back_mut(&mut self) -> Option<&mut T> {
+      let mut node = self.end_node()?;
+      // SAFETY: the tail is live and `self` is borrowed mutably
+      unsafe { Some(&mut node.as_mut().element) }
+   }
+
+   pub fn iter(&self) -> Iter<'_, T> {
+      Iter {
+         head: self.head,
+         tail: self.tail,
+         len: self.len,
+         marker: PhantomData,
+      }
+   }
+
+   /// A read-only cursor at the front element, or at the "ghost" position if the list is empty.
+   pub fn cursor_front(&self) -> Cursor<'_, T> {
+      Cursor {
+         current: self.head,
+         list: self,
+      }
+   }
+
+   pub fn cursor_back(&self) -> Cursor<'_, T> {
+      Cursor {
+         current: self.tail,
+         list: self,
+      }
+   }
+
+   /// A cursor at the front element that can edit the list around it.
+   pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+      CursorMut {
+         current: self.head,
+         list: self,
+      }
+   }
+
+   pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+      CursorMut {
+         current: self.tail,
+         list: self,
+      }
+   }
+
+   pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+      IterMut {
+         head: self.head,
+         tail: self.tail,
+         len: self.len,
+         marker: PhantomData,
+      }
+   }
+
+   pub(crate) fn splice_back(
       &mut self,
       dst_node: Option<NonNullNode<T>>,
       src: &mut List<T>,
       src_node: NonNullNode<T>,
    ) {
       src.detach(src_node);
-      src.splice_back_node(dst_node, src_node);
+      self.splice_back_node(dst_node, src_node);
       src.len -= 1;
       self.len += 1;
    }
 
-   pub fn splice_front(
+   pub(crate) fn splice_front(
       &mut self,
       dst_node: Option<NonNullNode<T>>,
       src: &mut List<T>,
@@ -156,7 +582,42 @@ impl<T> List<T> {
       self.len += 1;
    }
 
-   pub fn splice_self_front(&mut self, dst_node: Option<NonNullNode<T>>, src_node: NonNullNode<T>) {
+   /// Relinks `node` as the head. Does nothing if it already is, `len` never changes.
+   pub(crate) fn move_to_front(&mut self, node: NonNullNode<T>) {
+      self.splice_self_front(self.head, node);
+   }
+
+   /// Relinks `node` as the tail. Does nothing if it already is, `len` never changes.
+   pub(crate) fn move_to_back(&mut self, node: NonNullNode<T>) {
+      self.splice_self_back(self.tail, node);
+   }
+
+   /// Exchanges the positions of two nodes of this list by relinking them, so every handle
+   /// keeps pointing at the same element.
+   pub(crate) fn swap_nodes(&mut self, a: NonNullNode<T>, b: NonNullNode<T>) {
+      if a == b {
+         return;
+      }
+      let a_next = self.next_node(a);
+      if a_next == Some(b) {
+         self.splice_self_back(Some(b), a);
+      } else if self.next_node(b) == Some(a) {
+         self.splice_self_back(Some(a), b);
+      } else {
+         // put `a` after `b`, then `b` where `a` used to be
+         self.splice_self_back(Some(b), a);
+         match a_next {
+            Some(next) => self.splice_self_front(Some(next), b),
+            None => self.splice_self_back(self.tail, b),
+         }
+      }
+   }
+
+   pub(crate) fn splice_self_front(
+      &mut self,
+      dst_node: Option<NonNullNode<T>>,
+      src_node: NonNullNode<T>,
+   ) {
       if let Some(dst_node) = dst_node {
          if dst_node.eq(&src_node) {
             return;
@@ -166,16 +627,112 @@ impl<T> List<T> {
       self.splice_front_node(dst_node, src_node);
    }
 
-   pub fn remove_node(&mut self, node: NonNullNode<T>) -> T {
+   pub(crate) fn splice_self_back(
+      &mut self,
+      dst_node: Option<NonNullNode<T>>,
+      src_node: NonNullNode<T>,
+   ) {
+      if let Some(dst_node) = dst_node {
+         if dst_node.eq(&src_node) {
+            return;
+         }
+      }
+      self.detach(src_node);
+      self.splice_back_node(dst_node, src_node);
+   }
+
+   pub fn contains(&self, x: &T) -> bool
+   where
+      T: PartialEq,
+   {
+      self.iter().any(|ele| ele == x)
+   }
+
+   /// The first node, front to back, whose element satisfies `pred`.
+   pub(crate) fn find_node<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Option<NonNullNode<T>> {
+      let mut cur = self.head;
+      while let Some(node) = cur {
+         // SAFETY: the chain only holds live nodes of this list
+         if pred(unsafe { &node.as_ref().element }) {
+            return Some(node);
+         }
+         cur = self.next_node(node);
+      }
+      None
+   }
+
+   /// Like `find_node`, searching back to front.
+   pub(crate) fn rfind_node<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Option<NonNullNode<T>> {
+      let mut cur = self.tail;
+      while let Some(node) = cur {
+         // SAFETY: the chain only holds live nodes of this list
+         if pred(unsafe { &node.as_ref().element }) {
+            return Some(node);
+         }
+         cur = self.prev_node(node);
+      }
+      None
+   }
+
+   /// Allocates `ele` in a new node right after `node` and returns its handle.
+   pub(crate) fn insert_after(&mut self, node: NonNullNode<T>, ele: T) -> NonNullNode<T> {
+      let new = Box::leak(Box::new(Node::new(ele))).into();
+      self.splice_back_node(Some(node), new);
+      self.len += 1;
+      new
+   }
+
+   /// Allocates `ele` in a new node right before `node` and returns its handle.
+   pub(crate) fn insert_before(&mut self, node: NonNullNode<T>, ele: T) -> NonNullNode<T> {
+      let new = Box::leak(Box::new(Node::new(ele))).into();
+      self.splice_front_node(Some(node), new);
+      self.len += 1;
+      new
+   }
+
+   pub(crate) fn remove_node(&mut self, node: NonNullNode<T>) -> T {
       self.detach(node);
       self.len -= 1;
+      // SAFETY: the node was allocated by this list and is no longer linked
       unsafe {
          let boxed_node = Box::from_raw(node.as_ptr());
          boxed_node.element
       }
    }
 
+   /// Asserts that the links are symmetric and agree with `len` walking in both directions.
+   #[cfg(any(test, debug_assertions))]
+   pub(crate) fn validate(&self) {
+      assert_eq!(self.head.is_none(), self.len == 0);
+      assert_eq!(self.tail.is_none(), self.len == 0);
+      let mut count = 0;
+      let mut prev: Option<NonNullNode<T>> = None;
+      let mut cur = self.head;
+      while let Some(node) = cur {
+         assert_eq!(self.prev_node(node), prev);
+         count += 1;
+         assert!(count <= self.len, "list is longer than its len");
+         prev = cur;
+         cur = self.next_node(node);
+      }
+      assert_eq!(prev, self.tail);
+      assert_eq!(count, self.len);
+      let mut count = 0;
+      let mut next: Option<NonNullNode<T>> = None;
+      let mut cur = self.tail;
+      while let Some(node) = cur {
+         assert_eq!(self.next_node(node), next);
+         count += 1;
+         assert!(count <= self.len, "list is longer than its len");
+         next = cur;
+         cur = self.prev_node(node);
+      }
+      assert_eq!(next, self.head);
+      assert_eq!(count, self.len);
+   }
+
    fn splice_front_node(&mut self, dst_node: Option<NonNullNode<T>>, mut src_node: NonNullNode<T>) {
+      // SAFETY: `src_node` is detached and `dst_node` with its neighbours are live nodes here
       match dst_node {
          None => {
             unsafe {
@@ -199,6 +756,7 @@ impl<T> List<T> {
    }
 
    fn splice_back_node(&mut self, dst_node: Option<NonNullNode<T>>, mut src_node: NonNullNode<T>) {
+      // SAFETY: `src_node` is detached and `dst_node` with its neighbours are live nodes here
       match dst_node {
          None => {
             unsafe {
@@ -222,6 +780,7 @@ impl<T> List<T> {
    }
 
    fn detach(&mut self, node: NonNullNode<T>) {
+      // SAFETY: the caller passes a live node of this list, its neighbours are live as well
       unsafe {
          match node.as_ref().prev {
             None => {
@@ -252,6 +811,7 @@ impl<T> List<T> {
                self.head = None
             }
          }
+         // SAFETY: the new tail is a live node of this list
          Some(mut node) => unsafe {
             node.as_mut().next = None;
          },
@@ -266,6 +826,7 @@ impl<T> List<T> {
                self.tail = None
             }
          }
+         // SAFETY: the new head is a live node of this list
          Some(mut node) => unsafe {
             node.as_mut().prev = None;
          },
@@ -273,12 +834,397 @@ impl<T> List<T> {
    }
 }
 
+impl<T> FromIterator<T> for List<T> {
+   fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+      let mut list = List::new();
+      list.extend(iter);
+      list
+   }
+}
+
+impl<T> Extend<T> for List<T> {
+   fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+      for ele in iter {
+         self.push_back(ele);
+      }
+   }
+}
+
+impl<T> From<Vec<T>> for List<T> {
+   fn from(vec: Vec<T>) -> Self {
+      vec.into_iter().collect()
+   }
+}
+
+// Pops every node on the way, so the list is empty and owns nothing by the time it is dropped
+impl<T> From<List<T>> for Vec<T> {
+   fn from(list: List<T>) -> Self {
+      let mut vec = Vec::with_capacity(list.len());
+      vec.extend(list);
+      vec
+   }
+}
+
+// A deep copy: every element gets a fresh node
+impl<T: Clone> Clone for List<T> {
+   fn clone(&self) -> Self {
+      self.iter().cloned().collect()
+   }
+}
+
+impl<T: Debug> Debug for List<T> {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.debug_list().entries(self.iter()).finish()
+   }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+   fn eq(&self, other: &Self) -> bool {
+      self.len == other.len && self.iter().eq(other.iter())
+   }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T> Default for List<T> {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+// SAFETY: the list exclusively owns its nodes like a `Vec<T>` would, the raw links are never
+// shared outside of borrows of the list itself
+unsafe impl<T: Send> Send for List<T> {}
+unsafe impl<T: Sync> Sync for List<T> {}
+
+// SAFETY: the iterators and cursors stand in for `&List<T>` and `&mut List<T>`
+unsafe impl<'a, T: Sync> Send for Iter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
+unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Send for Cursor<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Cursor<'a, T> {}
+unsafe impl<'a, T: Send> Send for CursorMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CursorMut<'a, T> {}
+
 impl<T> Drop for List<T> {
    fn drop(&mut self) {
       while self.pop_back().is_some() {}
    }
 }
 
+pub struct Iter<'a, T> {
+   head: Option<NonNullNode<T>>,
+   tail: Option<NonNullNode<T>>,
+   len: usize,
+   marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+   type Item = &'a T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      if self.len == 0 {
+         return None;
+      }
+      // SAFETY: the remaining range only holds live nodes, the list is borrowed for 'a
+      self.head.map(|node| unsafe {
+         let node = &*node.as_ptr();
+         self.len -= 1;
+         self.head = node.next;
+         &node.element
+      })
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (self.len, Some(self.len))
+   }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      if self.len == 0 {
+         return None;
+      }
+      // SAFETY: the remaining range only holds live nodes, the list is borrowed for 'a
+      self.tail.map(|node| unsafe {
+         let node = &*node.as_ptr();
+         self.len -= 1;
+         self.tail = node.prev;
+         &node.element
+      })
+   }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// A position in a `List`. Like the std `LinkedList` cursors it is either on an element or on
+/// the "ghost" position between the tail and the head: moving past either end lands on the ghost
+/// and moving again wraps around to the other end.
+pub struct Cursor<'a, T> {
+   current: Option<NonNullNode<T>>,
+   list: &'a List<T>,
+}
+
+impl<'a, T> Cursor<'a, T> {
+   pub fn move_next(&mut self) {
+      self.current = match self.current {
+         Some(node) => self.list.next_node(node),
+         None => self.list.head,
+      };
+   }
+
+   pub fn move_prev(&mut self) {
+      self.current = match self.current {
+         Some(node) => self.list.prev_node(node),
+         None => self.list.tail,
+      };
+   }
+
+   pub fn current(&self) -> Option<&'a T> {
+      // SAFETY: the list is borrowed for 'a and cannot change meanwhile
+      self
+         .current
+         .map(|node| unsafe { &(*node.as_ptr()).element })
+   }
+}
+
+/// A `Cursor` that can also edit the list around its position.
+pub struct CursorMut<'a, T> {
+   current: Option<NonNullNode<T>>,
+   list: &'a mut List<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+   pub fn move_next(&mut self) {
+      self.current = match self.current {
+         Some(node) => self.list.next_node(node),
+         None => self.list.head,
+      };
+   }
+
+   pub fn move_prev(&mut self) {
+      self.current = match self.current {
+         Some(node) => self.list.prev_node(node),
+         None => self.list.tail,
+      };
+   }
+
+   pub fn current(&mut self) -> Option<&mut T> {
+      // SAFETY: the element is borrowed through the cursor, which borrows the list mutably
+      self
+         .current
+         .map(|node| unsafe { &mut (*node.as_ptr()).element })
+   }
+
+   /// Removes the current element and moves to the next one. Does nothing on the ghost.
+   pub fn remove_current(&mut self) -> Option<T> {
+      let node = self.current?;
+      self.current = self.list.next_node(node);
+      Some(self.list.remove_node(node))
+   }
+
+   /// Inserts before the current element, or at the back when on the ghost.
+   pub fn insert_before(&mut self, ele: T) {
+      match self.current {
+         Some(node) => {
+            self.list.insert_before(node, ele);
+         }
+         None => self.list.push_back(ele),
+      }
+   }
+
+   /// Inserts after the current element, or at the front when on the ghost.
+   pub fn insert_after(&mut self, ele: T) {
+      match self.current {
+         Some(node) => {
+            self.list.insert_after(node, ele);
+         }
+         None => self.list.push_front(ele),
+      }
+   }
+}
+
+pub struct ExtractIf<'a, T, F: FnMut(&mut T) -> bool> {
+   list: &'a mut List<T>,
+   cur: Option<NonNullNode<T>>,
+   pred: F,
+}
+
+impl<'a, T, F: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, F> {
+   type Item = T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      while let Some(node) = self.cur {
+         // step first, the node may be freed below
+         self.cur = self.list.next_node(node);
+         // SAFETY: the node is live until `remove_node` and the list is borrowed mutably
+         if (self.pred)(unsafe { &mut (*node.as_ptr()).element }) {
+            return Some(self.list.remove_node(node));
+         }
+      }
+      None
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (0, Some(self.list.len))
+   }
+}
+
+pub struct IterMut<'a, T> {
+   head: Option<NonNullNode<T>>,
+   tail: Option<NonNullNode<T>>,
+   len: usize,
+   marker: PhantomData<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+   type Item = &'a mut T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      if self.len == 0 {
+         return None;
+      }
+      // SAFETY: the list is borrowed mutably for 'a and each node is yielded once. Only the
+      // element is borrowed mutably, the links stay reachable for the other end
+      self.head.map(|node| unsafe {
+         let node = node.as_ptr();
+         self.len -= 1;
+         self.head = (*node).next;
+         &mut (*node).element
+      })
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (self.len, Some(self.len))
+   }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      if self.len == 0 {
+         return None;
+      }
+      // SAFETY: as in `next`
+      self.tail.map(|node| unsafe {
+         let node = node.as_ptr();
+         self.len -= 1;
+         self.tail = (*node).prev;
+         &mut (*node).element
+      })
+   }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+// Pops from the list as it goes, dropping it frees whatever was not visited
+pub struct IntoIter<T>(List<T>);
+
+impl<T> Iterator for IntoIter<T> {
+   type Item = T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      self.0.pop_front()
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (self.0.len, Some(self.0.len))
+   }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      self.0.pop_back()
+   }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+pub struct DrainFront<'a, T> {
+   list: &'a mut List<T>,
+   remaining: usize,
+}
+
+impl<'a, T> Iterator for DrainFront<'a, T> {
+   type Item = T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      if self.remaining == 0 {
+         return None;
+      }
+      self.remaining -= 1;
+      self.list.pop_front()
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (self.remaining, Some(self.remaining))
+   }
+}
+
+impl<'a, T> ExactSizeIterator for DrainFront<'a, T> {}
+
+impl<'a, T> Drop for DrainFront<'a, T> {
+   fn drop(&mut self) {
+      self.for_each(drop);
+   }
+}
+
+pub struct DrainBack<'a, T> {
+   list: &'a mut List<T>,
+   remaining: usize,
+}
+
+impl<'a, T> Iterator for DrainBack<'a, T> {
+   type Item = T;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      if self.remaining == 0 {
+         return None;
+      }
+      self.remaining -= 1;
+      self.list.pop_back()
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      (self.remaining, Some(self.remaining))
+   }
+}
+
+impl<'a, T> ExactSizeIterator for DrainBack<'a, T> {}
+
+impl<'a, T> Drop for DrainBack<'a, T> {
+   fn drop(&mut self) {
+      self.for_each(drop);
+   }
+}
+
+impl<T> IntoIterator for List<T> {
+   type Item = T;
+   type IntoIter = IntoIter<T>;
+
+   fn into_iter(self) -> Self::IntoIter {
+      IntoIter(self)
+   }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+   type Item = &'a T;
+   type IntoIter = Iter<'a, T>;
+
+   fn into_iter(self) -> Self::IntoIter {
+      self.iter()
+   }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+   type Item = &'a mut T;
+   type IntoIter = IterMut<'a, T>;
+
+   fn into_iter(self) -> Self::IntoIter {
+      self.iter_mut()
+   }
+}
+
 #[cfg(test)]
 mod tests {
    use super::*;
@@ -299,6 +1245,7 @@ mod tests {
       list.push_back(3);
       assert_eq!(list.back(), Some(&3));
       assert_eq!(list.len(), 3);
+      list.validate();
       // insert:1 3 pop:2
       assert_eq!(list.pop_front(), Some(2));
       assert_eq!(list.front(), Some(&1));
@@ -314,6 +1261,7 @@ mod tests {
       assert!(list.is_empty());
       assert_eq!(list.pop_front(), None);
       assert_eq!(list.pop_back(), None);
+      list.validate();
    }
 
    #[test]
@@ -333,6 +1281,7 @@ mod tests {
       list1.splice_back(list1.begin_node(), &mut list2, node);
       assert_eq!(list1.front(), Some(&3));
       assert_eq!(list2.front(), Some(&4));
+      list1.validate();
       // list1: 5 2 1 list2:4
       assert_eq!(list1.pop_front(), Some(3));
       assert_eq!(list1.front(), Some(&5));
@@ -344,5 +1293,900 @@ mod tests {
       assert_eq!(list1.pop_front(), Some(4));
       assert_eq!(list1.front(), Some(&5));
       assert!(list2.is_empty());
+      list1.validate();
+      list2.validate();
+   }
+
+   // `splice_back` used to relink the node on `src` instead of `self`, which left the
+   // destination's ends stale whenever the node became its head or tail
+   #[test]
+   fn test_list_splice_back_into_other_list() {
+      // into an empty destination
+      let mut dst = List::new();
+      let mut src: List<i32> = (1..=3).collect();
+      let node = src.begin_node().unwrap();
+      dst.splice_back(dst.end_node(), &mut src, node);
+      dst.validate();
+      src.validate();
+      assert_eq!(dst.front(), Some(&1));
+      assert_eq!(dst.back(), Some(&1));
+      assert_eq!(src.front(), Some(&2));
+
+      // after the destination's tail
+      let node = src.end_node().unwrap();
+      dst.splice_back(dst.end_node(), &mut src, node);
+      dst.validate();
+      src.validate();
+      assert_eq!(dst.back(), Some(&3));
+      assert_eq!((dst.len(), src.len()), (2, 1));
+
+      // the last source node, in the middle of the destination
+      let node = src.begin_node().unwrap();
+      dst.splice_back(dst.begin_node(), &mut src, node);
+      dst.validate();
+      src.validate();
+      assert!(src.is_empty());
+
+      let mut popped = Vec::new();
+      while let Some(ele) = dst.pop_front() {
+         popped.push(ele);
+      }
+      assert_eq!(popped, vec![1, 2, 3]);
+      assert_eq!(src.pop_back(), None);
+      assert_eq!(dst.len() + src.len(), 0);
+   }
+
+   #[test]
+   fn test_list_splice_self_back() {
+      let mut list = List::new();
+      // list:1
+      list.push_back(1);
+      list.splice_self_back(list.end_node(), list.begin_node().unwrap());
+      assert_eq!(list.front(), Some(&1));
+      assert_eq!(list.back(), Some(&1));
+      // list:1 2 3
+      list.push_back(2);
+      list.push_back(3);
+      // list:2 3 1
+      list.splice_self_back(list.end_node(), list.begin_node().unwrap());
+      assert_eq!(list.front(), Some(&2));
+      assert_eq!(list.back(), Some(&1));
+      assert_eq!(list.len(), 3);
+      list.validate();
+      // tail already at the back, list:2 3 1
+      list.splice_self_back(list.end_node(), list.end_node().unwrap());
+      assert_eq!(list.back(), Some(&1));
+      assert_eq!(list.pop_front(), Some(2));
+      assert_eq!(list.pop_front(), Some(3));
+      assert_eq!(list.pop_front(), Some(1));
+      assert!(list.is_empty());
+   }
+
+   #[test]
+   fn test_list_iter() {
+      let mut list = List::new();
+      assert_eq!(list.iter().next(), None);
+      list.push_back(2);
+      list.push_back(3);
+      list.push_front(1);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+      assert_eq!(
+         list.iter().rev().copied().collect::<Vec<_>>(),
+         vec![3, 2, 1]
+      );
+      let mut iter = list.iter();
+      assert_eq!(iter.size_hint(), (3, Some(3)));
+      assert_eq!(iter.next(), Some(&1));
+      assert_eq!(iter.next_back(), Some(&3));
+      assert_eq!(iter.next(), Some(&2));
+      assert_eq!(iter.next_back(), None);
+      assert_eq!(iter.size_hint(), (0, Some(0)));
+   }
+
+   #[test]
+   fn test_list_iter_mut() {
+      let mut list = List::new();
+      assert_eq!(list.iter_mut().next(), None);
+      for i in 1..=4 {
+         list.push_back(i);
+      }
+      for ele in &mut list {
+         *ele *= 10;
+      }
+      let mut iter = list.iter_mut();
+      assert_eq!(iter.size_hint(), (4, Some(4)));
+      let front = iter.next().unwrap();
+      let back = iter.next_back().unwrap();
+      // both ends can be held at once
+      *front += 1;
+      *back += 1;
+      assert_eq!(iter.len(), 2);
+      assert_eq!(iter.rev().map(|ele| *ele).collect::<Vec<_>>(), vec![30, 20]);
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         vec![11, 20, 30, 41]
+      );
+      list.validate();
+   }
+
+   #[test]
+   fn test_list_into_iter() {
+      use std::rc::Rc;
+
+      let mut list = List::new();
+      for i in 1..=4 {
+         list.push_back(i);
+      }
+      let mut iter = list.into_iter();
+      assert_eq!(iter.size_hint(), (4, Some(4)));
+      assert_eq!(iter.next(), Some(1));
+      assert_eq!(iter.next_back(), Some(4));
+      assert_eq!(iter.collect::<Vec<_>>(), vec![2, 3]);
+
+      // dropping a partly consumed iterator frees the rest
+      let value = Rc::new(0);
+      let mut list = List::new();
+      for _ in 0..4 {
+         list.push_back(Rc::clone(&value));
+      }
+      let mut iter = list.into_iter();
+      iter.next();
+      assert_eq!(Rc::strong_count(&value), 4);
+      drop(iter);
+      assert_eq!(Rc::strong_count(&value), 1);
+   }
+
+   #[test]
+   fn test_list_cursor() {
+      let mut list = List::new();
+      for i in 1..=3 {
+         list.push_back(i);
+      }
+      let mut cursor = list.cursor_front();
+      assert_eq!(cursor.current(), Some(&1));
+      cursor.move_prev();
+      assert_eq!(cursor.current(), None);
+      cursor.move_prev();
+      assert_eq!(cursor.current(), Some(&3));
+      cursor.move_next();
+      cursor.move_next();
+      assert_eq!(cursor.current(), Some(&1));
+      let mut cursor = list.cursor_back();
+      cursor.move_prev();
+      assert_eq!(cursor.current(), Some(&2));
+      let empty: List<i32> = List::new();
+      assert_eq!(empty.cursor_back().current(), None);
+   }
+
+   #[test]
+   fn test_list_cursor_mut() {
+      use std::collections::VecDeque;
+
+      let mut list = List::new();
+      let mut model = VecDeque::new();
+      // the model cursor is an index, None is the ghost
+      let mut index: Option<usize> = None;
+      let mut cursor = list.cursor_front_mut();
+      let mut seed = 7u32;
+      for value in 0..500 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         match (seed >> 16) % 6 {
+            0 => {
+               cursor.move_next();
+               index = match index {
+                  None if !model.is_empty() => Some(0),
+                  Some(i) if i + 1 < model.len() => Some(i + 1),
+                  _ => None,
+               };
+            }
+            1 => {
+               cursor.move_prev();
+               index = match index {
+                  None if !model.is_empty() => Some(model.len() - 1),
+                  Some(i) if i > 0 => Some(i - 1),
+                  _ => None,
+               };
+            }
+            2 => {
+               let removed = index.map(|i| model.remove(i).unwrap());
+               assert_eq!(cursor.remove_current(), removed);
+               index = index.filter(|&i| i < model.len());
+            }
+            3 | 4 => {
+               cursor.insert_before(value);
+               match index {
+                  Some(i) => {
+                     model.insert(i, value);
+                     index = Some(i + 1);
+                  }
+                  None => model.push_back(value),
+               }
+            }
+            _ => {
+               cursor.insert_after(value);
+               match index {
+                  Some(i) => model.insert(i + 1, value),
+                  None => model.push_front(value),
+               }
+            }
+         }
+         assert_eq!(cursor.current().copied(), index.map(|i| model[i]));
+      }
+      assert_eq!(list.len(), model.len());
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), Vec::from(model));
+      list.validate();
+
+      // removing at the head and tail fixes the list ends
+      let mut cursor = list.cursor_front_mut();
+      while cursor.remove_current().is_some() {}
+      assert!(list.is_empty());
+      list.validate();
+      list.push_back(1);
+      list.push_back(2);
+      let mut cursor = list.cursor_back_mut();
+      assert_eq!(cursor.remove_current(), Some(2));
+      assert_eq!(cursor.current(), None);
+      cursor.insert_after(0);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1]);
+      list.validate();
+   }
+
+   #[test]
+   fn test_list_append_prepend() {
+      let list_of = |range: std::ops::Range<i32>| {
+         let mut list = List::new();
+         for i in range {
+            list.push_back(i);
+         }
+         list
+      };
+      for (a, b) in [(0..3, 3..6), (0..0, 0..3), (0..3, 3..3), (0..0, 0..0)] {
+         let expected = a.clone().chain(b.clone()).collect::<Vec<_>>();
+         let mut list = list_of(a.clone());
+         let mut other = list_of(b.clone());
+         list.append(&mut other);
+         assert_eq!(list.len(), expected.len());
+         assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+         assert_eq!(
+            list.iter().rev().copied().collect::<Vec<_>>(),
+            expected.iter().rev().copied().collect::<Vec<_>>()
+         );
+         assert!(other.is_empty());
+         list.validate();
+         other.validate();
+
+         let mut list = list_of(b);
+         let mut other = list_of(a);
+         list.prepend(&mut other);
+         assert_eq!(list.len(), expected.len());
+         assert_eq!(list.into_iter().collect::<Vec<_>>(), expected);
+         assert!(other.is_empty());
+         other.validate();
+      }
+
+      // both lists stay usable afterwards
+      let mut list = list_of(0..2);
+      let mut other = list_of(2..4);
+      list.append(&mut other);
+      other.push_back(9);
+      list.push_back(4);
+      list.push_front(-1);
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         vec![-1, 0, 1, 2, 3, 4]
+      );
+      assert_eq!(other.iter().copied().collect::<Vec<_>>(), vec![9]);
+      list.validate();
+      other.validate();
+   }
+
+   #[test]
+   fn test_list_split_off() {
+      for len in 0..6 {
+         for at in 0..=len {
+            let mut list = List::new();
+            for i in 0..len {
+               list.push_back(i);
+            }
+            let mut model = (0..len).collect::<Vec<_>>();
+            let split = list.split_off(at);
+            let model_split = model.split_off(at);
+            assert_eq!(list.len(), model.len());
+            assert_eq!(split.len(), model_split.len());
+            assert_eq!(list.iter().copied().collect::<Vec<_>>(), model);
+            assert_eq!(split.iter().copied().collect::<Vec<_>>(), model_split);
+            list.validate();
+            split.validate();
+         }
+      }
+
+      let mut list = List::new();
+      for i in 0..5 {
+         list.push_back(i);
+      }
+      let node = list.next_node(list.begin_node().unwrap()).unwrap();
+      let mut split = list.split_off_at_node(node);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0]);
+      assert_eq!(split.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+      // splitting at the head moves everything, at the tail only the tail
+      let rest = split.split_off_at_node(split.end_node().unwrap());
+      assert_eq!(rest.iter().copied().collect::<Vec<_>>(), vec![4]);
+      let all = split.split_off_at_node(split.begin_node().unwrap());
+      assert!(split.is_empty());
+      assert_eq!(all.len(), 3);
+      list.validate();
+      split.validate();
+      all.validate();
+      rest.validate();
+   }
+
+   #[test]
+   #[should_panic(expected = "cannot split off at a nonexistent index")]
+   fn test_list_split_off_out_of_range() {
+      let mut list = List::new();
+      list.push_back(1);
+      list.split_off(2);
+   }
+
+   #[test]
+   fn test_list_retain() {
+      type Keep = fn(&i32) -> bool;
+      let cases: [(&[i32], Keep); 5] = [
+         // head and tail
+         (&[1, 2, 3, 4, 5], |&x| x != 1 && x != 5),
+         // consecutive elements
+         (&[1, 2, 3, 4, 5], |&x| !(2..=4).contains(&x)),
+         // every element
+         (&[1, 2, 3], |_| false),
+         (&[1, 2, 3], |_| true),
+         (&[], |_| false),
+      ];
+      for (values, keep) in cases {
+         let mut list = List::new();
+         for &v in values {
+            list.push_back(v);
+         }
+         list.retain(keep);
+         let expected = values.iter().copied().filter(keep).collect::<Vec<_>>();
+         assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+         assert_eq!(list.len(), expected.len());
+         list.validate();
+         // the ends are still usable
+         list.push_front(0);
+         list.push_back(9);
+         assert_eq!(list.pop_front(), Some(0));
+         assert_eq!(list.pop_back(), Some(9));
+         list.validate();
+      }
+   }
+
+   #[test]
+   fn test_list_extract_if() {
+      let mut list = List::new();
+      for i in 0..8 {
+         list.push_back(i);
+      }
+      let odd = list
+         .extract_if(|x| {
+            *x *= 10;
+            *x % 20 != 0
+         })
+         .collect::<Vec<_>>();
+      assert_eq!(odd, vec![10, 30, 50, 70]);
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         vec![0, 20, 40, 60]
+      );
+      list.validate();
+
+      // dropping early keeps the elements that were not visited
+      {
+         let mut iter = list.extract_if(|x| *x >= 20);
+         assert_eq!(iter.next(), Some(20));
+      }
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 40, 60]);
+      assert_eq!(list.len(), 3);
+      list.validate();
+      // removing the tail and then everything
+      assert_eq!(list.extract_if(|x| *x == 60).collect::<Vec<_>>(), vec![60]);
+      assert_eq!(list.back(), Some(&40));
+      assert_eq!(list.extract_if(|_| true).count(), 2);
+      assert!(list.is_empty());
+      list.validate();
+   }
+
+   #[test]
+   fn test_list_traits() {
+      let list = (1..=3).collect::<List<_>>();
+      assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+      list.validate();
+
+      // equal lists built by different push sequences
+      let mut other = List::new();
+      other.push_front(2);
+      other.push_back(3);
+      other.push_front(1);
+      assert_eq!(list, other);
+      other.pop_back();
+      assert_ne!(list, other);
+      other.extend([3, 4]);
+      assert_ne!(list, other);
+      other.pop_back();
+      assert_eq!(list, other);
+
+      // the clone owns its own nodes
+      let mut clone = list.clone();
+      clone.validate();
+      assert_eq!(clone, list);
+      for ele in clone.iter_mut() {
+         *ele *= 10;
+      }
+      clone.push_back(40);
+      assert_eq!(format!("{:?}", clone), "[10, 20, 30, 40]");
+      assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+      drop(list);
+      assert_eq!(clone.pop_front(), Some(10));
+      clone.validate();
+      assert_eq!(List::<i32>::new().clone(), List::new());
+   }
+
+   #[test]
+   fn test_list_clear_and_ends_mut() {
+      let mut list: List<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+      list.front_mut().unwrap().push('!');
+      list.back_mut().unwrap().push('?');
+      assert_eq!(list.front().map(String::as_str), Some("a!"));
+      assert_eq!(list.back().map(String::as_str), Some("c?"));
+
+      list.clear();
+      list.validate();
+      assert!(list.is_empty());
+      assert_eq!(list.front_mut(), None);
+      assert_eq!(list.back_mut(), None);
+
+      // still usable after clearing
+      list.push_back("d".to_string());
+      assert_eq!(list.front(), list.back());
+      list.validate();
+      assert_eq!(List::<u8>::default(), List::new());
+   }
+
+   #[test]
+   fn test_list_reverse() {
+      for n in [0, 1, 2, 5] {
+         let mut list: List<i32> = (0..n).collect();
+         list.reverse();
+         list.validate();
+         let expected: Vec<i32> = (0..n).rev().collect();
+         assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+         assert_eq!(
+            list.iter().rev().copied().collect::<Vec<_>>(),
+            (0..n).collect::<Vec<_>>()
+         );
+
+         // the ends agree with the traversal
+         assert_eq!(list.front(), expected.first());
+         assert_eq!(list.back(), expected.last());
+         let mut popped = Vec::new();
+         while let Some(front) = list.pop_front() {
+            popped.push(front);
+            if let Some(back) = list.pop_back() {
+               popped.push(back);
+            }
+            list.validate();
+         }
+         let mut alternating = Vec::new();
+         let (mut lo, mut hi) = (0, expected.len());
+         while lo < hi {
+            alternating.push(expected[lo]);
+            lo += 1;
+            if lo < hi {
+               hi -= 1;
+               alternating.push(expected[hi]);
+            }
+         }
+         assert_eq!(popped, alternating);
+      }
+
+      // reversing twice restores the order, and the list keeps working
+      let mut list: List<i32> = (0..5).collect();
+      list.reverse();
+      list.reverse();
+      list.push_front(-1);
+      list.push_back(5);
+      list.validate();
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         (-1..=5).collect::<Vec<_>>()
+      );
+   }
+
+   #[test]
+   fn test_list_insert_at_node() {
+      let mut list: List<i32> = [2, 4].into_iter().collect();
+      let head = list.begin_node().unwrap();
+      let tail = list.end_node().unwrap();
+
+      let first = list.insert_before(head, 1);
+      assert_eq!(list.begin_node(), Some(first));
+      let last = list.insert_after(tail, 5);
+      assert_eq!(list.end_node(), Some(last));
+      let middle = list.insert_after(head, 3);
+      list.validate();
+      assert_eq!(list.len(), 5);
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         vec![1, 2, 3, 4, 5]
+      );
+      assert_eq!(
+         list.iter().rev().copied().collect::<Vec<_>>(),
+         vec![5, 4, 3, 2, 1]
+      );
+
+      // the returned handles are ordinary nodes
+      list.insert_before(middle, 0);
+      assert_eq!(list.remove_node(middle), 3);
+      list.splice_self_front(list.begin_node(), last);
+      list.validate();
+      assert_eq!(list.len(), 5);
+      assert_eq!(
+         list.iter().copied().collect::<Vec<_>>(),
+         vec![5, 1, 2, 0, 4]
+      );
+   }
+
+   #[test]
+   fn test_list_find_node() {
+      let mut list: List<i32> = [1, 2, 3, 2, 5].into_iter().collect();
+      assert!(list.contains(&3));
+      assert!(!list.contains(&4));
+      assert_eq!(list.find_node(|&x| x == 1), list.begin_node());
+      assert_eq!(list.rfind_node(|&x| x == 5), list.end_node());
+      assert_eq!(list.find_node(|&x| x == 4), None);
+      assert_eq!(list.rfind_node(|&x| x == 4), None);
+
+      // the two directions pick different duplicates
+      let first = list.find_node(|&x| x == 2).unwrap();
+      let last = list.rfind_node(|&x| x == 2).unwrap();
+      assert_ne!(first, last);
+      assert_eq!(list.next_node(first), list.find_node(|&x| x == 3));
+
+      assert_eq!(list.remove_node(last), 2);
+      let head = list.find_node(|&x| x == 1).unwrap();
+      assert_eq!(list.remove_node(head), 1);
+      let tail = list.rfind_node(|&x| x == 5).unwrap();
+      list.splice_self_front(list.begin_node(), tail);
+      let middle = list.find_node(|&x| x == 3).unwrap();
+      list.insert_after(middle, 4);
+      list.validate();
+      assert_eq!(list.len(), 4);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![5, 2, 3, 4]);
+      assert!(!List::<i32>::new().contains(&0));
+   }
+
+   // The tests below pin down aliasing patterns that `cargo miri test` used to reject under
+   // stacked borrows: reading a node's link through its old pointer after its box had been
+   // reclaimed. Keep them cheap enough to run under Miri.
+   #[test]
+   fn test_miri_pop_reads_link_from_box() {
+      let mut list: List<Box<i32>> = (0..4).map(Box::new).collect();
+      assert_eq!(list.pop_front().as_deref(), Some(&0));
+      assert_eq!(list.pop_back().as_deref(), Some(&3));
+      // the surviving ends were relinked through the popped boxes
+      list.validate();
+      assert_eq!(list.pop_back().as_deref(), Some(&2));
+      assert_eq!(list.pop_front().as_deref(), Some(&1));
+      assert_eq!(list.pop_front(), None);
+      list.validate();
+   }
+
+   #[test]
+   fn test_miri_handles_survive_pops() {
+      let mut list: List<i32> = (0..3).collect();
+      let middle = list.next_node(list.begin_node().unwrap()).unwrap();
+      list.pop_front();
+      list.pop_back();
+      // the handle still points at a live node after its neighbours were freed
+      assert_eq!(list.begin_node(), Some(middle));
+      assert_eq!(list.end_node(), Some(middle));
+      list.push_front(0);
+      assert_eq!(list.remove_node(middle), 1);
+      list.validate();
+      assert_eq!(list.pop_back(), Some(0));
+   }
+
+   #[test]
+   fn test_list_truncate() {
+      let mut list: List<String> = (0..5).map(|i| i.to_string()).collect();
+      list.truncate(5);
+      assert_eq!(list.len(), 5);
+      list.truncate(7);
+      assert_eq!(list.len(), 5);
+      list.truncate(3);
+      list.validate();
+      assert_eq!(
+         list.iter().map(String::as_str).collect::<Vec<_>>(),
+         vec!["0", "1", "2"]
+      );
+      list.truncate(1);
+      assert_eq!(list.back().map(String::as_str), Some("0"));
+      list.truncate(0);
+      list.validate();
+      assert!(list.is_empty());
+   }
+
+   #[test]
+   fn test_list_drain() {
+      let mut list: List<Box<i32>> = (0..8).map(Box::new).collect();
+      let front: Vec<i32> = list.drain_front(2).map(|b| *b).collect();
+      assert_eq!(front, vec![0, 1]);
+      let back = list.drain_back(3);
+      assert_eq!(back.len(), 3);
+      assert_eq!(back.map(|b| *b).collect::<Vec<_>>(), vec![7, 6, 5]);
+      list.validate();
+      assert_eq!(list.iter().map(|b| **b).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+      // dropped early, the rest of the requested elements go too
+      let mut drain = list.drain_front(2);
+      assert_eq!(drain.next().as_deref(), Some(&2));
+      drop(drain);
+      list.validate();
+      assert_eq!(list.iter().map(|b| **b).collect::<Vec<_>>(), vec![4]);
+      list.drain_back(0);
+      assert_eq!(list.len(), 1);
+
+      // asking for more than there is drains everything
+      assert_eq!(list.drain_back(10).len(), 1);
+      list.validate();
+      assert!(list.is_empty());
+      assert_eq!(list.drain_front(3).next(), None);
+   }
+
+   #[test]
+   fn test_list_move_to_ends() {
+      let mut list: List<i32> = (1..=4).collect();
+      let head = list.begin_node().unwrap();
+      list.move_to_front(head);
+      assert_eq!(list.begin_node(), Some(head));
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+      list.move_to_front(list.end_node().unwrap());
+      list.validate();
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![4, 1, 2, 3]);
+
+      let middle = list.find_node(|&x| x == 1).unwrap();
+      list.move_to_back(middle);
+      list.validate();
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![4, 2, 3, 1]);
+      list.move_to_back(middle);
+      assert_eq!(list.end_node(), Some(middle));
+      assert_eq!(list.len(), 4);
+
+      let mut single: List<i32> = [7].into_iter().collect();
+      let node = single.begin_node().unwrap();
+      single.move_to_back(node);
+      single.move_to_front(node);
+      single.validate();
+      assert_eq!(
+         (single.front(), single.back(), single.len()),
+         (Some(&7), Some(&7), 1)
+      );
+   }
+
+   #[test]
+   fn test_list_indexed() {
+      let mut list: List<i32> = (0..7).collect();
+      let mut model: Vec<i32> = (0..7).collect();
+      for idx in 0..=model.len() {
+         assert_eq!(list.get(idx), model.get(idx));
+      }
+      *list.get_mut(5).unwrap() = 50;
+      model[5] = 50;
+      *list.get_mut(1).unwrap() = 10;
+      model[1] = 10;
+      assert_eq!(list.get_mut(7), None);
+
+      // boundaries and both halves
+      for (idx, ele) in [(0, -1), (8, 80), (2, 20), (7, 70), (11, 99)] {
+         list.insert_at(idx, ele);
+         model.insert(idx, ele);
+         list.validate();
+      }
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), model);
+      // 12 elements: the front, the back, then both halves
+      for idx in [0, 10, 3, 6, 5] {
+         assert_eq!(list.remove_at(idx), Some(model.remove(idx)));
+         list.validate();
+      }
+      assert_eq!(list.remove_at(model.len()), None);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), model);
+      assert_eq!(list.len(), model.len());
+   }
+
+   #[test]
+   #[should_panic(expected = "insertion index is out of bounds")]
+   fn test_list_insert_at_out_of_bounds() {
+      let mut list: List<i32> = (0..2).collect();
+      list.insert_at(3, 0);
+   }
+
+   #[test]
+   fn test_list_sort_by() {
+      let mut seed = 11u32;
+      let mut random = |len: usize| -> Vec<u32> {
+         (0..len)
+            .map(|_| {
+               seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+               (seed >> 16) % 100
+            })
+            .collect()
+      };
+      let mut cases: Vec<Vec<u32>> = vec![vec![], vec![1], vec![2, 1], vec![1, 2]];
+      cases.push((0..20).collect());
+      cases.push((0..21).rev().collect());
+      for len in [3, 8, 31, 100] {
+         cases.push(random(len));
+      }
+      for case in cases {
+         let mut list: List<u32> = case.iter().copied().collect();
+         list.sort_by(|a, b| a.cmp(b));
+         list.validate();
+         let mut expected = case.clone();
+         expected.sort();
+         assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+         assert_eq!(list.iter().rev().count(), expected.len());
+      }
+
+      // equal keys keep their insertion order
+      let pairs: Vec<(u32, usize)> = random(50).into_iter().map(|k| k % 5).zip(0..).collect();
+      let mut list: List<(u32, usize)> = pairs.iter().copied().collect();
+      let first = list.begin_node().unwrap();
+      list.sort_by_key(|&(key, _)| key);
+      list.validate();
+      let mut expected = pairs.clone();
+      expected.sort_by_key(|&(key, _)| key);
+      assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+      // nodes were relinked, not their elements
+      assert_eq!(unsafe { first.as_ref().element }, pairs[0]);
+   }
+
+   #[test]
+   fn test_list_rotate() {
+      use std::collections::VecDeque;
+
+      for len in 0..7usize {
+         for n in 0..=2 * len + 1 {
+            let mut left: List<usize> = (0..len).collect();
+            let mut right: List<usize> = (0..len).collect();
+            left.rotate_left(n);
+            right.rotate_right(n);
+            left.validate();
+            right.validate();
+            let mut expected_left: VecDeque<usize> = (0..len).collect();
+            let mut expected_right = expected_left.clone();
+            if len > 0 {
+               expected_left.rotate_left(n % len);
+               expected_right.rotate_right(n % len);
+            }
+            assert_eq!(left.iter().copied().collect::<VecDeque<_>>(), expected_left);
+            assert_eq!(
+               right.iter().copied().collect::<VecDeque<_>>(),
+               expected_right
+            );
+         }
+      }
+   }
+
+   #[test]
+   fn test_list_vec_conversions() {
+      let mut seed = 3u32;
+      for _ in 0..20 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let len = (seed >> 16) as usize % 40;
+         let original: Vec<String> = (0..len).map(|i| format!("{}-{}", seed, i)).collect();
+         let list = List::from(original.clone());
+         list.validate();
+         assert_eq!(list.len(), len);
+         assert_eq!(list.front(), original.first());
+         assert_eq!(list.back(), original.last());
+         let back: Vec<String> = list.into();
+         assert_eq!(back, original);
+      }
+      let empty: Vec<u8> = List::from(Vec::new()).into();
+      assert!(empty.is_empty());
+   }
+
+   #[test]
+   fn test_list_dedup() {
+      let cases: Vec<Vec<i32>> = vec![
+         vec![],
+         vec![1],
+         vec![1, 1, 1, 1],
+         vec![1, 1, 2, 3, 3],
+         vec![1, 2, 2, 2, 2, 3, 1, 1],
+         vec![1, 2, 3],
+      ];
+      for case in cases {
+         let mut list = List::from(case.clone());
+         list.dedup();
+         list.validate();
+         let mut expected = case;
+         expected.dedup();
+         assert_eq!(Vec::from(list), expected);
+      }
+
+      // the earlier element of a run is the one kept, and it can be edited by the callback
+      let words = ["a", "A", "b", "B", "b", "c"];
+      let mut list: List<String> = words.iter().map(|w| w.to_string()).collect();
+      let mut expected: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+      let same = |a: &mut String, b: &mut String| {
+         let dup = a.eq_ignore_ascii_case(b);
+         if dup {
+            b.make_ascii_uppercase();
+         }
+         dup
+      };
+      list.dedup_by(same);
+      expected.dedup_by(same);
+      list.validate();
+      assert_eq!(
+         list.iter().map(String::as_str).collect::<Vec<_>>(),
+         vec!["A", "B", "c"]
+      );
+      assert_eq!(Vec::from(list), expected);
+   }
+
+   #[test]
+   fn test_list_swap_nodes() {
+      let mut list: List<i32> = (0..5).collect();
+      let nodes: Vec<_> = {
+         let mut nodes = Vec::new();
+         let mut cur = list.begin_node();
+         while let Some(node) = cur {
+            nodes.push(node);
+            cur = list.next_node(node);
+         }
+         nodes
+      };
+      let order = |list: &List<i32>| list.iter().copied().collect::<Vec<_>>();
+
+      list.swap_nodes(nodes[2], nodes[2]);
+      assert_eq!(order(&list), vec![0, 1, 2, 3, 4]);
+      // adjacent, both ways round
+      list.swap_nodes(nodes[1], nodes[2]);
+      list.validate();
+      assert_eq!(order(&list), vec![0, 2, 1, 3, 4]);
+      list.swap_nodes(nodes[1], nodes[2]);
+      list.validate();
+      assert_eq!(order(&list), vec![0, 1, 2, 3, 4]);
+      // head and tail, then the head with a middle node
+      list.swap_nodes(nodes[0], nodes[4]);
+      list.validate();
+      assert_eq!(order(&list), vec![4, 1, 2, 3, 0]);
+      list.swap_nodes(nodes[3], nodes[4]);
+      list.validate();
+      assert_eq!(order(&list), vec![3, 1, 2, 4, 0]);
+      // adjacent at the tail
+      list.swap_nodes(nodes[0], nodes[4]);
+      list.validate();
+      assert_eq!(order(&list), vec![3, 1, 2, 0, 4]);
+      assert_eq!(list.end_node(), Some(nodes[4]));
+      // the tail with a node further in front
+      list.swap_nodes(nodes[4], nodes[1]);
+      list.validate();
+      assert_eq!(order(&list), vec![3, 4, 2, 0, 1]);
+
+      // the handles still reach their own elements
+      for (i, node) in nodes.iter().enumerate() {
+         assert_eq!(unsafe { node.as_ref().element }, i as i32);
+      }
+      assert_eq!(list.len(), 5);
+
+      let mut pair: List<i32> = (0..2).collect();
+      let (a, b) = (pair.begin_node().unwrap(), pair.end_node().unwrap());
+      pair.swap_nodes(b, a);
+      pair.validate();
+      assert_eq!(order(&pair), vec![1, 0]);
    }
 }
@@ -0,0 +1,172 @@
+use crate::lru::PushResult;
+use crate::CachePush;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// A read-through cache: pairs a cache with the loader that produces a value on a miss. Hits
+/// are answered by the inner cache, which promotes them as its policy dictates. A miss calls the
+/// loader once and inserts what it returns, a failed load inserts nothing. The value is inserted
+/// through [`CachePush`], which every eviction policy in the crate implements, so a cache
+/// declining to keep it hands it back instead of dropping it.
+///
+/// ```
+/// use rs_lru::{LRUCache, LoadingCache};
+///
+/// let mut squares = LoadingCache::new(LRUCache::with_capacity(16), |k: &u64| {
+///    k.checked_mul(*k).ok_or("overflow")
+/// });
+/// assert_eq!(squares.get(&12), Ok(&144));
+/// assert_eq!(squares.get(&u64::MAX), Err("overflow"));
+/// assert_eq!(squares.get_if_cached(&12), Some(&144));
+/// ```
+pub struct LoadingCache<K, V, C, F> {
+   cache: C,
+   loader: F,
+   // a loaded value the inner cache declined to keep, held until the next load
+   parked: Option<V>,
+   marker: PhantomData<fn(&K)>,
+}
+
+impl<K: Hash + Eq + Clone, V, C: CachePush<K, V>, F> LoadingCache<K, V, C, F> {
+   pub fn new(cache: C, loader: F) -> Self {
+      Self {
+         cache,
+         loader,
+         parked: None,
+         marker: PhantomData,
+      }
+   }
+
+   /// Returns the cached value, loading and inserting it on a miss. The loader's error is passed
+   /// through and leaves the cache untouched.
+   ///
+   /// A loaded value the cache declines to keep (a capacity of zero, say) is kept by the wrapper
+   /// until the next load instead, the loader still runs once per miss.
+   pub fn get<E>(&mut self, k: &K) -> Result<&V, E>
+   where
+      F: FnMut(&K) -> Result<V, E>,
+   {
+      if !self.cache.contains_key(k) {
+         let v = (self.loader)(k)?;
+         // the key was absent, so the only entry it can evict under its own key is the new one
+         if let PushResult::Evicted(evicted, v) = self.cache.push(k.clone(), v) {
+            if evicted == *k {
+               return Ok(self.parked.insert(v));
+            }
+         }
+      }
+      Ok(self.cache.get(k).expect("a cached key must be found"))
+   }
+
+   /// Returns the value if it is cached, without ever calling the loader. The lookup counts as
+   /// an access to the inner cache.
+   pub fn get_if_cached(&mut self, k: &K) -> Option<&V> {
+      self.cache.get(k)
+   }
+
+   /// Drops the cached value so the next `get` loads it again.
+   pub fn invalidate(&mut self, k: &K) -> Option<V> {
+      self.cache.remove(k)
+   }
+
+   pub fn invalidate_all(&mut self) {
+      self.cache.clear();
+      self.parked = None;
+   }
+
+   pub fn cache(&self) -> &C {
+      &self.cache
+   }
+
+   pub fn into_inner(self) -> (C, F) {
+      (self.cache, self.loader)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{LRUCache, LRUkCache, SlruCache};
+   use std::cell::Cell;
+
+   #[test]
+   fn test_loader_calls() {
+      let calls = Cell::new(0);
+      let mut cache = LoadingCache::new(LRUCache::with_capacity(2), |k: &u32| {
+         calls.set(calls.get() + 1);
+         if *k == 13 {
+            Err(format!("no {k}"))
+         } else {
+            Ok(k * 2)
+         }
+      });
+      assert_eq!(cache.get(&1), Ok(&2));
+      assert_eq!(cache.get(&1), Ok(&2));
+      assert_eq!(calls.get(), 1);
+      // failed loads are retried and never cached
+      assert_eq!(cache.get(&13), Err(String::from("no 13")));
+      assert_eq!(cache.get(&13), Err(String::from("no 13")));
+      assert_eq!(calls.get(), 3);
+      assert!(!cache.cache().contains_key(&13));
+      assert_eq!(cache.cache().len(), 1);
+
+      assert_eq!(cache.get_if_cached(&2), None);
+      assert_eq!(calls.get(), 3);
+      assert_eq!(cache.invalidate(&1), Some(2));
+      assert_eq!(cache.get(&1), Ok(&2));
+      assert_eq!(calls.get(), 4);
+      cache.invalidate_all();
+      assert!(cache.cache().is_empty());
+   }
+
+   #[test]
+   fn test_eviction_through_inner_cache() {
+      let calls = Cell::new(0);
+      let loader = |k: &u32| -> Result<u32, ()> {
+         calls.set(calls.get() + 1);
+         Ok(*k)
+      };
+      let mut cache = LoadingCache::new(LRUkCache::with_capacity_freq(2, 2), loader);
+      for k in [1, 2, 1, 3, 1, 2] {
+         cache.get(&k).unwrap();
+      }
+      // 1 was promoted by its hits, 2 made way for 3 and had to be loaded again
+      assert_eq!(calls.get(), 4);
+      assert_eq!(cache.get_if_cached(&1), Some(&1));
+      assert_eq!(cache.get_if_cached(&3), None);
+      let (inner, _) = cache.into_inner();
+      assert_eq!(inner.len(), 2);
+
+      let calls = Cell::new(0);
+      let mut uncached = LoadingCache::new(LRUCache::with_capacity(0), |k: &u32| {
+         calls.set(calls.get() + 1);
+         Ok::<_, ()>(*k)
+      });
+      assert_eq!(uncached.get(&7), Ok(&7));
+      assert_eq!(uncached.get(&7), Ok(&7));
+      assert_eq!(calls.get(), 2);
+      assert!(uncached.get_if_cached(&7).is_none());
+   }
+
+   #[test]
+   fn test_rejected_load_runs_loader_once() {
+      let calls = Cell::new(0);
+      // only the first load succeeds
+      let loader = |k: &u32| {
+         calls.set(calls.get() + 1);
+         if calls.get() == 1 {
+            Ok(*k)
+         } else {
+            Err(calls.get())
+         }
+      };
+      // no probationary segment, so every new entry is turned away despite the room
+      let mut cache = LoadingCache::new(SlruCache::with_capacities(0, 4), loader);
+      assert_eq!(cache.get(&7), Ok(&7));
+      assert_eq!(calls.get(), 1);
+      assert!(cache.cache().is_empty());
+      // nothing was kept, so the next get is a miss of its own
+      assert_eq!(cache.get(&7), Err(2));
+      assert_eq!(calls.get(), 2);
+   }
+}
@@ -1,126 +1,1384 @@
 #![allow(dead_code)]
 
-use crate::list::{List, NonNullNode};
-use crate::Cache;
+use crate::key_ref::{KeyRef, KeyWrapper};
+use crate::list::{self, List, Node, NonNullNode};
+use crate::policy::{self, Entries, EvictionPolicy, Handle, PolicyCache};
+use crate::{Cache, CacheLookup, CachePush};
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::mem;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem::{self, size_of};
+use std::ops::{Index, RangeBounds};
+use std::ptr::NonNull;
 
-struct Item<K, V> {
-   key: K,
-   value: V,
+type Item<K, V> = policy::Item<K, V, LruMeta>;
+
+/// Capacity used by `LRUCache::default()`.
+pub const DEFAULT_CAPACITY: usize = 128;
+
+/// Priority of entries inserted without one. Lower priorities are evicted first.
+pub const DEFAULT_PRIORITY: u8 = 128;
+
+/// Number of unpinned entries at the cold end compared by priority when choosing a victim.
+pub const DEFAULT_PRIORITY_WINDOW: usize = 8;
+
+/// Outcome of [`LRUCache::push`] and [`CachePush::push`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PushResult<K, V> {
+   /// The key was already cached, the old value is returned.
+   Replaced(V),
+   /// Another entry was evicted to make room for the new one.
+   Evicted(K, V),
+   /// The new entry fit without evicting anything.
+   Fit,
+}
+
+/// Opaque handle returned by [`LRUCache::insert_with_handle`] that reaches the entry without
+/// hashing its key. Once the entry is removed, evicted or cleared the handle is stale and every
+/// lookup through it returns `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntryHandle {
+   slot: usize,
+   generation: u64,
+}
+
+// A slot is bumped to a new generation every time its entry goes away
+struct Slot<K, V> {
+   generation: u64,
+   node: Option<NonNullNode<Item<K, V>>>,
+}
+
+/// A view into a single entry of an [`LRUCache`], obtained from [`LRUCache::entry_ref`].
+pub enum EntryRef<'a, 'q, K, Q: ?Sized, V> {
+   Occupied(OccupiedEntryRef<'a, K, V>),
+   Vacant(VacantEntryRef<'a, 'q, K, Q, V>),
+}
+
+pub struct OccupiedEntryRef<'a, K, V> {
+   node: NonNullNode<Item<K, V>>,
+   marker: PhantomData<&'a mut Item<K, V>>,
+}
+
+pub struct VacantEntryRef<'a, 'q, K, Q: ?Sized, V> {
+   cache: &'a mut LRUCache<K, V>,
+   key: &'q Q,
+}
+
+// Secondary index over the keys in order. The update functions are captured where `K: Ord + Clone`
+// is known, so the rest of the cache does not need those bounds.
+type OrderedNode<K, V> = NonNullNode<Item<K, V>>;
+type OrderedMap<K, V> = BTreeMap<K, OrderedNode<K, V>>;
+
+struct OrderedIndex<K, V> {
+   map: OrderedMap<K, V>,
+   insert: fn(&mut OrderedMap<K, V>, &K, OrderedNode<K, V>),
+   remove: fn(&mut OrderedMap<K, V>, &K),
+}
+
+impl<K, V> OrderedIndex<K, V> {
+   fn empty_like(&self) -> Self {
+      Self {
+         map: BTreeMap::new(),
+         insert: self.insert,
+         remove: self.remove,
+      }
+   }
+}
+
+/// The state [`Lru`] keeps next to every entry: its pin, its priority and the bookkeeping of
+/// [`LRUCache`].
+#[derive(Debug, Clone)]
+pub struct LruMeta {
+   pinned: bool,
+   slot: Option<usize>,
+   priority: u8,
+   // in the old region of a midpoint cache
+   old: bool,
+   // already relinked by the `get_many` in progress
+   moved: bool,
+}
+
+impl Default for LruMeta {
+   fn default() -> Self {
+      Self {
+         pinned: false,
+         slot: None,
+         priority: DEFAULT_PRIORITY,
+         old: false,
+         moved: false,
+      }
+   }
+}
+
+/// Least recently used: hits move to the front, the back is evicted. It is the policy of
+/// [`LRUCache`], so it also skips pinned entries, evicts the lowest priority within the priority
+/// window first and keeps the midpoint of [`LRUCache::with_capacity_and_midpoint`]. All three are
+/// only set up through `LRUCache`.
+#[derive(Debug)]
+pub struct Lru {
+   priority_window: usize,
+   // number of entries whose priority differs from the default
+   prioritized: usize,
+   midpoint: Option<Midpoint>,
+}
+
+impl Default for Lru {
+   fn default() -> Self {
+      Self {
+         priority_window: DEFAULT_PRIORITY_WINDOW,
+         prioritized: 0,
+         midpoint: None,
+      }
+   }
+}
+
+// SAFETY: the midpoint boundary only points into the list of the cache owning the policy
+unsafe impl Send for Lru {}
+unsafe impl Sync for Lru {}
+
+impl EvictionPolicy for Lru {
+   type Meta = LruMeta;
+
+   /// With a midpoint, the new entry joins the old region at its head.
+   fn on_insert<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, LruMeta>, h: Handle<'a>) {
+      let Some(midpoint) = &mut self.midpoint else {
+         return;
+      };
+      let mut node = policy::node(h);
+      match midpoint.boundary() {
+         Some(boundary) => entries.list.splice_self_front(Some(boundary), node),
+         None => entries.list.move_to_back(node),
+      }
+      unsafe { node.as_mut().element.meta.old = true };
+      midpoint.boundary = Some(node.cast());
+      midpoint.old_len += 1;
+      self.rebalance(entries.list);
+   }
+
+   fn on_hit<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, LruMeta>, h: Handle<'a>) {
+      let node = policy::node(h);
+      self.leave_region(entries.list, node);
+      entries.list.move_to_front(node);
+      self.rebalance(entries.list);
+   }
+
+   /// The midpoint is rebalanced by the caller once the entry is gone, or once the entry
+   /// replacing it is linked, so the young region does not give up an entry for the victim.
+   fn on_remove<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, LruMeta>, h: Handle<'a>) {
+      let node = policy::node(h);
+      self.leave_region(entries.list, node);
+      if unsafe { node.as_ref().element.meta.priority } != DEFAULT_PRIORITY {
+         self.prioritized -= 1;
+      }
+   }
+
+   /// Walks from the tail past pinned entries and picks the lowest priority among the first
+   /// `priority_window` candidates, the least recently used one wins ties. Returns `None` when
+   /// every entry is pinned.
+   fn choose_victim<'a, K, V>(
+      &mut self,
+      entries: &mut Entries<'a, K, V, LruMeta>,
+   ) -> Option<Handle<'a>> {
+      let window = if self.prioritized == 0 {
+         1
+      } else {
+         self.priority_window.max(1)
+      };
+      let mut victim: Option<(NonNullNode<Item<K, V>>, u8)> = None;
+      let mut candidates = 0;
+      let mut cur = entries.list.end_node();
+      while let Some(node) = cur {
+         if candidates == window {
+            break;
+         }
+         let meta = unsafe { &node.as_ref().element.meta };
+         if !meta.pinned {
+            candidates += 1;
+            if victim.is_none_or(|(_, priority)| meta.priority < priority) {
+               victim = Some((node, meta.priority));
+            }
+         }
+         cur = entries.list.prev_node(node);
+      }
+      victim.map(|(node, _)| policy::handle(node))
+   }
+}
+
+impl Lru {
+   // A policy with the same settings for a cache without entries
+   fn empty_like(&self) -> Self {
+      Self {
+         priority_window: self.priority_window,
+         prioritized: 0,
+         midpoint: self.midpoint.as_ref().map(Midpoint::empty_like),
+      }
+   }
+
+   fn set_priority<K, V>(&mut self, mut node: NonNullNode<Item<K, V>>, priority: u8) {
+      let meta = unsafe { &mut node.as_mut().element.meta };
+      match (
+         meta.priority == DEFAULT_PRIORITY,
+         priority == DEFAULT_PRIORITY,
+      ) {
+         (true, false) => self.prioritized += 1,
+         (false, true) => self.prioritized -= 1,
+         _ => {}
+      }
+      meta.priority = priority;
+   }
+
+   // Relink the entry as the least recently used one, which is the end of the old region
+   fn demote<K, V>(&mut self, list: &mut List<Item<K, V>>, mut node: NonNullNode<Item<K, V>>) {
+      self.leave_region(list, node);
+      list.move_to_back(node);
+      if let Some(midpoint) = &mut self.midpoint {
+         unsafe { node.as_mut().element.meta.old = true };
+         midpoint.old_len += 1;
+         midpoint.boundary.get_or_insert(node.cast());
+         self.rebalance(list);
+      }
+   }
+
+   // Take the entry out of the old region before it is moved or removed
+   fn leave_region<K, V>(&mut self, list: &List<Item<K, V>>, mut node: NonNullNode<Item<K, V>>) {
+      let Some(midpoint) = &mut self.midpoint else {
+         return;
+      };
+      if !unsafe { node.as_ref().element.meta.old } {
+         return;
+      }
+      if midpoint.boundary() == Some(node) {
+         midpoint.boundary = list.next_node(node).map(NonNull::cast);
+      }
+      midpoint.old_len -= 1;
+      unsafe { node.as_mut().element.meta.old = false };
+   }
+
+   // Move the boundary until the old region holds its share of the entries
+   fn rebalance<K, V>(&mut self, list: &List<Item<K, V>>) {
+      let Some(midpoint) = &mut self.midpoint else {
+         return;
+      };
+      let target = (list.len() as f32 * midpoint.fraction) as usize;
+      while midpoint.old_len > target {
+         let mut boundary = midpoint.boundary::<K, V>().unwrap();
+         unsafe { boundary.as_mut().element.meta.old = false };
+         midpoint.boundary = list.next_node(boundary).map(NonNull::cast);
+         midpoint.old_len -= 1;
+      }
+      while midpoint.old_len < target {
+         let mut young_tail = match midpoint.boundary() {
+            Some(boundary) => list.prev_node(boundary),
+            None => list.end_node(),
+         }
+         .unwrap();
+         unsafe { young_tail.as_mut().element.meta.old = true };
+         midpoint.boundary = Some(young_tail.cast());
+         midpoint.old_len += 1;
+      }
+   }
+
+   #[cfg(any(test, debug_assertions))]
+   fn validate<K, V>(&self, list: &List<Item<K, V>>) {
+      let prioritized = list
+         .iter()
+         .filter(|item| item.meta.priority != DEFAULT_PRIORITY);
+      assert_eq!(prioritized.count(), self.prioritized);
+      if let Some(midpoint) = &self.midpoint {
+         let target = (list.len() as f32 * midpoint.fraction) as usize;
+         assert_eq!(midpoint.old_len, target);
+         // the old region is the tail, starting at the boundary
+         let old: Vec<_> = list.iter().skip_while(|item| !item.meta.old).collect();
+         assert_eq!(old.len(), midpoint.old_len);
+         assert!(old.iter().all(|item| item.meta.old));
+         let first_old = list.find_node(|item| item.meta.old);
+         assert_eq!(first_old, midpoint.boundary());
+      } else {
+         assert!(list.iter().all(|item| !item.meta.old));
+      }
+   }
+}
+
+// Splits the list of a midpoint cache into a young head and an old tail
+#[derive(Debug)]
+struct Midpoint {
+   // share of the entries kept in the old region
+   fraction: f32,
+   // the first entry of the old region, `None` while it is empty
+   boundary: Option<NonNull<()>>,
+   old_len: usize,
+}
+
+impl Midpoint {
+   fn empty_like(&self) -> Self {
+      Self {
+         fraction: self.fraction,
+         boundary: None,
+         old_len: 0,
+      }
+   }
+
+   fn boundary<K, V>(&self) -> Option<NonNullNode<Item<K, V>>> {
+      self.boundary.map(NonNull::cast)
+   }
 }
 
-impl<K, V> Item<K, V> {
-   fn new(key: K, value: V) -> Self {
-      Self { key, value }
+/// A least recently used cache.
+///
+/// It is a [`PolicyCache`] running the [`Lru`] policy, which also keeps the pins, priorities and
+/// midpoint, plus what needs the key and value types: entry handles, the ordered index and the
+/// heap size function.
+///
+/// The cache is `Send` and `Sync` when both `K` and `V` are:
+///
+/// ```compile_fail
+/// use rs_lru::LRUCache;
+/// use std::rc::Rc;
+///
+/// let cache: LRUCache<u64, Rc<i32>> = LRUCache::with_capacity(1);
+/// std::thread::spawn(move || drop(cache));
+/// ```
+pub struct LRUCache<K, V> {
+   core: PolicyCache<K, V, Lru>,
+   heap_size: Option<fn(&K, &V) -> usize>,
+   slots: Vec<Slot<K, V>>,
+   free_slots: Vec<usize>,
+   ordered: Option<OrderedIndex<K, V>>,
+}
+
+impl<K: Hash + Eq, V> LRUCache<K, V> {
+   /// Creates a cache holding at most `cap` entries. A capacity of zero caches nothing: every
+   /// new key is rejected (`push` hands it back as `PushResult::Evicted`).
+   pub fn with_capacity(cap: usize) -> Self {
+      Self {
+         core: PolicyCache::with_policy(cap, Lru::default()),
+         heap_size: None,
+         slots: Vec::new(),
+         free_slots: Vec::new(),
+         ordered: None,
+      }
+   }
+
+   /// Creates a cache that inserts new entries at a midpoint instead of the head, like the
+   /// buffer pool of InnoDB. The list is split into a young head and an old tail holding
+   /// `old_fraction` of the entries. New entries join the old region at its head and only move to
+   /// the front of the young region when they are hit again, so a one-pass scan churns the old
+   /// region and leaves the young one alone. Eviction still takes the tail.
+   ///
+   /// ```
+   /// use rs_lru::{Cache, LRUCache};
+   ///
+   /// let mut cache = LRUCache::with_capacity_and_midpoint(4, 0.5);
+   /// for k in 0..4 {
+   ///    cache.insert(k, ());
+   /// }
+   /// cache.get(&0);
+   /// cache.get(&1);
+   /// for k in 100..200 {
+   ///    cache.insert(k, ());
+   /// }
+   /// assert!(cache.contains_key(&0) && cache.contains_key(&1));
+   /// ```
+   ///
+   /// # Panics
+   ///
+   /// Panics unless `old_fraction` is within `0.0..=1.0`.
+   pub fn with_capacity_and_midpoint(cap: usize, old_fraction: f32) -> Self {
+      assert!(
+         (0.0..=1.0).contains(&old_fraction),
+         "old_fraction must be a fraction, got {}",
+         old_fraction
+      );
+      let mut cache = Self::with_capacity(cap);
+      cache.core.policy.midpoint = Some(Midpoint {
+         fraction: old_fraction,
+         boundary: None,
+         old_len: 0,
+      });
+      cache
+   }
+
+   /// Creates a cache that never evicts on insert. Its `capacity()` is `usize::MAX`.
+   pub fn unbounded() -> Self {
+      Self::with_capacity(usize::MAX)
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.core.capacity()
+   }
+
+   pub fn len(&self) -> usize {
+      self.core.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.core.is_empty()
+   }
+
+   pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.core.get(k)
+   }
+
+   /// Gets the entry for a borrowed key. A hit is promoted right away, an owned `K` is only built
+   /// through `From<&Q>` when a vacant entry is inserted into.
+   pub fn entry_ref<'q, Q>(&mut self, k: &'q Q) -> EntryRef<'_, 'q, K, Q, V>
+   where
+      K: Borrow<Q> + From<&'q Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      match self.core.find(k) {
+         Some(node) => {
+            self.update(node);
+            EntryRef::Occupied(OccupiedEntryRef {
+               node,
+               marker: PhantomData,
+            })
+         }
+         None => EntryRef::Vacant(VacantEntryRef {
+            cache: self,
+            key: k,
+         }),
+      }
+   }
+
+   /// Looks up every key and leaves the hits in the recency order a loop of `get` would. The
+   /// hits are only relinked once every key has been looked up, each entry at most once however
+   /// often its key repeats.
+   pub fn get_many<'a, Q, I>(&mut self, keys: I) -> Vec<Option<&V>>
+   where
+      I: IntoIterator<Item = &'a Q>,
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized + 'a,
+   {
+      let keys = keys.into_iter();
+      let mut nodes = Vec::with_capacity(keys.size_hint().0);
+      for k in keys {
+         nodes.push(self.core.find(k));
+      }
+      // Walking back from the last lookup, the first sighting of an entry is the one deciding its
+      // place: it goes right behind the entry placed before it, later sightings are skipped
+      let mut placed = None;
+      let mut moved = 0;
+      for &node in nodes.iter().rev().flatten() {
+         let mut node = node;
+         if unsafe { node.as_ref().element.meta.moved } {
+            continue;
+         }
+         unsafe { node.as_mut().element.meta.moved = true };
+         self.leave_region(node);
+         match placed {
+            None => self.core.list.move_to_front(node),
+            Some(prev) => self.core.list.splice_self_back(Some(prev), node),
+         }
+         placed = Some(node);
+         moved += 1;
+      }
+      // the relinked entries lead the list now
+      let mut cur = self.core.list.begin_node();
+      for _ in 0..moved {
+         let mut node = cur.unwrap();
+         unsafe { node.as_mut().element.meta.moved = false };
+         cur = self.core.list.next_node(node);
+      }
+      self.rebalance();
+      nodes
+         .into_iter()
+         .map(|node| node.map(|node| unsafe { &(*node.as_ptr()).element.value }))
+         .collect()
+   }
+
+   /// Runs `f` on the cached value in place and promotes the entry. Returns whether the key was
+   /// cached. The list is only touched after `f` returns, so a panicking closure leaves the cache
+   /// intact.
+   pub fn modify<Q, F>(&mut self, k: &Q, f: F) -> bool
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+      F: FnOnce(&mut V),
+   {
+      match self.core.find(k) {
+         Some(node) => {
+            self.modify_node(node, f);
+            self.update(node);
+            true
+         }
+         None => false,
+      }
+   }
+
+   /// Like `modify`, but leaves the entry where it is in the recency order.
+   pub fn modify_no_promote<Q, F>(&mut self, k: &Q, f: F) -> bool
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+      F: FnOnce(&mut V),
+   {
+      match self.core.find(k) {
+         Some(node) => {
+            self.modify_node(node, f);
+            true
+         }
+         None => false,
+      }
+   }
+
+   fn modify_node<F: FnOnce(&mut V)>(&mut self, mut node: NonNullNode<Item<K, V>>, f: F) {
+      f(unsafe { &mut node.as_mut().element.value })
+   }
+
+   /// Looks up a value without promoting it.
+   pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.core.peek(k)
+   }
+
+   pub fn contains_key<Q>(&self, k: &Q) -> bool
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.core.contains_key(k)
+   }
+
+   pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.pop_entry(k).map(|(_, v)| v)
+   }
+
+   /// Removes every cached key of `keys` and returns the removed pairs. Absent and repeated keys
+   /// are skipped.
+   pub fn remove_many<'a, Q, I>(&mut self, keys: I) -> Vec<(K, V)>
+   where
+      I: IntoIterator<Item = &'a Q>,
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized + 'a,
+   {
+      keys.into_iter().filter_map(|k| self.pop_entry(k)).collect()
+   }
+
+   /// Removes the entry and returns both the stored key and its value.
+   pub fn pop_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.core.find(k)?;
+      Some(self.unlink(node))
+   }
+
+   /// Inserts like `insert` and returns a handle to the entry for later O(1) access.
+   pub fn insert_with_handle(&mut self, k: K, v: V) -> EntryHandle {
+      if self.capacity() == 0 {
+         // a handle to a slot that never exists
+         return EntryHandle {
+            slot: usize::MAX,
+            generation: 0,
+         };
+      }
+      let (mut node, _) = self.push_node(k, v);
+      let meta = unsafe { &mut node.as_mut().element.meta };
+      if let Some(slot) = meta.slot {
+         return EntryHandle {
+            slot,
+            generation: self.slots[slot].generation,
+         };
+      }
+      let slot = match self.free_slots.pop() {
+         Some(slot) => slot,
+         None => {
+            self.slots.push(Slot {
+               generation: 0,
+               node: None,
+            });
+            self.slots.len() - 1
+         }
+      };
+      self.slots[slot].node = Some(node);
+      meta.slot = Some(slot);
+      EntryHandle {
+         slot,
+         generation: self.slots[slot].generation,
+      }
+   }
+
+   /// Looks up the entry behind the handle and promotes it.
+   pub fn get_by_handle(&mut self, handle: EntryHandle) -> Option<&V> {
+      let node = self.handle_node(handle)?;
+      self.update(node);
+      unsafe { Some(&(*node.as_ptr()).element.value) }
+   }
+
+   pub fn promote_by_handle(&mut self, handle: EntryHandle) -> bool {
+      match self.handle_node(handle) {
+         Some(node) => {
+            self.update(node);
+            true
+         }
+         None => false,
+      }
+   }
+
+   pub fn remove_by_handle(&mut self, handle: EntryHandle) -> Option<(K, V)> {
+      let node = self.handle_node(handle)?;
+      Some(self.unlink(node))
+   }
+
+   fn handle_node(&self, handle: EntryHandle) -> Option<NonNullNode<Item<K, V>>> {
+      let slot = self.slots.get(handle.slot)?;
+      if slot.generation != handle.generation {
+         return None;
+      }
+      slot.node
+   }
+
+   // Drop the bookkeeping of an item that has left the list
+   fn release(&mut self, item: Item<K, V>) -> (K, V) {
+      if let Some(slot) = item.meta.slot {
+         self.free_slot(slot);
+      }
+      if let Some(ordered) = &mut self.ordered {
+         (ordered.remove)(&mut ordered.map, &item.key);
+      }
+      (item.key, item.value)
+   }
+
+   fn free_slot(&mut self, slot: usize) {
+      self.slots[slot].node = None;
+      self.slots[slot].generation += 1;
+      self.free_slots.push(slot);
+   }
+
+   /// Reserves map capacity for at least `additional` more entries.
+   pub fn reserve(&mut self, additional: usize) {
+      self.core.map.reserve(additional);
+   }
+
+   /// Shrinks the map allocation as much as possible. The map only stores pointers to the list
+   /// nodes, which never move, so reallocating it leaves every entry valid.
+   pub fn shrink_to_fit(&mut self) {
+      self.core.map.shrink_to_fit();
+   }
+
+   /// Sets a function reporting the heap memory owned by a key and its value, which
+   /// `approximate_memory_usage` adds to the cache's own allocations.
+   pub fn set_heap_size_fn(&mut self, f: fn(&K, &V) -> usize) {
+      self.heap_size = Some(f);
+   }
+
+   /// Estimates the bytes used by the cache: the map allocation, one node per entry and whatever
+   /// the heap size function reports for the keys and values.
+   pub fn approximate_memory_usage(&self) -> usize {
+      let map = self.core.map.capacity()
+         * (size_of::<(KeyRef<Item<K, V>>, NonNullNode<Item<K, V>>)>() + 1);
+      let nodes = self.len() * size_of::<Node<Item<K, V>>>();
+      let slots = self.slots.capacity() * size_of::<Slot<K, V>>();
+      let ordered = match &self.ordered {
+         Some(ordered) => ordered.map.len() * size_of::<(K, NonNullNode<Item<K, V>>)>(),
+         None => 0,
+      };
+      let heap = match self.heap_size {
+         Some(f) => self.iter().map(|(k, v)| f(k, v)).sum(),
+         None => 0,
+      };
+      size_of::<Self>() + map + nodes + slots + ordered + heap
+   }
+
+   pub fn clear(&mut self) {
+      self.core.clear();
+      self.core.policy = self.core.policy.empty_like();
+      if let Some(ordered) = &mut self.ordered {
+         ordered.map.clear();
+      }
+      for slot in 0..self.slots.len() {
+         if self.slots[slot].node.is_some() {
+            self.free_slot(slot);
+         }
+      }
+   }
+
+   /// Asserts that the map and the list agree: every list node is the target of exactly one map
+   /// entry keyed by that node's key, and vice versa. Live handle slots must point at their node.
+   #[cfg(any(test, debug_assertions))]
+   pub fn validate(&self) {
+      let PolicyCache {
+         map, list, policy, ..
+      } = &self.core;
+      list.validate();
+      assert_eq!(map.len(), list.len());
+      for (key, node) in map {
+         assert_eq!(key.0, *node);
+      }
+      let mut cur = list.begin_node();
+      while let Some(node) = cur {
+         let item = unsafe { &node.as_ref().element };
+         assert_eq!(map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+         if let Some(slot) = item.meta.slot {
+            assert_eq!(self.slots[slot].node, Some(node));
+         }
+         assert!(!item.meta.moved);
+         cur = list.next_node(node);
+      }
+      if let Some(ordered) = &self.ordered {
+         assert_eq!(ordered.map.len(), map.len());
+         for (key, node) in &ordered.map {
+            assert_eq!(map.get(KeyWrapper::from_ref(key)), Some(node));
+         }
+      }
+      policy.validate(list);
+   }
+
+   /// Moves the entry to the tail of the list so that it becomes the next eviction victim.
+   pub fn demote(&mut self, k: &K) -> bool {
+      match self.core.find(k) {
+         Some(node) => {
+            self.move_to_back(node);
+            true
+         }
+         None => false,
+      }
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if self.capacity() == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      self.push_node(k, v).1
+   }
+
+   // `push` into a cache with room for at least one entry, also returning the entry's node
+   fn push_node(&mut self, k: K, v: V) -> (NonNullNode<Item<K, V>>, PushResult<K, V>) {
+      // check cache
+      // cache exist
+      if let Some(mut node) = self.core.find(&k) {
+         self.update(node);
+         let value = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+         return (node, PushResult::Replaced(value));
+      }
+      // cache not exist
+      self.admit(k, v)
+   }
+
+   /// Inserts every pair as a sequence of `insert` calls (later duplicates overwrite earlier ones)
+   /// and returns all entries evicted along the way, in eviction order.
+   pub fn insert_many<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) -> Vec<(K, V)> {
+      let entries = entries.into_iter();
+      let room = self.capacity().saturating_sub(self.len());
+      self.reserve(entries.size_hint().0.min(room));
+      let mut evicted = Vec::new();
+      for (k, v) in entries {
+         if let PushResult::Evicted(k, v) = self.push(k, v) {
+            evicted.push((k, v));
+         }
+      }
+      evicted
+   }
+
+   /// Inserts only if the key is absent. On conflict the pair is handed back and the existing
+   /// entry is neither changed nor promoted.
+   pub fn try_insert(&mut self, k: K, v: V) -> Result<&V, (K, V)> {
+      if self.capacity() == 0 || self.contains_key(&k) {
+         return Err((k, v));
+      }
+      let (node, _) = self.admit(k, v);
+      Ok(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Swaps the value of an existing entry without treating the write as an access.
+   pub fn replace_no_promote(&mut self, k: &K, v: V) -> Option<V> {
+      let mut node = self.core.find(k)?;
+      Some(unsafe { mem::replace(&mut node.as_mut().element.value, v) })
+   }
+
+   /// Pins the entry so that it is skipped by eviction. When every entry is pinned, inserting a
+   /// new key still succeeds and the cache temporarily grows past its capacity; call
+   /// `evict_to(cap)` after unpinning to shrink it back.
+   pub fn pin(&mut self, k: &K) -> bool {
+      self.set_pinned(k, true)
+   }
+
+   pub fn unpin(&mut self, k: &K) -> bool {
+      self.set_pinned(k, false)
+   }
+
+   fn set_pinned(&mut self, k: &K, pinned: bool) -> bool {
+      match self.core.find(k) {
+         Some(mut node) => {
+            unsafe { node.as_mut().element.meta.pinned = pinned };
+            true
+         }
+         None => false,
+      }
+   }
+
+   /// Inserts like `insert` and sets the entry's priority. When choosing a victim, the lowest
+   /// priority among the last `priority_window` unpinned entries is evicted first.
+   pub fn insert_with_priority(&mut self, k: K, v: V, priority: u8) -> Option<V> {
+      if self.capacity() == 0 {
+         return None;
+      }
+      let (node, old) = self.push_node(k, v);
+      self.core.policy.set_priority(node, priority);
+      match old {
+         PushResult::Replaced(value) => Some(value),
+         _ => None,
+      }
+   }
+
+   pub fn set_priority(&mut self, k: &K, priority: u8) -> bool {
+      match self.core.find(k) {
+         Some(node) => {
+            self.core.policy.set_priority(node, priority);
+            true
+         }
+         None => false,
+      }
+   }
+
+   /// Sets how many unpinned entries at the cold end are compared by priority on eviction.
+   pub fn set_priority_window(&mut self, window: usize) {
+      self.core.policy.priority_window = window;
+   }
+
+   /// Removes and returns the next eviction victim: the least recently used unpinned entry,
+   /// unless a lower priority one sits within the priority window.
+   pub fn pop_lru(&mut self) -> Option<(K, V)> {
+      self.evict()
+   }
+
+   /// Walks from the least recently used end and removes the first unpinned entry accepted by
+   /// `pred`. Skipped entries keep their positions.
+   pub fn pop_lru_if<F: FnMut(&K, &V) -> bool>(&mut self, mut pred: F) -> Option<(K, V)> {
+      let mut cur = self.core.list.end_node();
+      while let Some(node) = cur {
+         let item = unsafe { &node.as_ref().element };
+         if !item.meta.pinned && pred(&item.key, &item.value) {
+            return Some(self.unlink(node));
+         }
+         cur = self.core.list.prev_node(node);
+      }
+      None
+   }
+
+   /// Evicts unpinned LRU entries until at most `target_len` remain, returning them in eviction
+   /// order.
+   pub fn evict_to(&mut self, target_len: usize) -> Vec<(K, V)> {
+      let mut evicted = Vec::with_capacity(self.len().saturating_sub(target_len));
+      while self.len() > target_len {
+         match self.evict() {
+            Some(entry) => evicted.push(entry),
+            None => break,
+         }
+      }
+      evicted
+   }
+
+   /// Inserts like `insert` but never evicts: when the cache is full and the key is new, the pair
+   /// is handed back untouched.
+   pub fn insert_if_room(&mut self, k: K, v: V) -> Result<Option<V>, (K, V)> {
+      if self.len() >= self.capacity() && !self.contains_key(&k) {
+         return Err((k, v));
+      }
+      match self.push(k, v) {
+         PushResult::Replaced(value) => Ok(Some(value)),
+         _ => Ok(None),
+      }
+   }
+
+   /// Inserts or replaces the entry but places it at the least recently used end, so it is the
+   /// first eviction candidate until a `get` promotes it. When the cache is full the current
+   /// victim is evicted before the cold entry is admitted.
+   pub fn insert_cold(&mut self, k: K, v: V) -> Option<V> {
+      if let Some(mut node) = self.core.find(&k) {
+         self.move_to_back(node);
+         let value = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+         return Some(value);
+      }
+      if self.capacity() == 0 {
+         return None;
+      }
+      let (node, _) = self.admit(k, v);
+      self.move_to_back(node);
+      None
+   }
+
+   /// Moves every entry of `other` into this cache, replaying them from `other`'s least to most
+   /// recently used as inserts, so `other`'s MRU entry ends up at the head. On a key collision
+   /// the entry keeps this cache's value and is promoted. `other` is left empty; pins, priorities
+   /// and handles of its entries are not carried over.
+   pub fn absorb(&mut self, other: &mut LRUCache<K, V>) {
+      self.absorb_by(other, |cache, node, _, _| cache.update(node));
+   }
+
+   /// Like `absorb`, but a colliding key stores `resolve(key, ours, theirs)`. The merged entry is
+   /// reinserted at the head, keeping its pin and priority, but handles to it are invalidated.
+   pub fn absorb_with<F>(&mut self, other: &mut LRUCache<K, V>, mut resolve: F)
+   where
+      F: FnMut(&K, V, V) -> V,
+   {
+      self.absorb_by(other, |cache, node, _, theirs| {
+         let (pinned, priority) = unsafe {
+            let meta = &node.as_ref().element.meta;
+            (meta.pinned, meta.priority)
+         };
+         let (k, ours) = cache.unlink(node);
+         let v = resolve(&k, ours, theirs);
+         // the unlinked entry left room, nothing is evicted
+         let (mut node, _) = cache.admit(k, v);
+         unsafe { node.as_mut().element.meta.pinned = pinned };
+         cache.core.policy.set_priority(node, priority);
+      });
+   }
+
+   /// Moves every entry accepted by `pred` into a new cache with the same capacity. Both caches
+   /// keep the relative recency order of their entries, and nodes are relinked rather than
+   /// cloned. Moved entries keep their pins and priorities but their handles are invalidated.
+   pub fn split_by<F: FnMut(&K, &V) -> bool>(&mut self, mut pred: F) -> LRUCache<K, V> {
+      let mut split = Self::with_capacity(self.capacity());
+      split.heap_size = self.heap_size;
+      split.core.policy = self.core.policy.empty_like();
+      split.ordered = self.ordered.as_ref().map(OrderedIndex::empty_like);
+      // walk from the tail and push to the front so the split keeps the order
+      let mut cur = self.core.list.end_node();
+      while let Some(mut node) = cur {
+         cur = self.core.list.prev_node(node);
+         // only shared borrows while the map still hashes through the node
+         let item = unsafe { &node.as_ref().element };
+         if !pred(&item.key, &item.value) {
+            continue;
+         }
+         self.core.map.remove(KeyWrapper::from_ref(&item.key));
+         if let Some(ordered) = &mut self.ordered {
+            (ordered.remove)(&mut ordered.map, &item.key);
+         }
+         self.leave_region(node);
+         let (slot, priority) = unsafe {
+            let meta = &mut node.as_mut().element.meta;
+            (meta.slot.take(), meta.priority)
+         };
+         if let Some(slot) = slot {
+            self.free_slot(slot);
+         }
+         if priority != DEFAULT_PRIORITY {
+            self.core.policy.prioritized -= 1;
+            split.core.policy.prioritized += 1;
+         }
+         let begin = split.core.list.begin_node();
+         split
+            .core
+            .list
+            .splice_front(begin, &mut self.core.list, node);
+         split.attach(node);
+      }
+      self.rebalance();
+      split.rebalance();
+      split
+   }
+
+   fn absorb_by<F>(&mut self, other: &mut LRUCache<K, V>, mut collide: F)
+   where
+      F: FnMut(&mut Self, NonNullNode<Item<K, V>>, K, V),
+   {
+      self.reserve(other.len().min(self.capacity().saturating_sub(self.len())));
+      while let Some(node) = other.core.list.end_node() {
+         let (k, v) = other.unlink(node);
+         match self.core.find(&k) {
+            Some(node) => collide(self, node, k, v),
+            None if self.capacity() > 0 => {
+               self.admit(k, v);
+            }
+            None => {}
+         }
+      }
+   }
+
+   // Insert a key known to be absent, evicting first if the cache is full. The policy places it
+   // at the head, or the midpoint
+   fn admit(&mut self, k: K, v: V) -> (NonNullNode<Item<K, V>>, PushResult<K, V>) {
+      let evicted = match self.make_room() {
+         Some((k, v)) => PushResult::Evicted(k, v),
+         None => PushResult::Fit,
+      };
+      let node = self.core.link(k, v);
+      self.index(node);
+      (node, evicted)
+   }
+
+   // Register a node that was linked into the list by hand
+   fn attach(&mut self, node: NonNullNode<Item<K, V>>) {
+      self.core.map.insert(KeyRef(node), node);
+      self.index(node);
+   }
+
+   // Add a linked node to the ordered index
+   fn index(&mut self, node: NonNullNode<Item<K, V>>) {
+      if let Some(ordered) = &mut self.ordered {
+         (ordered.insert)(
+            &mut ordered.map,
+            unsafe { &node.as_ref().element.key },
+            node,
+         );
+      }
+   }
+
+   fn make_room(&mut self) -> Option<(K, V)> {
+      // check cap
+      if self.len() + 1 > self.capacity() {
+         // every entry pinned, the cache grows past its capacity
+         let node = self.core.victim()?;
+         // the caller links the new entry before the midpoint is rebalanced, so the young
+         // region does not give up an entry for the victim
+         Some(self.detach(node))
+      } else {
+         None
+      }
+   }
+
+   fn evict(&mut self) -> Option<(K, V)> {
+      let node = self.core.victim()?;
+      Some(self.unlink(node))
+   }
+
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> (K, V) {
+      let entry = self.detach(node);
+      self.rebalance();
+      entry
+   }
+
+   // `unlink` without rebalancing the midpoint
+   fn detach(&mut self, node: NonNullNode<Item<K, V>>) -> (K, V) {
+      let item = self.core.unlink(node);
+      self.release(item)
+   }
+
+   fn update(&mut self, node: NonNullNode<Item<K, V>>) {
+      self.core.hit(node);
+   }
+
+   fn move_to_back(&mut self, node: NonNullNode<Item<K, V>>) {
+      self.core.policy.demote(&mut self.core.list, node);
+   }
+
+   fn leave_region(&mut self, node: NonNullNode<Item<K, V>>) {
+      self.core.policy.leave_region(&self.core.list, node);
+   }
+
+   fn rebalance(&mut self) {
+      self.core.policy.rebalance(&self.core.list);
+   }
+}
+
+impl<K: Hash + Eq, V> CachePush<K, V> for LRUCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      LRUCache::push(self, k, v)
+   }
+}
+
+impl<K: Hash + Eq + Borrow<Q>, V, Q: Hash + Eq + ?Sized> CacheLookup<K, V, Q> for LRUCache<K, V> {
+   fn get_borrowed(&mut self, k: &Q) -> Option<&V> {
+      self.get(k)
+   }
+
+   fn remove_borrowed(&mut self, k: &Q) -> Option<V> {
+      self.remove(k)
+   }
+
+   fn contains_key_borrowed(&self, k: &Q) -> bool {
+      self.contains_key(k)
+   }
+}
+
+// SAFETY: the slots and the ordered index point into the core's list, like the core's map
+unsafe impl<K: Send, V: Send> Send for LRUCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LRUCache<K, V> {}
+
+impl<K: Hash + Eq, V> Cache<K, V> for LRUCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      LRUCache::get(self, k)
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(value) => Some(value),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      LRUCache::remove(self, k)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      LRUCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      LRUCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      LRUCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      LRUCache::clear(self)
+   }
+
+   /// Visits the entries from the most to the least recently used, like `iter`.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.iter().for_each(|(k, v)| f(k, v))
+   }
+}
+
+impl<K: Hash + Eq + Ord + Clone, V> LRUCache<K, V> {
+   /// Creates a cache that also keeps its keys in a `BTreeMap`, enabling `peek_range`. Every
+   /// insert, removal and eviction keeps the ordered index in sync.
+   pub fn with_capacity_ordered(cap: usize) -> Self {
+      let mut cache = Self::with_capacity(cap);
+      cache.ordered = Some(OrderedIndex {
+         map: BTreeMap::new(),
+         insert: |map, k, node| {
+            map.insert(k.clone(), node);
+         },
+         remove: |map, k| {
+            map.remove(k);
+         },
+      });
+      cache
+   }
+
+   /// Iterates in key order over the entries whose keys fall in `range`, without promoting.
+   ///
+   /// # Panics
+   ///
+   /// Panics if the cache was not created with `with_capacity_ordered`.
+   pub fn peek_range<R: RangeBounds<K>>(&self, range: R) -> impl Iterator<Item = (&K, &V)> {
+      let ordered = self
+         .ordered
+         .as_ref()
+         .expect("peek_range requires an LRUCache created with with_capacity_ordered");
+      ordered
+         .map
+         .range(range)
+         .map(|(k, node)| (k, unsafe { &(*node.as_ptr()).element.value }))
+   }
+}
+
+impl<K: Hash + Eq, V> LRUCache<K, V> {
+   /// Builds a cache as if every pair of `iter` was inserted one by one.
+   pub fn from_iter_with_capacity<I: IntoIterator<Item = (K, V)>>(cap: usize, iter: I) -> Self {
+      let mut cache = Self::with_capacity(cap);
+      cache.extend(iter);
+      cache
+   }
+
+   /// Takes the cache apart into its capacity and its entries ordered from most to least recently
+   /// used. Pins, priorities and handles are not part of the snapshot.
+   pub fn into_parts(mut self) -> (usize, Vec<(K, V)>) {
+      self.core.map.clear();
+      let mut entries = Vec::with_capacity(self.core.list.len());
+      while let Some(item) = self.core.list.pop_front() {
+         entries.push((item.key, item.value));
+      }
+      (self.core.cap, entries)
+   }
+
+   /// Rebuilds a cache from `into_parts` output: the first entry becomes the most recently used.
+   /// Entries past the capacity are dropped and a repeated key keeps its last value and position.
+   pub fn from_parts(cap: usize, entries: Vec<(K, V)>) -> Self {
+      let mut cache = Self::with_capacity(cap);
+      cache.reserve(entries.len().min(cap));
+      for (k, v) in entries {
+         if cache.len() < cap || cache.contains_key(&k) {
+            cache.insert_cold(k, v);
+         }
+      }
+      cache
    }
 }
 
-struct KeyRef<K, V>(NonNullNode<Item<K, V>>);
+impl<K: Hash + Eq + Clone, V: Clone> Clone for LRUCache<K, V> {
+   fn clone(&self) -> Self {
+      let mut cache = Self::with_capacity(self.capacity());
+      cache.heap_size = self.heap_size;
+      cache.core.policy = self.core.policy.empty_like();
+      cache.core.policy.prioritized = self.core.policy.prioritized;
+      cache.ordered = self.ordered.as_ref().map(OrderedIndex::empty_like);
+      cache.reserve(self.len());
+      // the clone's map must point at the clone's own nodes
+      for item in self.core.list.iter() {
+         // handles belong to the original cache
+         let mut item = item.clone();
+         item.meta.slot = None;
+         item.meta.old = false;
+         cache.core.list.push_back(item);
+         cache.attach(cache.core.list.end_node().unwrap());
+      }
+      // the old region of a balanced cache is always the same share of the tail
+      cache.rebalance();
+      cache
+   }
+}
 
-impl<K: Eq, V> Eq for KeyRef<K, V> {}
+impl<'a, 'q, K, Q, V> EntryRef<'a, 'q, K, Q, V>
+where
+   K: Hash + Eq + Borrow<Q> + From<&'q Q>,
+   Q: Hash + Eq + ?Sized,
+{
+   pub fn or_insert(self, v: V) -> &'a mut V {
+      self.or_insert_with(|| v)
+   }
 
-impl<K: Eq, V> PartialEq for KeyRef<K, V> {
-   fn eq(&self, other: &Self) -> bool {
-      unsafe {
-         self
-            .0
-            .as_ref()
-            .element
-            .key
-            .eq(&other.0.as_ref().element.key)
+   pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+      match self {
+         EntryRef::Occupied(entry) => entry.into_mut(),
+         EntryRef::Vacant(entry) => entry.insert(f()),
       }
    }
 }
 
-impl<K: Hash, V> Hash for KeyRef<K, V> {
-   fn hash<H: Hasher>(&self, state: &mut H) {
-      unsafe { self.0.as_ref().element.key.hash(state) }
+impl<'a, K, V> OccupiedEntryRef<'a, K, V> {
+   pub fn key(&self) -> &K {
+      unsafe { &self.node.as_ref().element.key }
+   }
+
+   pub fn get(&self) -> &V {
+      unsafe { &self.node.as_ref().element.value }
+   }
+
+   pub fn get_mut(&mut self) -> &mut V {
+      unsafe { &mut self.node.as_mut().element.value }
+   }
+
+   pub fn into_mut(self) -> &'a mut V {
+      unsafe { &mut (*self.node.as_ptr()).element.value }
    }
 }
 
-impl<K: Hash + Eq, V> Borrow<K> for KeyRef<K, V> {
-   fn borrow(&self) -> &K {
-      unsafe { &self.0.as_ref().element.key }
+impl<'a, 'q, K, Q, V> VacantEntryRef<'a, 'q, K, Q, V>
+where
+   K: Hash + Eq + Borrow<Q> + From<&'q Q>,
+   Q: Hash + Eq + ?Sized,
+{
+   pub fn key(&self) -> &'q Q {
+      self.key
+   }
+
+   /// Builds the owned key and inserts it at the head, evicting if the cache is full.
+   ///
+   /// # Panics
+   ///
+   /// Panics if the cache has capacity zero, since there is no entry to return.
+   pub fn insert(self, v: V) -> &'a mut V {
+      assert!(
+         self.cache.capacity() > 0,
+         "cannot insert into an LRUCache with capacity zero"
+      );
+      let (node, _) = self.cache.admit(K::from(self.key), v);
+      unsafe { &mut (*node.as_ptr()).element.value }
    }
 }
 
-struct LRUCache<K, V> {
-   map: HashMap<KeyRef<K, V>, NonNullNode<Item<K, V>>>,
-   list: List<Item<K, V>>,
-   cap: usize,
+impl<K, V> LRUCache<K, V> {
+   /// Iterates over the entries from the most to the least recently used, without promoting.
+   pub fn iter(&self) -> Iter<'_, K, V> {
+      Iter(self.core.list.iter())
+   }
 }
 
-impl<K: Hash + Eq, V> LRUCache<K, V> {
-   pub fn with_capacity(cap: usize) -> Self {
-      Self {
-         map: HashMap::new(),
-         list: List::new(),
-         cap,
-      }
+pub struct Iter<'a, K, V>(list::Iter<'a, Item<K, V>>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+   type Item = (&'a K, &'a V);
+
+   fn next(&mut self) -> Option<Self::Item> {
+      self.0.next().map(|item| (&item.key, &item.value))
    }
 
-   fn update(&mut self, node: NonNullNode<Item<K, V>>) {
-      if self.list.is_empty() {
-         return;
-      }
-      self.list.splice_self_front(self.list.begin_node(), node);
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      self.0.size_hint()
    }
 }
 
-impl<K: Hash + Eq, V> Cache<K, V> for LRUCache<K, V> {
-   fn get(&mut self, k: &K) -> Option<&V> {
-      let op = self.map.get(k);
-      if let Some(&node) = op {
-         self.update(node);
-         let value = unsafe { &node.as_ref().element.value };
-         return Some(value);
-      }
-      None
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      self.0.next_back().map(|item| (&item.key, &item.value))
    }
+}
 
-   fn insert(&mut self, k: K, v: V) -> Option<V> {
-      // check cache
-      // cache exist
-      if let Some(node) = self.map.get(&k) {
-         let mut node = *node;
-         self.update(node);
-         let value = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
-         return Some(value);
-      }
-      // cache not exist
-      // check cap
-      if self.map.len() + 1 > self.cap {
-         // Pay attention to the lifetime of the pointer and don't let it die before the map removes
-         if let Some(e) = self.list.back() {
-            self.map.remove(&e.key);
-         }
-         self.list.pop_back();
-      }
-      // make node and insert
-      self.list.push_front(Item::new(k, v));
-      let iter = self.list.begin_node().unwrap();
-      self.map.insert(KeyRef(iter), iter);
-      None
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for LRUCache<K, V> {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.debug_struct("LRUCache")
+         .field("cap", &self.core.cap)
+         .field("entries", &self.iter().collect::<Vec<_>>())
+         .finish()
    }
+}
 
-   fn remove(&mut self, k: &K) -> Option<V> {
-      if let Some(node) = self.map.remove(k) {
-         return Some(self.list.remove_node(node).value);
+impl<K: Hash + Eq, V> Default for LRUCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+// Two caches are equal when they hold the same pairs in the same recency order
+impl<K: PartialEq, V: PartialEq> PartialEq for LRUCache<K, V> {
+   fn eq(&self, other: &Self) -> bool {
+      self.core.list.len() == other.core.list.len() && self.iter().eq(other.iter())
+   }
+}
+
+impl<K: Eq, V: Eq> Eq for LRUCache<K, V> {}
+
+/// `cache[&k]` takes `&self`, so indexing behaves like `peek` and never promotes the entry.
+///
+/// # Panics
+///
+/// Panics if the key is not cached.
+impl<K, V, Q> Index<&Q> for LRUCache<K, V>
+where
+   K: Hash + Eq + Borrow<Q>,
+   Q: Hash + Eq + Debug + ?Sized,
+{
+   type Output = V;
+
+   fn index(&self, k: &Q) -> &V {
+      match self.peek(k) {
+         Some(value) => value,
+         None => panic!("key {:?} not found in LRUCache", k),
       }
-      None
    }
+}
 
-   fn is_emtpy(&self) -> bool {
-      self.map.is_empty() && self.list.is_empty()
+impl<K: Hash + Eq, V> Extend<(K, V)> for LRUCache<K, V> {
+   fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+      for (k, v) in iter {
+         self.push(k, v);
+      }
    }
 }
 
 #[cfg(test)]
 mod tests {
    use super::*;
+   use std::cell::Cell;
+   use std::panic::{self, AssertUnwindSafe};
+   use std::path::Path;
 
    #[test]
    fn test_cache() {
@@ -128,62 +1386,1150 @@ mod tests {
 
       // insert full
       assert_eq!(cache.insert(1, 100), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.insert(2, 200), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
 
       // test lru strategy
       // head:(2,200) tail:(1,100)
       assert_eq!(cache.get(&1), Some(&100));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(1,100) tail:(2,200) disuse:(2,200)
       assert_eq!(cache.insert(3, 300), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(3,300) tail:(1,100)
       assert_eq!(cache.get(&1), Some(&100));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&2), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(3,300) tail:(1,100) disuse:(1,100)
       assert_eq!(cache.insert(4, 400), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(4,400) tail:(3,300) disuse:(3,300)
       assert_eq!(cache.insert(5, 500), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(5,500) tail:(4,400)
       assert_eq!(cache.get(&3), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&4), Some(&400));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(5,500) tail:(4,400) disuse:(4,400)
       assert_eq!(cache.insert(6, 600), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(6,600) tail:(5,500)
       assert_eq!(cache.get(&2), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&6), Some(&600));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(6,600) tail:(5,500) change:(6,600)->(6,700)
       assert_eq!(cache.insert(6, 700), Some(600));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(6,700) tail:(5,500) disuse:(5,500)
       assert_eq!(cache.insert(8, 800), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // head:(8,800) tail:(6,700)
       assert_eq!(cache.get(&5), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&8), Some(&800));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&6), Some(&700));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       // remove
       assert_eq!(cache.remove(&6), Some(700));
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.get(&6), None);
-      assert_eq!(cache.is_emtpy(), false);
+      cache.validate();
+      assert!(!cache.is_empty());
       assert_eq!(cache.remove(&8), Some(800));
-      assert_eq!(cache.is_emtpy(), true);
+      cache.validate();
+      assert!(cache.is_empty());
       assert_eq!(cache.get(&8), None);
-      assert_eq!(cache.is_emtpy(), true);
+      cache.validate();
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_demote() {
+      let mut cache = LRUCache::with_capacity(3);
+      assert!(!cache.demote(&1));
+      cache.validate();
+      // head:(1,100) tail:(1,100)
+      cache.insert(1, 100);
+      assert!(cache.demote(&1));
+      assert_eq!(cache.get(&1), Some(&100));
+      cache.validate();
+      // head:(3,300) (2,200) tail:(1,100)
+      cache.insert(2, 200);
+      cache.insert(3, 300);
+      cache.validate();
+      // already the tail
+      assert!(cache.demote(&1));
+      cache.validate();
+      // head:(2,200) (1,100) tail:(3,300)
+      assert!(cache.demote(&3));
+      cache.validate();
+      // head:(4,400) (2,200) tail:(1,100) disuse:(3,300)
+      assert_eq!(cache.insert(4, 400), None);
+      assert_eq!(cache.get(&3), None);
+      assert_eq!(cache.get(&1), Some(&100));
+      assert_eq!(cache.get(&2), Some(&200));
+      assert_eq!(cache.get(&4), Some(&400));
+      assert!(!cache.demote(&3));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_push() {
+      let mut cache = LRUCache::with_capacity(2);
+      assert_eq!(cache.push(1, 100), PushResult::Fit);
+      assert_eq!(cache.push(2, 200), PushResult::Fit);
+      cache.validate();
+      // head:(1,100) tail:(2,200)
+      assert_eq!(cache.push(1, 101), PushResult::Replaced(100));
+      cache.validate();
+      // head:(3,300) tail:(1,101) disuse:(2,200)
+      assert_eq!(cache.push(3, 300), PushResult::Evicted(2, 200));
+      assert_eq!(cache.get(&2), None);
+      cache.validate();
+      // head:(4,400) tail:(3,300) disuse:(1,101)
+      assert_eq!(cache.push(4, 400), PushResult::Evicted(1, 101));
+      assert_eq!(cache.get(&3), Some(&300));
+      assert_eq!(cache.get(&4), Some(&400));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_try_insert() {
+      let mut cache = LRUCache::with_capacity(2);
+      assert_eq!(cache.try_insert(1, 100), Ok(&100));
+      assert_eq!(cache.try_insert(2, 200), Ok(&200));
+      cache.validate();
+      // conflict: head:(2,200) tail:(1,100) stays untouched
+      assert_eq!(cache.try_insert(1, 101), Err((1, 101)));
+      cache.validate();
+      // head:(3,300) tail:(2,200) disuse:(1,100)
+      assert_eq!(cache.try_insert(3, 300), Ok(&300));
+      assert_eq!(cache.get(&1), None);
+      assert_eq!(cache.get(&2), Some(&200));
+      assert_eq!(cache.get(&3), Some(&300));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_replace_no_promote() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(1, 100);
+      cache.insert(2, 200);
+      cache.validate();
+      // head:(2,200) tail:(1,101)
+      assert_eq!(cache.replace_no_promote(&1, 101), Some(100));
+      assert_eq!(cache.replace_no_promote(&3, 300), None);
+      cache.validate();
+      // head:(3,300) tail:(2,200) disuse:(1,101)
+      assert_eq!(cache.push(3, 300), PushResult::Evicted(1, 101));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_borrowed_lookup() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      assert_eq!(cache.get("a"), Some(&1));
+      assert_eq!(cache.peek("b"), Some(&2));
+      assert!(cache.contains_key("a"));
+      assert!(!cache.contains_key("c"));
+      assert_eq!(cache.remove("a"), Some(1));
+      assert!(!cache.contains_key("a"));
+      assert_eq!(cache.remove("a"), None);
+      cache.validate();
+
+      let mut cache: LRUCache<Vec<u8>, i32> = LRUCache::with_capacity(2);
+      cache.insert(vec![1, 2], 12);
+      assert_eq!(cache.get(&[1u8, 2][..]), Some(&12));
+      assert!(cache.contains_key(&[1u8, 2][..]));
+      assert_eq!(cache.remove(&[1u8, 2][..]), Some(12));
+      assert!(!cache.contains_key(&[1u8, 2][..]));
+      cache.validate();
+
+      let mut cache: LRUCache<Box<Path>, i32> = LRUCache::with_capacity(2);
+      cache.insert(Path::new("/tmp/a").into(), 1);
+      assert_eq!(cache.get(Path::new("/tmp/a")), Some(&1));
+      assert!(cache.contains_key(Path::new("/tmp/a")));
+      assert_eq!(cache.remove(Path::new("/tmp/a")), Some(1));
+      assert!(!cache.contains_key(Path::new("/tmp/a")));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_extend() {
+      // head:(5,500) (4,400) tail:(3,300)
+      let mut cache = LRUCache::from_iter_with_capacity(3, (1..=5).map(|i| (i, i * 100)));
+      assert!(!cache.contains_key(&1));
+      assert!(!cache.contains_key(&2));
+      assert_eq!(cache.core.list.front().unwrap().key, 5);
+      assert_eq!(cache.core.list.back().unwrap().key, 3);
+      // head:(3,301) (6,600) tail:(5,500) disuse:(4,400)
+      cache.extend(vec![(3, 301), (6, 600), (3, 301)]);
+      assert_eq!(cache.peek(&3), Some(&301));
+      assert!(!cache.contains_key(&4));
+      assert_eq!(cache.push(7, 700), PushResult::Evicted(5, 500));
+      assert_eq!(cache.push(8, 800), PushResult::Evicted(6, 600));
+      assert_eq!(cache.push(9, 900), PushResult::Evicted(3, 301));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_clone() {
+      let mut cache = LRUCache::with_capacity(3);
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      cache.insert(String::from("c"), 3);
+      cache.get("a");
+      cache.validate();
+      // head:a c tail:b
+      let mut snapshot = cache.clone();
+      assert_eq!(
+         snapshot.push(String::from("d"), 4),
+         PushResult::Evicted(String::from("b"), 2)
+      );
+      assert_eq!(snapshot.remove("a"), Some(1));
+      assert_eq!(snapshot.push(String::from("e"), 5), PushResult::Fit);
+      snapshot.validate();
+      // the original keeps its entries and order
+      assert_eq!(cache.peek("a"), Some(&1));
+      assert_eq!(cache.peek("b"), Some(&2));
+      assert!(!cache.contains_key("d"));
+      assert_eq!(
+         cache.push(String::from("d"), 4),
+         PushResult::Evicted(String::from("b"), 2)
+      );
+      assert_eq!(
+         cache.push(String::from("e"), 5),
+         PushResult::Evicted(String::from("c"), 3)
+      );
+      assert_eq!(
+         cache.push(String::from("f"), 6),
+         PushResult::Evicted(String::from("a"), 1)
+      );
+   }
+
+   #[test]
+   fn test_debug() {
+      let mut cache = LRUCache::with_capacity(3);
+      assert_eq!(format!("{:?}", cache), "LRUCache { cap: 3, entries: [] }");
+      cache.insert("a", 1);
+      cache.insert("b", 2);
+      cache.insert("c", 3);
+      cache.get("a");
+      assert_eq!(
+         format!("{:?}", cache),
+         r#"LRUCache { cap: 3, entries: [("a", 1), ("c", 3), ("b", 2)] }"#
+      );
+      assert_eq!(
+         cache.iter().rev().map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec!["b", "c", "a"]
+      );
+      cache.validate();
+   }
+
+   #[test]
+   fn test_default_eq() {
+      let cache: LRUCache<i32, i32> = LRUCache::default();
+      assert_eq!(cache.core.cap, DEFAULT_CAPACITY);
+      assert!(cache.is_empty());
+
+      let mut a = LRUCache::with_capacity(3);
+      a.insert(1, 100);
+      a.insert(2, 200);
+      a.insert(3, 300);
+      a.validate();
+      // a: head:(2,200) (3,300) tail:(1,100)
+      a.get(&3);
+      a.get(&2);
+      let mut b = LRUCache::with_capacity(2);
+      b.insert(3, 300);
+      b.insert(1, 100);
+      b.insert(2, 200);
+      a.validate();
+      b.validate();
+      // b: head:(2,200) tail:(1,100)
+      assert_ne!(a, b);
+      b.core.cap = 3;
+      b.insert(3, 0);
+      b.replace_no_promote(&3, 300);
+      b.validate();
+      // b: head:(3,300) (2,200) tail:(1,100)
+      assert_ne!(a, b);
+      b.get(&2);
+      b.validate();
+      // b: head:(2,200) (3,300) tail:(1,100)
+      assert_eq!(a, b);
+      // comparing does not promote
+      assert_eq!(a.core.list.back().unwrap().key, 1);
+      b.replace_no_promote(&1, 101);
+      assert_ne!(a, b);
+      b.validate();
+   }
+
+   #[test]
+   fn test_index() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      assert_eq!(cache["a"], 1);
+      assert_eq!(cache[&String::from("b")], 2);
+      cache.validate();
+      // indexing does not promote, head:(c,3) tail:(b,2) disuse:(a,1)
+      cache.insert(String::from("c"), 3);
+      assert!(!cache.contains_key("a"));
+      cache.validate();
+   }
+
+   #[test]
+   #[should_panic(expected = "key \"z\" not found in LRUCache")]
+   fn test_index_missing() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(String::from("a"), 1);
+      let _ = cache["z"];
+      cache.validate();
+   }
+
+   #[test]
+   fn test_evict_to() {
+      let mut cache = LRUCache::with_capacity(4);
+      // head:(4,400) (1,100) (3,300) tail:(2,200)
+      for i in 1..=4 {
+         cache.insert(i, i * 100);
+      }
+      cache.get(&1);
+      cache.get(&4);
+      assert!(cache.evict_to(4).is_empty());
+      assert!(cache.evict_to(10).is_empty());
+      assert_eq!(cache.evict_to(2), vec![(2, 200), (3, 300)]);
+      assert_eq!(cache.pop_lru(), Some((1, 100)));
+      assert_eq!(cache.evict_to(0), vec![(4, 400)]);
+      assert!(cache.is_empty());
+      assert_eq!(cache.pop_lru(), None);
+      cache.insert(5, 500);
+      assert_eq!(cache.get(&5), Some(&500));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_pin() {
+      let mut cache = LRUCache::with_capacity(2);
+      assert!(!cache.pin(&1));
+      cache.insert(1, 100);
+      cache.insert(2, 200);
+      cache.validate();
+      // head:(2,200) tail:(1,100) pinned:1
+      assert!(cache.pin(&1));
+      for i in 3..10 {
+         assert_eq!(
+            cache.push(i, i * 100),
+            PushResult::Evicted(i - 1, (i - 1) * 100)
+         );
+         cache.validate();
+      }
+      assert_eq!(cache.peek(&1), Some(&100));
+      assert_eq!(cache.core.list.back().unwrap().key, 1);
+      // everything pinned: head:(10,1000) (9,900) tail:(1,100) overshoot
+      assert!(cache.pin(&9));
+      assert_eq!(cache.push(10, 1000), PushResult::Fit);
+      assert_eq!(cache.core.map.len(), 3);
+      assert_eq!(cache.pop_lru(), Some((10, 1000)));
+      assert_eq!(cache.pop_lru(), None);
+      cache.validate();
+      // head:(11,1100) (9,900) tail:(1,100)
+      cache.insert(11, 1100);
+      assert!(cache.unpin(&1));
+      assert_eq!(cache.evict_to(2), vec![(1, 100)]);
+      assert_eq!(cache.push(12, 1200), PushResult::Evicted(11, 1100));
+      assert!(cache.unpin(&9));
+      assert_eq!(cache.push(13, 1300), PushResult::Evicted(9, 900));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_get_many() {
+      let mut cache = LRUCache::with_capacity(4);
+      for i in 1..=4 {
+         cache.insert(i, i * 100);
+      }
+      // head:(2,200) (4,400) (1,100) tail:(3,300)
+      assert_eq!(
+         cache.get_many(&[2, 5, 1, 2, 4, 6, 2]),
+         vec![
+            Some(&200),
+            None,
+            Some(&100),
+            Some(&200),
+            Some(&400),
+            None,
+            Some(&200)
+         ]
+      );
+      assert_eq!(
+         cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![2, 4, 1, 3]
+      );
+      assert!(cache.get_many(&[7, 8]).iter().all(Option::is_none));
+      assert!(cache.get_many(&[]).is_empty());
+      cache.validate();
+
+      let mut expected = cache.clone();
+      for k in [3, 1, 3, 9] {
+         expected.get(&k);
+      }
+      cache.get_many(&[3, 1, 3, 9]);
+      assert_eq!(cache, expected);
+      cache.validate();
+
+      // the old region ends up the same too, so later inserts land in the same place
+      let mut cache = LRUCache::with_capacity_and_midpoint(8, 0.5);
+      for i in 0..8 {
+         cache.insert(i, i);
+      }
+      let mut expected = cache.clone();
+      for batch in [&[7, 0, 7, 6, 1, 0][..], &[5, 9, 5, 5], &[2, 3, 4, 2, 1]] {
+         for k in batch {
+            expected.get(k);
+         }
+         cache.get_many(batch);
+         assert_eq!(cache, expected);
+         cache.validate();
+      }
+      for i in 10..14 {
+         cache.insert(i, i);
+         expected.insert(i, i);
+      }
+      assert_eq!(cache, expected);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_insert_many() {
+      let mut cache = LRUCache::with_capacity(3);
+      assert!(cache.insert_many(Vec::new()).is_empty());
+      assert!(cache.insert_many(vec![(1, 100), (2, 200)]).is_empty());
+      cache.validate();
+      // head:(2,202) (5,500) tail:(4,400) disuse:(1,100) (3,300)
+      assert_eq!(
+         cache.insert_many(vec![(3, 300), (2, 201), (4, 400), (5, 500), (2, 202)]),
+         vec![(1, 100), (3, 300)]
+      );
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&2, &202), (&5, &500), (&4, &400)]
+      );
+
+      let mut expected = LRUCache::with_capacity(3);
+      let entries: Vec<_> = (0..10).map(|i| (i % 4, i)).collect();
+      for &(k, v) in &entries {
+         expected.insert(k, v);
+      }
+      let mut cache = LRUCache::with_capacity(3);
+      assert_eq!(
+         cache.insert_many(entries),
+         vec![(0, 0), (1, 1), (2, 2), (3, 3), (0, 4), (1, 5), (2, 6)]
+      );
+      assert_eq!(cache, expected);
+   }
+
+   #[test]
+   fn test_reserve_shrink() {
+      let mut cache = LRUCache::with_capacity(64);
+      cache.reserve(64);
+      assert!(cache.core.map.capacity() >= 64);
+      for i in 0..64 {
+         cache.insert(i.to_string(), i);
+         cache.validate();
+      }
+      cache.evict_to(8);
+      cache.shrink_to_fit();
+      assert!(cache.core.map.capacity() < 64);
+      for i in 56..64 {
+         assert_eq!(cache.get(i.to_string().as_str()), Some(&i));
+         cache.validate();
+      }
+      assert_eq!(cache.remove("60"), Some(60));
+      cache.reserve(100);
+      assert_eq!(cache.evict_to(0).len(), 7);
+      cache.shrink_to_fit();
+      assert!(cache.is_empty());
+      for i in 0..100 {
+         cache.insert(i.to_string(), i);
+         cache.validate();
+      }
+      for i in 36..100 {
+         assert_eq!(cache.remove(i.to_string().as_str()), Some(i));
+      }
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_pop_entry() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      assert_eq!(cache.pop_entry("a"), Some((String::from("a"), 1)));
+      assert!(!cache.contains_key("a"));
+      assert_eq!(cache.pop_entry("a"), None);
+      assert_eq!(
+         cache.pop_entry(&String::from("b")),
+         Some((String::from("b"), 2))
+      );
+      assert!(cache.is_empty());
+      cache.validate();
+   }
+
+   #[test]
+   fn test_approximate_memory_usage() {
+      let mut cache = LRUCache::with_capacity(100);
+      let empty = cache.approximate_memory_usage();
+      cache.reserve(100);
+      let reserved = cache.approximate_memory_usage();
+      assert!(reserved > empty);
+      let mut last = reserved;
+      for i in 0..100 {
+         cache.insert(i, vec![0u8; 16]);
+         let usage = cache.approximate_memory_usage();
+         assert!(usage > last);
+         last = usage;
+         cache.validate();
+      }
+      cache.set_heap_size_fn(|_, v| v.capacity());
+      assert!(cache.approximate_memory_usage() >= last + 100 * 16);
+      cache.clear();
+      assert!(cache.is_empty());
+      assert!(cache.approximate_memory_usage() < last);
+      cache.shrink_to_fit();
+      assert_eq!(cache.approximate_memory_usage(), empty);
+      cache.insert(1, vec![1]);
+      assert_eq!(cache.get(&1), Some(&vec![1]));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_modify() {
+      let mut cache = LRUCache::with_capacity(2);
+      cache.insert(1, vec![1]);
+      cache.insert(2, vec![2]);
+      // head:(2,[2]) tail:(1,[1,10])
+      assert!(cache.modify_no_promote(&1, |v| v.push(10)));
+      assert_eq!(cache.core.list.back().unwrap().key, 1);
+      // head:(1,[1,10,11]) tail:(2,[2])
+      assert!(cache.modify(&1, |v| v.push(11)));
+      assert!(!cache.modify(&3, |v| v.push(30)));
+      assert!(!cache.modify_no_promote(&3, |v| v.push(30)));
+      assert_eq!(cache.peek(&1), Some(&vec![1, 10, 11]));
+      assert_eq!(cache.core.list.front().unwrap().key, 1);
+      cache.validate();
+
+      let result = panic::catch_unwind(AssertUnwindSafe(|| {
+         cache.modify(&2, |v| {
+            v.push(20);
+            panic!("modify closure panicked");
+         });
+      }));
+      assert!(result.is_err());
+      cache.validate();
+      assert_eq!(cache.get(&2), Some(&vec![2, 20]));
+      assert_eq!(
+         cache.push(3, vec![3]),
+         PushResult::Evicted(1, vec![1, 10, 11])
+      );
+      assert_eq!(cache.remove(&2), Some(vec![2, 20]));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_pop_lru_if() {
+      let mut cache = LRUCache::with_capacity(5);
+      assert_eq!(cache.pop_lru_if(|_, _| true), None);
+      // head:(5,500) (4,400) (3,300) (2,200) tail:(1,100)
+      for i in 1..=5 {
+         cache.insert(i, i * 100);
+      }
+      // tail accepted immediately
+      assert_eq!(cache.pop_lru_if(|_, _| true), Some((1, 100)));
+      // nothing qualifies
+      assert_eq!(cache.pop_lru_if(|_, v| *v > 1000), None);
+      // only the head qualifies
+      assert_eq!(cache.pop_lru_if(|k, _| *k == 5), Some((5, 500)));
+      // middle entry, the skipped ones keep their order
+      assert_eq!(cache.pop_lru_if(|k, _| k % 2 == 1), Some((3, 300)));
+      assert_eq!(
+         cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![4, 2]
+      );
+      cache.pin(&2);
+      assert_eq!(cache.pop_lru_if(|_, _| true), Some((4, 400)));
+      assert_eq!(cache.pop_lru_if(|_, _| true), None);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_remove_many() {
+      let mut cache = LRUCache::with_capacity(5);
+      for i in 1..=5 {
+         cache.insert(i, i * 100);
+      }
+      assert_eq!(
+         cache.remove_many(&[2, 7, 4, 2, 9, 4]),
+         vec![(2, 200), (4, 400)]
+      );
+      assert_eq!(cache.core.map.len(), 3);
+      assert!(cache.remove_many(&[]).is_empty());
+      assert_eq!(
+         cache.remove_many(&[1, 3, 5, 1]),
+         vec![(1, 100), (3, 300), (5, 500)]
+      );
+      assert!(cache.is_empty());
+      cache.validate();
+   }
+
+   #[test]
+   fn test_handle() {
+      let mut cache = LRUCache::with_capacity(2);
+      let h1 = cache.insert_with_handle(1, 100);
+      let h2 = cache.insert_with_handle(2, 200);
+      assert_ne!(h1, h2);
+      // head:(1,100) tail:(2,200)
+      assert_eq!(cache.get_by_handle(h1), Some(&100));
+      assert_eq!(cache.core.list.front().unwrap().key, 1);
+      // replacing keeps the handle
+      assert_eq!(cache.insert_with_handle(2, 201), h2);
+      assert!(cache.promote_by_handle(h1));
+      cache.validate();
+
+      // use after evict, head:(3,300) tail:(1,100) disuse:(2,201)
+      let h3 = cache.insert_with_handle(3, 300);
+      assert_eq!(cache.get_by_handle(h2), None);
+      assert!(!cache.promote_by_handle(h2));
+      assert_eq!(cache.remove_by_handle(h2), None);
+      // head:(4,400) tail:(3,300) disuse:(1,100), its slot is reused with a new generation
+      let h4 = cache.insert_with_handle(4, 400);
+      assert_eq!(h4.slot, h1.slot);
+      assert_eq!(cache.get_by_handle(h1), None);
+      assert_eq!(cache.get_by_handle(h4), Some(&400));
+      cache.validate();
+
+      // use after remove
+      assert_eq!(cache.remove_by_handle(h4), Some((4, 400)));
+      assert_eq!(cache.get_by_handle(h4), None);
+      assert_eq!(cache.remove(&3), Some(300));
+      assert_eq!(cache.get_by_handle(h3), None);
+      cache.validate();
+
+      // use after clear
+      let h5 = cache.insert_with_handle(5, 500);
+      cache.clear();
+      assert_eq!(cache.get_by_handle(h5), None);
+      assert_eq!(cache.remove_by_handle(h5), None);
+      let h6 = cache.insert_with_handle(6, 600);
+      assert_eq!(cache.get_by_handle(h6), Some(&600));
+
+      // handles are not shared with a clone
+      let mut snapshot = cache.clone();
+      assert_eq!(snapshot.get_by_handle(h6), None);
+      assert_eq!(snapshot.get(&6), Some(&600));
+      cache.validate();
+   }
+
+   thread_local! {
+      static KEY_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+   }
+
+   #[derive(Debug, Hash, PartialEq, Eq)]
+   struct CountedKey(String);
+
+   impl From<&str> for CountedKey {
+      fn from(s: &str) -> Self {
+         KEY_ALLOCATIONS.with(|count| count.set(count.get() + 1));
+         CountedKey(s.to_string())
+      }
+   }
+
+   impl Borrow<str> for CountedKey {
+      fn borrow(&self) -> &str {
+         &self.0
+      }
+   }
+
+   #[test]
+   fn test_entry_ref() {
+      let mut cache: LRUCache<CountedKey, i32> = LRUCache::with_capacity(2);
+      *cache.entry_ref("a").or_insert(0) += 1;
+      *cache.entry_ref("b").or_insert_with(|| 10) += 1;
+      assert_eq!(KEY_ALLOCATIONS.with(Cell::get), 2);
+      // hits allocate nothing and promote, head:(a,2) tail:(b,11)
+      *cache.entry_ref("a").or_insert(0) += 1;
+      assert_eq!(*cache.entry_ref("b").or_insert_with(|| unreachable!()), 11);
+      *cache.entry_ref("a").or_insert(0) += 0;
+      assert_eq!(KEY_ALLOCATIONS.with(Cell::get), 2);
+      match cache.entry_ref("a") {
+         EntryRef::Occupied(mut entry) => {
+            assert_eq!(entry.key(), &CountedKey(String::from("a")));
+            *entry.get_mut() += 1;
+            assert_eq!(entry.get(), &3);
+         }
+         EntryRef::Vacant(_) => unreachable!(),
+      }
+      // head:(c,0) tail:(a,3) disuse:(b,11)
+      match cache.entry_ref("c") {
+         EntryRef::Occupied(_) => unreachable!(),
+         EntryRef::Vacant(entry) => {
+            assert_eq!(entry.key(), "c");
+            assert_eq!(entry.insert(0), &0);
+         }
+      }
+      assert_eq!(KEY_ALLOCATIONS.with(Cell::get), 3);
+      assert!(!cache.contains_key("b"));
+      assert_eq!(cache.peek("a"), Some(&3));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_priority() {
+      let mut cache = LRUCache::with_capacity(4);
+      // head:(4,400) (3,300) (2,200) tail:(1,100)
+      cache.insert_with_priority(1, 100, 255);
+      for i in 2..=4 {
+         cache.insert(i, i * 100);
+      }
+      // the cold high priority entry outlives many warmer default ones
+      for i in 5..100 {
+         assert_eq!(
+            cache.push(i, i * 100),
+            PushResult::Evicted(i - 3, (i - 3) * 100)
+         );
+      }
+      assert_eq!(cache.core.list.back().unwrap().key, 1);
+      cache.validate();
+      // head:(99) (98) (97) tail:(1) low:(98)
+      assert!(cache.set_priority(&98, 0));
+      assert!(!cache.set_priority(&5, 0));
+      assert_eq!(cache.pop_lru(), Some((98, 9800)));
+      // head:(100) (99) (97) tail:(1), a window of one falls back to strict lru
+      cache.insert(100, 10000);
+      cache.set_priority_window(1);
+      assert_eq!(cache.pop_lru(), Some((1, 100)));
+      assert_eq!(cache.core.policy.prioritized, 0);
+      cache.validate();
+
+      // equal priorities behave exactly like plain lru
+      let mut plain = LRUCache::with_capacity(3);
+      let mut prioritized = LRUCache::with_capacity(3);
+      for i in 0..20 {
+         plain.insert(i % 5, i);
+         prioritized.insert_with_priority(i % 5, i, 7);
+         if i % 3 == 0 {
+            plain.get(&(i % 4));
+            prioritized.get(&(i % 4));
+         }
+      }
+      assert_eq!(plain, prioritized);
+   }
+
+   #[test]
+   fn test_insert_cold() {
+      let mut cache = LRUCache::with_capacity(3);
+      cache.insert(1, 100);
+      cache.insert(2, 200);
+      // head:(2,200) (1,100) tail:(3,300)
+      assert_eq!(cache.insert_cold(3, 300), None);
+      assert_eq!(cache.core.list.back().unwrap().key, 3);
+      cache.validate();
+      // head:(2,200) (1,100) tail:(4,400) disuse:(3,300)
+      assert_eq!(cache.insert_cold(4, 400), None);
+      assert!(!cache.contains_key(&3));
+      // head:(5,500) (2,200) tail:(1,100) disuse:(4,400)
+      assert_eq!(cache.push(5, 500), PushResult::Evicted(4, 400));
+      cache.validate();
+
+      // replacing cold also demotes, head:(2,200) (1,100) tail:(5,501)
+      assert_eq!(cache.insert_cold(5, 501), Some(500));
+      assert_eq!(cache.core.list.back().unwrap().key, 5);
+      // a single get rescues it, head:(5,501) (2,200) tail:(1,100)
+      assert_eq!(cache.get(&5), Some(&501));
+      // head:(8,800) (7,700) tail:(5,501) disuse:(1,100) (2,200)
+      assert_eq!(cache.push(7, 700), PushResult::Evicted(1, 100));
+      assert_eq!(cache.push(8, 800), PushResult::Evicted(2, 200));
+      assert!(cache.contains_key(&5));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_insert_if_room() {
+      let mut cache = LRUCache::with_capacity(2);
+      assert_eq!(cache.insert_if_room(1, 100), Ok(None));
+      assert_eq!(cache.insert_if_room(2, 200), Ok(None));
+      assert_eq!(cache.insert_if_room(3, 300), Err((3, 300)));
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&2, &200), (&1, &100)]
+      );
+      // replacing never needs room, head:(1,101) tail:(2,200)
+      assert_eq!(cache.insert_if_room(1, 101), Ok(Some(100)));
+      assert_eq!(cache.core.map.len(), 2);
+      cache.validate();
+      assert_eq!(cache.remove(&2), Some(200));
+      assert_eq!(cache.insert_if_room(3, 300), Ok(None));
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&3, &300), (&1, &101)]
+      );
+      cache.validate();
+   }
+
+   #[test]
+   fn test_capacity_zero_and_unbounded() {
+      let mut cache = LRUCache::with_capacity(0);
+      assert_eq!(cache.insert(1, 100), None);
+      assert_eq!(cache.push(1, 100), PushResult::Evicted(1, 100));
+      assert_eq!(cache.try_insert(1, 100), Err((1, 100)));
+      assert_eq!(cache.insert_if_room(1, 100), Err((1, 100)));
+      assert_eq!(cache.insert_cold(1, 100), None);
+      assert_eq!(cache.insert_with_priority(1, 100, 0), None);
+      let handle = cache.insert_with_handle(1, 100);
+      assert_eq!(cache.get_by_handle(handle), None);
+      assert_eq!(
+         cache.insert_many(vec![(1, 100), (2, 200)]),
+         vec![(1, 100), (2, 200)]
+      );
+      assert!(cache.is_empty());
+      assert_eq!(cache.get(&1), None);
+      cache.validate();
+
+      let mut cache = LRUCache::with_capacity(1);
+      assert_eq!(cache.push(1, 100), PushResult::Fit);
+      assert_eq!(cache.push(2, 200), PushResult::Evicted(1, 100));
+      assert_eq!(cache.insert_cold(3, 300), None);
+      assert_eq!(cache.iter().collect::<Vec<_>>(), vec![(&3, &300)]);
+      cache.validate();
+
+      let mut cache = LRUCache::unbounded();
+      assert_eq!(cache.capacity(), usize::MAX);
+      for i in 0..5000 {
+         assert_eq!(cache.push(i, i), PushResult::Fit);
+      }
+      assert_eq!(cache.core.map.len(), 5000);
+      assert_eq!(cache.get(&0), Some(&0));
+      cache.validate();
+   }
+
+   #[test]
+   #[should_panic(expected = "capacity zero")]
+   fn test_entry_ref_capacity_zero() {
+      let mut cache: LRUCache<String, i32> = LRUCache::with_capacity(0);
+      cache.entry_ref("a").or_insert(1);
+   }
+
+   #[test]
+   fn test_peek_range() {
+      let mut cache = LRUCache::with_capacity_ordered(4);
+      for t in [30, 10, 40, 20] {
+         cache.insert(t, t * 10);
+      }
+      assert_eq!(
+         cache.peek_range(15..=40).collect::<Vec<_>>(),
+         vec![(&20, &200), (&30, &300), (&40, &400)]
+      );
+      // peeking does not promote, head:(50,500) (20,200) (40,400) tail:(10,100) disuse:(30,300)
+      assert_eq!(cache.push(50, 500), PushResult::Evicted(30, 300));
+      assert_eq!(
+         cache.peek_range(..).map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![10, 20, 40, 50]
+      );
+      cache.validate();
+      assert_eq!(cache.remove(&20), Some(200));
+      cache.insert_cold(25, 250);
+      cache.pop_lru_if(|k, _| *k == 10);
+      assert_eq!(
+         cache.peek_range(..45).map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![25, 40]
+      );
+      let snapshot = cache.clone();
+      snapshot.validate();
+      cache.evict_to(1);
+      assert_eq!(
+         cache.peek_range(..).map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![50]
+      );
+      assert_eq!(snapshot.peek_range(..).count(), 3);
+      cache.clear();
+      assert_eq!(cache.peek_range(..).count(), 0);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_into_from_parts() {
+      let mut cache = LRUCache::with_capacity(4);
+      // head:(1,10) (3,30) (4,40) tail:(2,20)
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+      }
+      cache.get(&3);
+      cache.get(&1);
+      let snapshot = cache.clone();
+      let (cap, entries) = snapshot.into_parts();
+      assert_eq!(cap, 4);
+      assert_eq!(entries, vec![(1, 10), (3, 30), (4, 40), (2, 20)]);
+      let mut rebuilt = LRUCache::from_parts(cap, entries);
+      rebuilt.validate();
+      assert_eq!(rebuilt, cache);
+      // both caches evict the same sequence
+      for i in 5..=6 {
+         assert_eq!(rebuilt.push(i, i * 10), cache.push(i, i * 10));
+      }
+      rebuilt.get(&4);
+      cache.get(&4);
+      assert_eq!(rebuilt.evict_to(0), cache.evict_to(0));
+      rebuilt.validate();
+
+      // overflowing entries are dropped, a repeated key keeps its last position
+      let cache = LRUCache::from_parts(2, vec![(1, 10), (2, 20), (1, 11), (3, 30)]);
+      assert_eq!(cache.into_parts(), (2, vec![(2, 20), (1, 11)]));
+      let cache: LRUCache<i32, i32> = LRUCache::from_parts(0, vec![(1, 10)]);
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_absorb() {
+      let mut cache = LRUCache::with_capacity(4);
+      let mut other = LRUCache::with_capacity(4);
+      // cache head:(2,20) tail:(1,10), other head:(3,300) (2,200) tail:(4,400)
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      other.insert(4, 400);
+      other.insert(2, 200);
+      other.insert(3, 300);
+      // replay 4, 2, 3: head:(3,300) (2,20) (4,400) tail:(1,10)
+      cache.absorb(&mut other);
+      assert!(other.is_empty());
+      other.validate();
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&3, &300), (&2, &20), (&4, &400), (&1, &10)]
+      );
+      cache.validate();
+
+      // above capacity: head:(6,600) (5,500) (3,300) (2,20) disuse:(4,400) (1,10)
+      other.insert(5, 500);
+      other.insert(6, 600);
+      cache.absorb(&mut other);
+      assert_eq!(
+         cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+         vec![6, 5, 3, 2]
+      );
+      assert!(other.is_empty());
+      cache.validate();
+
+      // absorbing an empty cache changes nothing
+      let before = cache.clone();
+      cache.absorb(&mut other);
+      assert_eq!(cache, before);
+
+      // absorbing into an empty cache keeps the order
+      let mut empty = LRUCache::with_capacity(4);
+      let mut source = before.clone();
+      empty.absorb(&mut source);
+      assert_eq!(empty, before);
+      assert!(source.is_empty());
+      empty.validate();
+   }
+
+   #[test]
+   fn test_absorb_with() {
+      let mut cache = LRUCache::with_capacity(3);
+      let mut other = LRUCache::with_capacity(3);
+      // cache head:(2,20) (1,10) tail:(3,30), other head:(1,100) tail:(3,300)
+      cache.insert(3, 30);
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.pin(&3);
+      other.insert(3, 300);
+      other.insert(1, 100);
+      cache.absorb_with(&mut other, |_, ours, theirs| ours + theirs);
+      // head:(1,110) (3,330) tail:(2,20)
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&1, &110), (&3, &330), (&2, &20)]
+      );
+      assert!(other.is_empty());
+      cache.validate();
+      // the pin survived the merge
+      cache.demote(&3);
+      cache.insert(4, 40);
+      assert!(cache.contains_key(&3));
+      assert!(!cache.contains_key(&2));
+      cache.validate();
+   }
+
+   // Also a Miri regression test: `split_by` used to hold a `&mut` to an item while the map
+   // hashed the same key through its own pointer, which stacked borrows rejects.
+   #[test]
+   fn test_split_by() {
+      let mut cache = LRUCache::with_capacity(6);
+      // head:(6,60) (5,50) (4,40) (3,30) (2,20) tail:(1,10)
+      for i in 1..=6 {
+         cache.insert(i, i * 10);
+      }
+      cache.set_priority(&4, 1);
+      let handle = cache.insert_with_handle(2, 21);
+      let mut odd = cache.split_by(|k, _| k % 2 == 1);
+      assert_eq!(odd.capacity(), 6);
+      assert_eq!(
+         cache.iter().collect::<Vec<_>>(),
+         vec![(&2, &21), (&6, &60), (&4, &40)]
+      );
+      assert_eq!(
+         odd.iter().collect::<Vec<_>>(),
+         vec![(&5, &50), (&3, &30), (&1, &10)]
+      );
+      assert_eq!(cache.core.map.len() + odd.core.map.len(), 6);
+      assert_eq!(cache.get_by_handle(handle), Some(&21));
+      cache.validate();
+      odd.validate();
+      // both halves keep working as caches
+      odd.insert(7, 70);
+      assert_eq!(odd.get(&1), Some(&10));
+      assert_eq!(cache.pop_lru(), Some((4, 40)));
+      odd.validate();
+      cache.validate();
+
+      let none = cache.split_by(|_, _| false);
+      assert!(none.is_empty());
+      let all = cache.split_by(|_, _| true);
+      assert!(cache.is_empty());
+      assert_eq!(all.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![2, 6]);
+      cache.validate();
+      all.validate();
+   }
+
+   fn keys(cache: &LRUCache<i32, i32>) -> Vec<i32> {
+      cache.iter().map(|(k, _)| *k).collect()
+   }
+
+   #[test]
+   fn test_midpoint() {
+      let mut cache = LRUCache::with_capacity_and_midpoint(4, 0.5);
+      for i in 1..=4 {
+         cache.insert(i, i);
+         cache.validate();
+      }
+      // new entries join the old region at its head, the other half of the list
+      assert_eq!(keys(&cache), [1, 3, 4, 2]);
+      assert_eq!(cache.insert(5, 5), None);
+      assert_eq!(keys(&cache), [1, 3, 5, 4]);
+      // a hit in the old region promotes to the head, the young tail ages into the old region
+      cache.get(&4);
+      cache.validate();
+      assert_eq!(keys(&cache), [4, 1, 3, 5]);
+      assert_eq!(cache.push(6, 6), PushResult::Evicted(5, 5));
+      assert_eq!(keys(&cache), [4, 1, 6, 3]);
+      // replacing promotes like a hit, cold inserts and demotions go to the tail
+      assert_eq!(cache.insert(6, 60), Some(6));
+      cache.insert_cold(7, 7);
+      cache.demote(&4);
+      cache.validate();
+      assert_eq!(keys(&cache), [6, 1, 7, 4]);
+      assert_eq!(cache.try_insert(8, 8), Ok(&8));
+      assert_eq!(keys(&cache), [6, 1, 8, 7]);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_midpoint_scan_resistance() {
+      let mut cache = LRUCache::with_capacity_and_midpoint(100, 0.375);
+      let mut lru = LRUCache::with_capacity(100);
+      for k in 0..100 {
+         cache.insert(k, k);
+         lru.insert(k, k);
+      }
+      // the hot keys are hit again, which makes them young
+      for k in 0..50 {
+         cache.get(&k);
+         lru.get(&k);
+      }
+      cache.validate();
+      for k in 1000..11000 {
+         cache.insert(k, k);
+         lru.insert(k, k);
+      }
+      cache.validate();
+      assert!((0..50).all(|k| cache.contains_key(&k)));
+      assert!((0..50).all(|k| !lru.contains_key(&k)));
+   }
+
+   #[test]
+   fn test_midpoint_invariants() {
+      let rounds = if cfg!(miri) { 300 } else { 5000 };
+      for fraction in [0.0, 0.375, 1.0] {
+         let mut cache = LRUCache::with_capacity_and_midpoint(8, fraction);
+         let mut seed = 17u32;
+         for _ in 0..rounds {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let k = (seed >> 16) % 16;
+            match (seed >> 8) % 10 {
+               0..=2 => {
+                  cache.get(&k);
+               }
+               3 | 4 => {
+                  cache.insert(k, k);
+               }
+               5 => {
+                  cache.insert_cold(k, k);
+               }
+               6 => {
+                  cache.demote(&k);
+               }
+               7 => {
+                  cache.remove(&k);
+               }
+               8 => {
+                  cache.pop_lru();
+               }
+               _ => {
+                  let mut odd = cache.split_by(|k, _| k % 2 == 1);
+                  odd.validate();
+                  cache.absorb(&mut odd);
+               }
+            }
+            cache.validate();
+         }
+         let clone = cache.clone();
+         clone.validate();
+         assert!(clone.iter().eq(cache.iter()));
+         cache.clear();
+         cache.validate();
+      }
+   }
+
+   #[test]
+   #[should_panic(expected = "old_fraction must be a fraction")]
+   fn test_midpoint_bad_fraction() {
+      LRUCache::<u8, u8>::with_capacity_and_midpoint(4, 1.5);
    }
 }
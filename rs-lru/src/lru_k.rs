@@ -1,17 +1,22 @@
-#![allow(dead_code)]
-
-use crate::list::{List, NonNullNode};
-use crate::Cache;
+use crate::key_ref::{KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{self, List, Node, NonNullNode};
+use crate::lru::{LRUCache, PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CacheLookup, CachePush};
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::mem;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::iter::Chain;
+use std::mem::{self, size_of};
 // 小坑：注意标准库中的map需要调用key对应的一些方法才能正常删除，所以在此期间需要保证key不被释放内存！！！
 
+#[derive(Clone)]
 struct Item<K, V> {
    key: K,
    value: V,
    freq: u32,
+   // logical time of the last access
+   tick: u64,
 }
 
 impl<K, V> Item<K, V> {
@@ -20,63 +25,204 @@ impl<K, V> Item<K, V> {
          key,
          value,
          freq: 0,
+         tick: 0,
       }
    }
 }
 
-struct KeyNode<K, V>(NonNullNode<Item<K, V>>);
-
-impl<K: Eq, V> Eq for KeyNode<K, V> {}
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
 
-impl<K: Eq, V> PartialEq for KeyNode<K, V> {
-   fn eq(&self, other: &Self) -> bool {
-      unsafe {
-         self
-            .0
-            .as_ref()
-            .element
-            .key
-            .eq(&other.0.as_ref().element.key)
-      }
+   fn key(&self) -> &K {
+      &self.key
    }
 }
 
-impl<K: Hash, V> Hash for KeyNode<K, V> {
-   fn hash<H: Hasher>(&self, state: &mut H) {
-      unsafe { self.0.as_ref().element.key.hash(state) }
-   }
+/// The segment of an `LRUkCache` an entry currently lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Segment {
+   /// Probation: the entry has been accessed fewer than `freq` times.
+   Fcfo,
+   /// Protected: the entry reached the promotion threshold.
+   Lru,
 }
 
-impl<K: Hash + Eq, V> Borrow<K> for KeyNode<K, V> {
-   fn borrow(&self) -> &K {
-      unsafe { &self.0.as_ref().element.key }
-   }
+/// Which segment of an `LRUkCache` gives up the victim when both hold entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EvictionPreference {
+   /// Evict the oldest probation entry, the protected segment only once probation is empty.
+   #[default]
+   ProbationFirst,
+   /// Evict the least recently used protected entry, probation only once protected is empty.
+   ProtectedFirst,
+   /// Evict whichever of the two candidates was accessed longer ago.
+   GlobalLru,
 }
 
-pub(crate) struct LRUkCache<K, V> {
-   map: HashMap<KeyNode<K, V>, NonNullNode<Item<K, V>>>,
+/// Counters describing how an `LRUkCache` has been used since it was created or its stats were
+/// last reset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+   /// `get` calls that found the entry in the probation segment.
+   pub fcfo_hits: u64,
+   /// `get` calls that found the entry in the protected segment.
+   pub lru_hits: u64,
+   /// `get` calls that found nothing.
+   pub misses: u64,
+   /// Accesses that moved an entry from probation to protected.
+   pub promotions: u64,
+   /// Entries evicted from the probation segment.
+   pub fcfo_evictions: u64,
+   /// Entries evicted from the protected segment.
+   pub lru_evictions: u64,
+}
+
+/// An LRU-K cache: new entries wait in a first-come-first-out probation segment (`fcfo`) and are
+/// promoted to a protected LRU segment once they have been accessed `promote_threshold` times.
+/// Eviction drains the probation segment before touching the protected one, so a scan of
+/// one-off keys cannot flush the hot set.
+///
+/// ```
+/// use rs_lru::{Cache, LRUkCache};
+///
+/// let mut cache = LRUkCache::with_capacity_freq(2, 1);
+/// cache.insert("hot", 1);
+/// cache.get(&"hot");
+/// // a scan of cold keys only churns the probation segment
+/// for key in ["a", "b", "c"] {
+///    cache.insert(key, 0);
+/// }
+/// assert_eq!(cache.get(&"hot"), Some(&1));
+/// assert_eq!(cache.len(), 2);
+/// ```
+///
+/// Like [`LRUCache`] it is `Send` and `Sync` when both `K` and `V` are:
+///
+/// ```compile_fail
+/// use rs_lru::LRUkCache;
+/// use std::cell::Cell;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let cache: LRUkCache<u64, Cell<i32>> = LRUkCache::with_capacity_freq(1, 2);
+/// assert_sync(&cache);
+/// ```
+pub struct LRUkCache<K, V> {
+   map: KeyMap<Item<K, V>>,
    fcfo: List<Item<K, V>>,
    lru: List<Item<K, V>>,
    freq: u32,
    cap: usize,
+   heap_size: Option<fn(&K, &V) -> usize>,
+   // keys evicted on insert and the freq they had, disabled while its capacity is zero
+   ghost: LRUCache<K, u32>,
+   count_writes: bool,
+   // (probation, protected) budgets, when the segments are bounded separately
+   segment_caps: Option<(usize, usize)>,
+   eviction: EvictionPreference,
+   clock: u64,
+   // move probation entries to the newest end when accessed
+   probation_by_access: bool,
+   stats: Stats,
 }
 
 impl<K: Hash + Eq, V> LRUkCache<K, V> {
-   pub fn with_capacity_freq(cap: usize, freq: u32) -> Self {
+   /// Creates a cache holding at most `cap` entries whose keys are promoted to the protected
+   /// segment after `promote_threshold` accesses. A threshold of zero promotes on insert, making
+   /// the cache a plain LRU. A capacity of zero caches nothing: every new key is rejected.
+   pub fn with_capacity_freq(cap: usize, promote_threshold: u32) -> Self {
       Self {
          map: HashMap::new(),
          fcfo: List::new(),
          lru: List::new(),
-         freq,
+         freq: promote_threshold,
          cap,
+         heap_size: None,
+         ghost: LRUCache::with_capacity(0),
+         count_writes: false,
+         segment_caps: None,
+         eviction: EvictionPreference::ProbationFirst,
+         clock: 0,
+         probation_by_access: false,
+         stats: Stats::default(),
       }
    }
 
+   pub fn stats(&self) -> &Stats {
+      &self.stats
+   }
+
+   pub fn reset_stats(&mut self) {
+      self.stats = Stats::default();
+   }
+
+   /// Orders the probation segment by last access instead of insertion: an access that does not
+   /// promote an entry moves it to the newest end, so probation evicts its least recently used
+   /// entry. Off by default.
+   pub fn set_probation_by_access(&mut self, by_access: bool) {
+      self.probation_by_access = by_access;
+   }
+
+   /// Chooses the segment evictions are taken from. See [`EvictionPreference`].
+   pub fn set_eviction_preference(&mut self, preference: EvictionPreference) {
+      self.eviction = preference;
+   }
+
+   pub fn eviction_preference(&self) -> EvictionPreference {
+      self.eviction
+   }
+
+   fn tick(&mut self) -> u64 {
+      self.clock += 1;
+      self.clock
+   }
+
+   /// Creates a cache whose probation and protected segments have their own budgets. A
+   /// promotion that overflows the protected segment demotes its least recently used entry to the
+   /// newest end of the probation segment, with its `freq` reset to zero so it has to earn its
+   /// promotion again. Overflowing the probation segment evicts its oldest entry. `len()` never
+   /// exceeds the sum of both budgets, and an insert only evicts from the segment it overflows, so
+   /// the eviction preference is left to `evict_to`. With a `freq` of zero nothing stays in
+   /// probation and the protected segment evicts instead of demoting.
+   pub fn with_segment_capacities(probation_cap: usize, protected_cap: usize, freq: u32) -> Self {
+      let mut cache = Self::with_capacity_freq(probation_cap.saturating_add(protected_cap), freq);
+      cache.segment_caps = Some((probation_cap, protected_cap));
+      cache
+   }
+
+   /// Returns the probation and protected budgets of a cache built by `with_segment_capacities`.
+   pub fn segment_capacities(&self) -> Option<(usize, usize)> {
+      self.segment_caps
+   }
+
+   /// Chooses whether `insert` counts as an access. When it does, a fresh entry starts with a
+   /// `freq` of one and overwriting an entry bumps its `freq` and recency like a `get`. When it
+   /// does not, the default, fresh entries start at zero and overwrites only swap the value.
+   pub fn set_count_writes_as_access(&mut self, count: bool) {
+      self.count_writes = count;
+   }
+
+   /// Keeps the keys of up to `cap` entries evicted to make room, without their values. When an
+   /// evicted key is inserted again it resumes from the access count it had, entering the
+   /// protected segment directly if that count reaches the threshold. Shrinking the history
+   /// forgets its least recently evicted keys, zero (the default) disables it.
+   pub fn set_ghost_capacity(&mut self, cap: usize) {
+      let mut ghost = LRUCache::with_capacity(cap);
+      ghost.absorb(&mut self.ghost);
+      self.ghost = ghost;
+   }
+
+   /// Number of evicted keys currently remembered.
+   pub fn ghost_len(&self) -> usize {
+      self.ghost.len()
+   }
+
    fn update(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      let tick = self.tick();
       let item = unsafe { &mut node.as_mut().element };
+      item.tick = tick;
       // item in lru
       if item.freq >= self.freq {
-         self.lru.splice_self_front(self.lru.begin_node(), node);
+         self.lru.move_to_front(node);
          return;
       }
       // item in fcfo
@@ -86,78 +232,812 @@ impl<K: Hash + Eq, V> LRUkCache<K, V> {
          self
             .lru
             .splice_front(self.lru.begin_node(), &mut self.fcfo, node);
+         self.stats.promotions += 1;
+         self.rebalance();
+      } else if self.probation_by_access {
+         self.fcfo.move_to_back(node);
+      }
+   }
+
+   // Demote protected entries and evict probation entries past their segment budgets
+   fn rebalance(&mut self) {
+      while let Some(node) = self.overflow() {
+         let item = self.unlink(node);
+         self.ghost.insert(item.key, item.freq);
       }
    }
 
+   // Demote protected entries past their budget and return the oldest probation entry if
+   // probation is still over its own
+   fn overflow(&mut self) -> Option<NonNullNode<Item<K, V>>> {
+      let (probation_cap, protected_cap) = self.segment_caps?;
+      while self.lru.len() > protected_cap {
+         // with a zero threshold no entry can live in probation
+         if self.freq == 0 {
+            return self.lru.end_node();
+         }
+         let mut node = self.lru.end_node().unwrap();
+         unsafe { node.as_mut().element.freq = 0 };
+         self
+            .fcfo
+            .splice_back(self.fcfo.end_node(), &mut self.lru, node);
+      }
+      self
+         .fcfo
+         .begin_node()
+         .filter(|_| self.fcfo.len() > probation_cap)
+   }
+
+   /// Creates a cache that never evicts on insert. Its capacity is `usize::MAX`.
+   pub fn unbounded_with_freq(promote_threshold: u32) -> Self {
+      Self::with_capacity_freq(usize::MAX, promote_threshold)
+   }
+
+   /// Swaps the value of an existing entry without counting the write towards its `freq`.
+   pub fn replace_no_promote(&mut self, k: &K, v: V) -> Option<V> {
+      let mut node = *self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { mem::replace(&mut node.as_mut().element.value, v) })
+   }
+
+   /// Sets a function reporting the heap memory owned by a key and its value, which
+   /// `approximate_memory_usage` adds to the cache's own allocations.
+   pub fn set_heap_size_fn(&mut self, f: fn(&K, &V) -> usize) {
+      self.heap_size = Some(f);
+   }
+
+   /// Estimates the bytes used by the cache: the map allocation, one node per entry in either
+   /// segment and whatever the heap size function reports for the keys and values.
+   pub fn approximate_memory_usage(&self) -> usize {
+      let map =
+         self.map.capacity() * (size_of::<(KeyRef<Item<K, V>>, NonNullNode<Item<K, V>>)>() + 1);
+      let nodes = (self.fcfo.len() + self.lru.len()) * size_of::<Node<Item<K, V>>>();
+      let heap = match self.heap_size {
+         Some(f) => self
+            .fcfo
+            .iter()
+            .chain(self.lru.iter())
+            .map(|item| f(&item.key, &item.value))
+            .sum(),
+         None => 0,
+      };
+      let ghost = self.ghost.approximate_memory_usage() - size_of::<LRUCache<K, u32>>();
+      size_of::<Self>() + map + nodes + ghost + heap
+   }
+
+   /// Asserts that the map and both lists agree and that every item's `freq` matches the list it
+   /// lives in.
+   #[cfg(any(test, debug_assertions))]
+   pub fn validate(&self) {
+      self.ghost.validate();
+      for (key, _) in self.ghost.iter() {
+         assert!(!self.map.contains_key(KeyWrapper::from_ref(key)));
+      }
+      self.fcfo.validate();
+      self.lru.validate();
+      assert_eq!(self.map.len(), self.fcfo.len() + self.lru.len());
+      if let Some((probation_cap, protected_cap)) = self.segment_caps {
+         assert!(self.fcfo.len() <= probation_cap);
+         assert!(self.lru.len() <= protected_cap);
+      }
+      for (key, node) in &self.map {
+         assert_eq!(key.0, *node);
+      }
+      for (list, promoted) in [(&self.fcfo, false), (&self.lru, true)] {
+         let mut cur = list.begin_node();
+         while let Some(node) = cur {
+            let item = unsafe { &node.as_ref().element };
+            assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+            assert_eq!(item.freq >= self.freq, promoted);
+            cur = list.next_node(node);
+         }
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn contains_key<Q>(&self, k: &Q) -> bool
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns the value without counting an access or moving the entry.
+   pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Returns the value mutably without counting an access or moving the entry.
+   pub fn peek_mut(&mut self, k: &K) -> Option<&mut V> {
+      let mut node = *self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &mut node.as_mut().element.value })
+   }
+
+   /// Drops every entry and the evicted key history.
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.fcfo = List::new();
+      self.lru = List::new();
+      self.ghost.clear();
+   }
+
+   /// Returns the number of entries across both segments.
    pub fn len(&self) -> usize {
       self.map.len()
    }
 
-   fn disuse(&mut self) -> Option<()> {
-      // disuse fcfo
-      if !self.fcfo.is_empty() {
-         let item = self.fcfo.front()?;
-         self.map.remove(&item.key)?;
-         self.fcfo.pop_front()?;
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   /// Returns the promotion threshold.
+   pub fn freq(&self) -> u32 {
+      self.freq
+   }
+
+   /// Changes the promotion threshold and re-segments the existing entries. Lowering it promotes
+   /// probation entries that now qualify to the cold end of the protected segment, oldest last.
+   /// Raising it demotes protected entries that fall short to the newest end of the probation
+   /// segment, least recently used first. Access counts are kept, so a demoted entry needs
+   /// `new_freq - freq_of(k)` more accesses to come back. Zero promotes every entry.
+   pub fn set_freq(&mut self, new_freq: u32) {
+      if new_freq < self.freq {
+         let mut cur = self.fcfo.end_node();
+         while let Some(node) = cur {
+            cur = self.fcfo.prev_node(node);
+            if unsafe { node.as_ref().element.freq } >= new_freq {
+               self
+                  .lru
+                  .splice_back(self.lru.end_node(), &mut self.fcfo, node);
+            }
+         }
+      } else if new_freq > self.freq {
+         let mut cur = self.lru.end_node();
+         while let Some(node) = cur {
+            cur = self.lru.prev_node(node);
+            if unsafe { node.as_ref().element.freq } < new_freq {
+               self
+                  .fcfo
+                  .splice_back(self.fcfo.end_node(), &mut self.lru, node);
+            }
+         }
+      }
+      self.freq = new_freq;
+      self.rebalance();
+   }
+
+   /// Number of entries in the probation segment.
+   pub fn fcfo_len(&self) -> usize {
+      self.fcfo.len()
+   }
+
+   /// Number of entries in the protected segment.
+   pub fn lru_len(&self) -> usize {
+      self.lru.len()
+   }
+
+   /// Returns how many accesses the entry has counted, without touching it. Counting stops once
+   /// the entry is promoted.
+   pub fn freq_of(&self, k: &K) -> Option<u32> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { node.as_ref().element.freq })
+   }
+
+   /// Returns the segment holding the entry, without touching it.
+   pub fn segment_of(&self, k: &K) -> Option<Segment> {
+      self.freq_of(k).map(|freq| {
+         if freq >= self.freq {
+            Segment::Lru
+         } else {
+            Segment::Fcfo
+         }
+      })
+   }
+
+   /// Removes every cached key of `keys` and returns the removed pairs. Absent and repeated keys
+   /// are skipped.
+   pub fn remove_many<'a, I: IntoIterator<Item = &'a K>>(&mut self, keys: I) -> Vec<(K, V)>
+   where
+      K: 'a,
+   {
+      keys.into_iter().filter_map(|k| self.pop_entry(k)).collect()
+   }
+
+   /// Removes the entry and returns both the stored key and its value.
+   pub fn pop_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.map.remove(KeyWrapper::from_ref(k))?;
+      let item: &Item<K, V> = unsafe { &node.as_ref().element };
+      // in lru list
+      let item = if item.freq >= self.freq {
+         self.lru.remove_node(node)
+      } else {
+         // in fcfo list
+         self.fcfo.remove_node(node)
+      };
+      Some((item.key, item.value))
+   }
+
+   /// Evicts entries until at most `target_len` remain, returning them in eviction order.
+   pub fn evict_to(&mut self, target_len: usize) -> Vec<(K, V)> {
+      let mut evicted = Vec::with_capacity(self.map.len().saturating_sub(target_len));
+      while self.map.len() > target_len {
+         match self.disuse() {
+            Some(item) => evicted.push((item.key, item.value)),
+            None => break,
+         }
+      }
+      evicted
+   }
+
+   /// Takes the cache apart into its capacity, promotion threshold and entries with their access
+   /// counts. The probation (fcfo) segment comes first, oldest entry first, followed by the
+   /// protected (lru) segment from most to least recently used.
+   pub fn into_parts(mut self) -> (usize, u32, Vec<(K, V, u32)>) {
+      self.map.clear();
+      let mut entries = Vec::with_capacity(self.fcfo.len() + self.lru.len());
+      while let Some(item) = self.fcfo.pop_front() {
+         entries.push((item.key, item.value, item.freq));
+      }
+      while let Some(item) = self.lru.pop_front() {
+         entries.push((item.key, item.value, item.freq));
+      }
+      (self.cap, self.freq, entries)
+   }
+
+   /// Rebuilds a cache from `into_parts` output. Each entry joins the segment its count selects,
+   /// keeping the relative order of the vector. Entries past the capacity are dropped and a
+   /// repeated key keeps its last value and position.
+   pub fn from_parts(cap: usize, freq: u32, entries: Vec<(K, V, u32)>) -> Self {
+      let mut cache = Self::with_capacity_freq(cap, freq);
+      for (k, v, item_freq) in entries {
+         if cache.pop_entry(&k).is_none() && cache.map.len() >= cap {
+            continue;
+         }
+         let mut item = Item::new(k, v);
+         item.freq = item_freq;
+         let list = if item_freq >= freq {
+            &mut cache.lru
+         } else {
+            &mut cache.fcfo
+         };
+         list.push_back(item);
+         let node = list.end_node().unwrap();
+         cache.map.insert(KeyRef(node), node);
+      }
+      cache
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room, or the new entry itself
+   /// when its segment has no room at all. The ghost history keeps a copy of the victim's key.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V>
+   where
+      K: Clone,
+   {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return PushResult::Replaced(self.overwrite(node, v));
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      match self.admit(k, v) {
+         Some(item) => {
+            if self.ghost.capacity() > 0 {
+               self.ghost.insert(item.key.clone(), item.freq);
+            }
+            PushResult::Evicted(item.key, item.value)
+         }
+         None => PushResult::Fit,
+      }
+   }
+
+   fn overwrite(&mut self, mut node: NonNullNode<Item<K, V>>, v: V) -> V {
+      let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+      if self.count_writes {
+         self.update(node);
+      }
+      ret
+   }
+
+   // Link a new key and return the entry evicted for it, leaving its key to the caller's ghost
+   // history. With segment budgets only the segment the entry overflows gives up a victim, so
+   // there is one at most.
+   fn admit(&mut self, k: K, v: V) -> Option<Item<K, V>> {
+      // a remembered key resumes from its old freq
+      let freq = self.ghost.remove(&k).unwrap_or(0) + self.count_writes as u32;
+      // check cap
+      let victim = if self.segment_caps.is_none() && self.map.len() >= self.cap {
+         Some(self.disuse().expect("a full cache must have a victim"))
+      } else {
+         None
+      };
+      // make node and insert
+      let mut item = Item::new(k, v);
+      item.freq = freq;
+      item.tick = self.tick();
+      let node = if freq >= self.freq {
+         self.lru.push_front(item);
+         self.lru.begin_node()
+      } else {
+         self.fcfo.push_back(item);
+         self.fcfo.end_node()
+      }
+      .expect("end_node must not be none,because just insert in the previous statement");
+      self.map.insert(KeyRef(node), node);
+      victim.or_else(|| self.overflow().map(|node| self.unlink(node)))
+   }
+
+   // Pick the oldest probation entry or the least recently used protected one, as the eviction
+   // preference says, and unlink it
+   fn disuse(&mut self) -> Option<Item<K, V>> {
+      let node = match (self.fcfo.begin_node(), self.lru.end_node()) {
+         (None, None) => return None,
+         (Some(node), None) | (None, Some(node)) => node,
+         (Some(probation), Some(protected)) => match self.eviction {
+            EvictionPreference::ProbationFirst => probation,
+            EvictionPreference::ProtectedFirst => protected,
+            EvictionPreference::GlobalLru => {
+               let tick = |node: NonNullNode<Item<K, V>>| unsafe { node.as_ref().element.tick };
+               if tick(protected) < tick(probation) {
+                  protected
+               } else {
+                  probation
+               }
+            }
+         },
+      };
+      Some(self.unlink(node))
+   }
+
+   // Evict a node, removing it from the map and its list as one step. The map entry goes first because the
+   // map reads keys through the nodes.
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      let item = unsafe { &node.as_ref().element };
+      match self.map.get(KeyWrapper::from_ref(&item.key)) {
+         Some(&found) if found == node => {
+            self.map.remove(KeyWrapper::from_ref(&item.key));
+         }
+         // the key does not find its own entry (an inconsistent Hash or Eq), match the node
+         _ => self.map.retain(|_, found| *found != node),
+      }
+      if item.freq >= self.freq {
+         self.stats.lru_evictions += 1;
+         self.lru.remove_node(node)
       } else {
-         // disuse lru
-         let item = self.lru.back()?;
-         self.map.remove(&item.key)?;
-         self.lru.pop_back()?;
+         self.stats.fcfo_evictions += 1;
+         self.fcfo.remove_node(node)
       }
-      Some(())
    }
 }
 
+// SAFETY: the map only points into `fcfo` and `lru`, both owned by the cache
+unsafe impl<K: Send, V: Send> Send for LRUkCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for LRUkCache<K, V> {}
+
+impl<K, V> LRUkCache<K, V> {
+   /// Iterates over the probation segment from the oldest entry (the next victim) to the newest,
+   /// yielding each entry's access count. Nothing is promoted.
+   pub fn iter_probation(&self) -> SegmentIter<'_, K, V> {
+      SegmentIter(self.fcfo.iter())
+   }
+
+   /// Iterates over the protected segment from the most to the least recently used entry,
+   /// yielding each entry's access count. Nothing is promoted.
+   pub fn iter_protected(&self) -> SegmentIter<'_, K, V> {
+      SegmentIter(self.lru.iter())
+   }
+
+   /// Iterates over the protected segment and then the probation segment.
+   pub fn iter(&self) -> Iter<'_, K, V> {
+      Iter(self.iter_protected().chain(self.iter_probation()))
+   }
+}
+
+pub struct SegmentIter<'a, K, V>(list::Iter<'a, Item<K, V>>);
+
+impl<'a, K, V> Iterator for SegmentIter<'a, K, V> {
+   type Item = (&'a K, &'a V, u32);
+
+   fn next(&mut self) -> Option<Self::Item> {
+      self
+         .0
+         .next()
+         .map(|item| (&item.key, &item.value, item.freq))
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      self.0.size_hint()
+   }
+}
+
+impl<'a, K, V> DoubleEndedIterator for SegmentIter<'a, K, V> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      self
+         .0
+         .next_back()
+         .map(|item| (&item.key, &item.value, item.freq))
+   }
+}
+
+impl<'a, K, V> ExactSizeIterator for SegmentIter<'a, K, V> {}
+
+pub struct Iter<'a, K, V>(Chain<SegmentIter<'a, K, V>, SegmentIter<'a, K, V>>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+   type Item = (&'a K, &'a V, u32);
+
+   fn next(&mut self) -> Option<Self::Item> {
+      self.0.next()
+   }
+
+   fn size_hint(&self) -> (usize, Option<usize>) {
+      self.0.size_hint()
+   }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+   fn next_back(&mut self) -> Option<Self::Item> {
+      self.0.next_back()
+   }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Clone for LRUkCache<K, V> {
+   fn clone(&self) -> Self {
+      let mut cache = Self::with_capacity_freq(self.cap, self.freq);
+      cache.heap_size = self.heap_size;
+      cache.ghost = self.ghost.clone();
+      cache.count_writes = self.count_writes;
+      cache.segment_caps = self.segment_caps;
+      cache.eviction = self.eviction;
+      cache.clock = self.clock;
+      cache.probation_by_access = self.probation_by_access;
+      cache.stats = self.stats.clone();
+      cache.map.reserve(self.map.len());
+      // the clone's map must point at the clone's own nodes
+      for item in self.fcfo.iter() {
+         cache.fcfo.push_back(item.clone());
+         let node = cache.fcfo.end_node().unwrap();
+         cache.map.insert(KeyRef(node), node);
+      }
+      for item in self.lru.iter() {
+         cache.lru.push_back(item.clone());
+         let node = cache.lru.end_node().unwrap();
+         cache.map.insert(KeyRef(node), node);
+      }
+      cache
+   }
+}
+
+impl<K: Debug, V: Debug> Debug for LRUkCache<K, V> {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.debug_struct("LRUkCache")
+         .field("cap", &self.cap)
+         .field("freq", &self.freq)
+         .field("fcfo", &self.iter_probation().collect::<Vec<_>>())
+         .field("lru", &self.iter_protected().collect::<Vec<_>>())
+         .finish()
+   }
+}
+
+impl<K: Hash + Eq, V> Default for LRUkCache<K, V> {
+   /// Creates an LRU-2 cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity_freq(DEFAULT_CAPACITY, 2)
+   }
+}
+
+// Two caches are equal when both segments hold the same items in the same order
+impl<K: PartialEq, V: PartialEq> PartialEq for LRUkCache<K, V> {
+   fn eq(&self, other: &Self) -> bool {
+      fn same<K: PartialEq, V: PartialEq>(a: &List<Item<K, V>>, b: &List<Item<K, V>>) -> bool {
+         a.len() == b.len()
+            && a
+               .iter()
+               .zip(b.iter())
+               .all(|(a, b)| a.key == b.key && a.value == b.value && a.freq == b.freq)
+      }
+      same(&self.fcfo, &other.fcfo) && same(&self.lru, &other.lru)
+   }
+}
+
+impl<K: Eq, V: Eq> Eq for LRUkCache<K, V> {}
+
 impl<K: Hash + Eq, V> Cache<K, V> for LRUkCache<K, V> {
    fn get(&mut self, k: &K) -> Option<&V> {
-      let op = self.map.get(k);
-      if let Some(&node) = op {
-         self.update(node);
-         let value = unsafe { &node.as_ref().element.value };
-         return Some(value);
-      }
-      None
+      self.get_borrowed(k)
    }
 
    fn insert(&mut self, k: K, v: V) -> Option<V> {
       // check cache
       // cache exist
-      if let Some(node) = self.map.get(&k) {
-         let mut node = *node;
-         let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
-         self.update(node);
-         return Some(ret);
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return Some(self.overwrite(node, v));
       }
       // cache not exist
-      // check cap
-      if self.map.len() + 1 > self.cap {
-         self.disuse();
+      if self.cap == 0 {
+         return None;
+      }
+      if let Some(item) = self.admit(k, v) {
+         self.ghost.insert(item.key, item.freq);
       }
-      // make node and insert
-      self.fcfo.push_back(Item::new(k, v));
-      let node = self
-         .fcfo
-         .end_node()
-         .expect("end_node must not be none,because just insert in the previous statement");
-      let key = KeyNode(node);
-      self.map.insert(key, node);
       None
    }
 
    fn remove(&mut self, k: &K) -> Option<V> {
-      let node = self.map.remove(k)?;
-      let item: &Item<K, V> = unsafe { &node.as_ref().element };
-      // in lru list
-      if item.freq >= self.freq {
-         return Some(self.lru.remove_node(node).value);
+      self.pop_entry(k).map(|(_, v)| v)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      LRUkCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      LRUkCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      LRUkCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      LRUkCache::clear(self)
+   }
+
+   /// Visits the protected segment from the most recently used entry, then the probation segment
+   /// from the next victim, like `iter`.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.iter().for_each(|(k, v, _)| f(k, v))
+   }
+}
+
+impl<K: Hash + Eq + Borrow<Q>, V, Q: Hash + Eq + ?Sized> CacheLookup<K, V, Q> for LRUkCache<K, V> {
+   fn get_borrowed(&mut self, k: &Q) -> Option<&V> {
+      let op = self.map.get(KeyWrapper::from_ref(k));
+      if let Some(&node) = op {
+         if unsafe { node.as_ref().element.freq } >= self.freq {
+            self.stats.lru_hits += 1;
+         } else {
+            self.stats.fcfo_hits += 1;
+         }
+         self.update(node);
+         let value = unsafe { &node.as_ref().element.value };
+         return Some(value);
+      }
+      self.stats.misses += 1;
+      None
+   }
+
+   fn remove_borrowed(&mut self, k: &Q) -> Option<V> {
+      self.pop_entry(k).map(|(_, v)| v)
+   }
+
+   fn contains_key_borrowed(&self, k: &Q) -> bool {
+      self.contains_key(k)
+   }
+}
+
+struct HistoryEntry<V> {
+   value: V,
+   // access times, most recent first, at most `k` of them
+   history: VecDeque<u64>,
+}
+
+impl<V> HistoryEntry<V> {
+   // Entries without a full history sort before every entry with one. Each access time belongs
+   // to exactly one entry, so ranks never collide.
+   fn rank(&self, k: usize) -> (bool, u64) {
+      if self.history.len() >= k {
+         (true, self.history[k - 1])
+      } else {
+         (false, self.history[0])
+      }
+   }
+}
+
+/// A classic LRU-K cache. Every entry remembers the logical times of its last `k` accesses and
+/// the victim is the entry whose `k`-th most recent access is the oldest. Entries accessed fewer
+/// than `k` times are evicted first, least recently used first, so a sequential scan only
+/// displaces other scanned keys.
+///
+/// Accesses that follow the previous access of the same key within the correlated reference
+/// period replace that access instead of adding to the history, so a burst of hits counts once.
+pub struct LruK<K, V> {
+   map: HashMap<K, HistoryEntry<V>>,
+   order: BTreeMap<(bool, u64), K>,
+   cap: usize,
+   k: usize,
+   period: u64,
+   clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruK<K, V> {
+   /// Creates a cache holding at most `cap` entries ranked by their `k`-th most recent access.
+   /// A capacity of zero caches nothing.
+   ///
+   /// # Panics
+   ///
+   /// Panics if `k` is zero.
+   pub fn with_capacity_k(cap: usize, k: usize) -> Self {
+      assert!(k > 0, "LruK needs k of at least 1");
+      Self {
+         map: HashMap::new(),
+         order: BTreeMap::new(),
+         cap,
+         k,
+         period: 0,
+         clock: 0,
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn k(&self) -> usize {
+      self.k
+   }
+
+   /// Sets the correlated reference period, measured in accesses to the whole cache. An access
+   /// less than `period` ticks after the key's previous access replaces it. Zero, the default,
+   /// counts every access.
+   pub fn set_correlated_period(&mut self, period: u64) {
+      self.period = period;
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(k)
+   }
+
+   /// Returns the value without recording an access.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      self.map.get(k).map(|entry| &entry.value)
+   }
+
+   /// Drops every entry. The logical clock keeps running.
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.order.clear();
+   }
+
+   /// Returns how many accesses the entry currently remembers, at most `k`.
+   pub fn history_len(&self, k: &K) -> Option<usize> {
+      self.map.get(k).map(|entry| entry.history.len())
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if let Some(entry) = self.access(&k) {
+         return PushResult::Replaced(mem::replace(&mut entry.value, v));
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let evicted = if self.map.len() >= self.cap {
+         self.pop_victim()
+      } else {
+         None
+      };
+      self.clock += 1;
+      let entry = HistoryEntry {
+         value: v,
+         history: VecDeque::from([self.clock]),
+      };
+      self.order.insert(entry.rank(self.k), k.clone());
+      self.map.insert(k, entry);
+      match evicted {
+         Some((k, v)) => PushResult::Evicted(k, v),
+         None => PushResult::Fit,
+      }
+   }
+
+   /// Removes and returns the entry with the oldest `k`-th most recent access.
+   pub fn pop_victim(&mut self) -> Option<(K, V)> {
+      let (_, key) = self.order.pop_first()?;
+      let entry = self.map.remove(&key).expect("order and map must agree");
+      Some((key, entry.value))
+   }
+
+   /// Asserts that every entry is ranked exactly once and that histories are bounded and ordered.
+   #[cfg(any(test, debug_assertions))]
+   pub fn validate(&self) {
+      assert_eq!(self.map.len(), self.order.len());
+      assert!(self.map.len() <= self.cap);
+      for (key, entry) in &self.map {
+         assert!(!entry.history.is_empty() && entry.history.len() <= self.k);
+         assert!(entry
+            .history
+            .iter()
+            .zip(entry.history.iter().skip(1))
+            .all(|(a, b)| a > b));
+         assert!(self.order.get(&entry.rank(self.k)) == Some(key));
       }
-      // in fcfo list
-      Some(self.fcfo.remove_node(node).value)
    }
 
-   fn is_emtpy(&self) -> bool {
-      self.map.is_empty() && self.fcfo.is_empty() && self.lru.is_empty()
+   fn access(&mut self, k: &K) -> Option<&mut HistoryEntry<V>> {
+      let entry = self.map.get_mut(k)?;
+      let old = entry.rank(self.k);
+      self.clock += 1;
+      let now = self.clock;
+      if now - entry.history[0] < self.period {
+         entry.history[0] = now;
+      } else {
+         entry.history.push_front(now);
+         entry.history.truncate(self.k);
+      }
+      let key = self.order.remove(&old).expect("order and map must agree");
+      self.order.insert(entry.rank(self.k), key);
+      Some(entry)
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> Cache<K, V> for LruK<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      self.access(k).map(|entry| &entry.value)
+   }
+
+   /// Inserting counts as an access, both for new keys and for overwrites.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let entry = self.map.remove(k)?;
+      self.order.remove(&entry.rank(self.k));
+      Some(entry.value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      LruK::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      LruK::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      LruK::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      LruK::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.map.iter().for_each(|(k, entry)| f(k, &entry.value))
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for LRUkCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      LRUkCache::push(self, k, v)
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for LruK<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      LruK::push(self, k, v)
    }
 }
 
@@ -171,47 +1051,954 @@ mod tests {
 
       // fcfo:(1,10) lru:
       cache.insert(1, 10);
+      cache.validate();
       assert_eq!(cache.get(&1), Some(&10));
+      cache.validate();
       // fcfo: lru:(1,10)
       assert_eq!(cache.fcfo.len(), 0);
       assert_eq!(cache.lru.len(), 1);
       assert_eq!(cache.get(&1), Some(&10));
+      cache.validate();
       assert_eq!(cache.fcfo.len(), 0);
       // fcfo:(2,20) lru:(1,10)
       cache.insert(2, 20);
+      cache.validate();
       assert_eq!(cache.fcfo.len(), 1);
       assert_eq!(cache.lru.len(), 1);
       // fcfo:(2,20) lru:(1,10)
       assert_eq!(cache.get(&1), Some(&10));
+      cache.validate();
       assert_eq!(cache.fcfo.len(), 1);
       assert_eq!(cache.lru.len(), 1);
       // fcfo:(3,30) lru:(1,10) disuse:(2,20)
       cache.insert(3, 30);
+      cache.validate();
       assert_eq!(cache.fcfo.front().unwrap().value, 30);
       assert_eq!(cache.lru.front().unwrap().value, 10);
       assert_eq!(cache.get(&2), None);
+      cache.validate();
       // fcfo:  lru: (3,30) (1,10)
       assert_eq!(cache.get(&3), Some(&30));
+      cache.validate();
       assert!(cache.fcfo.is_empty());
       assert_eq!(cache.lru.len(), 2);
       assert_eq!(cache.lru.front().unwrap().value, 30);
       // fcfo:(4,40) lru:(3,30)  disuse:(1,10)
       cache.insert(4, 40);
+      cache.validate();
       assert_eq!(cache.fcfo.len(), 1);
       assert_eq!(cache.lru.len(), 1);
       assert_eq!(cache.lru.front().unwrap().value, 30);
       assert_eq!(cache.len(), 2);
       // fcfo:(4,40) lru:
       assert_eq!(cache.remove(&3).unwrap(), 30);
-      assert_eq!(cache.lru.is_empty(), true);
+      cache.validate();
+      assert!(cache.lru.is_empty());
       assert_eq!(cache.fcfo.front().unwrap().value, 40);
       assert_eq!(cache.len(), 1);
       // fcfo: (5,50) (6,60)
       assert_eq!(cache.insert(5, 50), None);
+      cache.validate();
       assert_eq!(cache.insert(6, 60), None);
+      cache.validate();
       // fcfo:empty
       assert_eq!(cache.remove(&5), Some(50));
+      cache.validate();
       assert_eq!(cache.remove(&6), Some(60));
-      assert_eq!(cache.is_emtpy(), true);
+      cache.validate();
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_replace_no_promote() {
+      let mut cache = LRUkCache::with_capacity_freq(2, 1);
+      // fcfo:(1,10) (2,20) lru:
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.validate();
+      // fcfo:(1,11) (2,20) lru:
+      assert_eq!(cache.replace_no_promote(&1, 11), Some(10));
+      assert_eq!(cache.replace_no_promote(&3, 30), None);
+      assert_eq!(cache.fcfo.len(), 2);
+      assert_eq!(cache.fcfo.front().unwrap().freq, 0);
+      cache.validate();
+      // fcfo:(2,20) (3,30) lru: disuse:(1,11)
+      cache.insert(3, 30);
+      assert_eq!(cache.get(&1), None);
+      assert_eq!(cache.fcfo.front().unwrap().value, 20);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_clone() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 1);
+      // fcfo:(2,20) (3,30) lru:(1,10)
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.insert(3, 30);
+      cache.get(&1);
+      let mut snapshot = cache.clone();
+      assert_eq!(snapshot.fcfo.len(), 2);
+      assert_eq!(snapshot.lru.len(), 1);
+      assert_eq!(snapshot.lru.front().unwrap().freq, 1);
+      cache.validate();
+      // fcfo:(4,40) (5,50) lru:(1,10) disuse:(2,20) (3,30)
+      snapshot.insert(4, 40);
+      snapshot.insert(5, 50);
+      assert_eq!(snapshot.remove(&1), Some(10));
+      assert_eq!(snapshot.len(), 2);
+      snapshot.validate();
+      // the original keeps its entries and segments
+      assert_eq!(cache.len(), 3);
+      assert_eq!(cache.fcfo.front().unwrap().value, 20);
+      assert_eq!(cache.fcfo.back().unwrap().value, 30);
+      assert_eq!(cache.lru.front().unwrap().value, 10);
+      assert_eq!(cache.get(&2), Some(&20));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_debug() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 2);
+      // fcfo:(1,10,1) (2,20,0) lru:(3,30,2)
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.insert(3, 30);
+      cache.get(&1);
+      cache.get(&3);
+      cache.get(&3);
+      assert_eq!(
+         format!("{:?}", cache),
+         "LRUkCache { cap: 3, freq: 2, fcfo: [(1, 10, 1), (2, 20, 0)], lru: [(3, 30, 2)] }"
+      );
+      cache.validate();
+   }
+
+   #[test]
+   fn test_default_eq() {
+      let cache: LRUkCache<i32, i32> = LRUkCache::default();
+      assert_eq!(cache.cap, DEFAULT_CAPACITY);
+      assert_eq!(cache.freq, 2);
+
+      let mut a = LRUkCache::with_capacity_freq(3, 1);
+      let mut b = LRUkCache::with_capacity_freq(3, 1);
+      // a: fcfo:(2,20) lru:(1,10)
+      a.insert(1, 10);
+      a.insert(2, 20);
+      a.get(&1);
+      a.validate();
+      // b: fcfo:(1,10) (2,20) lru:
+      b.insert(1, 10);
+      b.insert(2, 20);
+      assert_ne!(a, b);
+      b.validate();
+      // b: fcfo:(2,20) lru:(1,10)
+      b.get(&1);
+      assert_eq!(a, b);
+      b.get(&2);
+      assert_ne!(a, b);
+      b.validate();
+   }
+
+   #[test]
+   fn test_evict_to() {
+      let mut cache = LRUkCache::with_capacity_freq(4, 1);
+      // fcfo:(3,30) (4,40) lru:(2,20) (1,10)
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+      }
+      cache.get(&1);
+      cache.get(&2);
+      assert!(cache.evict_to(4).is_empty());
+      assert_eq!(cache.evict_to(1), vec![(3, 30), (4, 40), (1, 10)]);
+      assert_eq!(cache.len(), 1);
+      assert_eq!(cache.evict_to(0), vec![(2, 20)]);
+      assert!(cache.is_empty());
+      assert!(cache.evict_to(0).is_empty());
+      cache.insert(5, 50);
+      assert_eq!(cache.get(&5), Some(&50));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_pop_entry() {
+      let mut cache = LRUkCache::with_capacity_freq(2, 1);
+      // fcfo:(b,2) lru:(a,1)
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      cache.get(&String::from("a"));
+      assert_eq!(
+         cache.pop_entry(&String::from("a")),
+         Some((String::from("a"), 1))
+      );
+      assert!(cache.lru.is_empty());
+      assert_eq!(cache.pop_entry(&String::from("a")), None);
+      assert_eq!(
+         cache.pop_entry(&String::from("b")),
+         Some((String::from("b"), 2))
+      );
+      assert!(cache.fcfo.is_empty());
+      assert!(cache.map.is_empty());
+      cache.validate();
+   }
+
+   #[test]
+   fn test_approximate_memory_usage() {
+      let mut cache = LRUkCache::with_capacity_freq(100, 1);
+      let empty = cache.approximate_memory_usage();
+      let mut last = empty;
+      for i in 0..100 {
+         cache.insert(i, String::from("value"));
+         if i % 2 == 0 {
+            cache.get(&i);
+         }
+         let usage = cache.approximate_memory_usage();
+         assert!(usage > last);
+         last = usage;
+      }
+      cache.set_heap_size_fn(|_, v| v.capacity());
+      assert!(cache.approximate_memory_usage() >= last + 100 * 5);
+      cache.evict_to(0);
+      assert!(cache.approximate_memory_usage() < last);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_remove_many() {
+      let mut cache = LRUkCache::with_capacity_freq(4, 1);
+      // fcfo:(2,20) (4,40) lru:(3,30) (1,10)
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+      }
+      cache.get(&1);
+      cache.get(&3);
+      assert_eq!(cache.remove_many(&[1, 5, 2, 1]), vec![(1, 10), (2, 20)]);
+      assert_eq!(cache.len(), 2);
+      assert_eq!(cache.fcfo.len(), 1);
+      assert_eq!(cache.lru.len(), 1);
+      assert_eq!(cache.remove_many(&[3, 4, 3, 4]), vec![(3, 30), (4, 40)]);
+      assert!(cache.is_empty());
+      cache.validate();
+   }
+
+   #[test]
+   fn test_capacity_zero_and_unbounded() {
+      let mut cache = LRUkCache::with_capacity_freq(0, 1);
+      assert_eq!(cache.insert(1, 10), None);
+      assert_eq!(cache.get(&1), None);
+      assert!(cache.is_empty());
+      cache.validate();
+
+      let mut cache = LRUkCache::with_capacity_freq(1, 1);
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      assert_eq!(cache.len(), 1);
+      assert_eq!(cache.get(&2), Some(&20));
+      cache.insert(3, 30);
+      assert_eq!(cache.len(), 1);
+      assert_eq!(cache.get(&3), Some(&30));
+      cache.validate();
+
+      let mut cache = LRUkCache::unbounded_with_freq(1);
+      for i in 0..5000 {
+         cache.insert(i, i);
+         if i % 2 == 0 {
+            cache.get(&i);
+         }
+      }
+      assert_eq!(cache.len(), 5000);
+      assert_eq!(cache.lru.len(), 2500);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_into_from_parts() {
+      let mut cache = LRUkCache::with_capacity_freq(5, 2);
+      // fcfo:(2,20,1) (4,40,0) (5,50,0) lru:(1,10,2) (3,30,2)
+      for i in 1..=5 {
+         cache.insert(i, i * 10);
+      }
+      for k in [1, 1, 2, 3, 3, 1] {
+         cache.get(&k);
+      }
+      let (cap, freq, entries) = cache.clone().into_parts();
+      assert_eq!((cap, freq), (5, 2));
+      assert_eq!(
+         entries,
+         vec![(2, 20, 1), (4, 40, 0), (5, 50, 0), (1, 10, 2), (3, 30, 2)]
+      );
+      let mut rebuilt = LRUkCache::from_parts(cap, freq, entries);
+      rebuilt.validate();
+      assert_eq!(rebuilt, cache);
+      // both caches evict the same sequence
+      for i in 6..=7 {
+         rebuilt.insert(i, i * 10);
+         cache.insert(i, i * 10);
+      }
+      rebuilt.get(&6);
+      cache.get(&6);
+      assert_eq!(rebuilt, cache);
+      assert_eq!(rebuilt.evict_to(0), cache.evict_to(0));
+      rebuilt.validate();
+
+      // overflowing entries are dropped, a repeated key keeps its last position
+      let cache = LRUkCache::from_parts(2, 1, vec![(1, 10, 0), (2, 20, 1), (1, 11, 0), (3, 30, 0)]);
+      cache.validate();
+      assert_eq!(cache.into_parts(), (2, 1, vec![(1, 11, 0), (2, 20, 1)]));
+   }
+
+   #[test]
+   fn test_freq_and_segment_of() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 2);
+      assert_eq!(cache.freq_of(&1), None);
+      assert_eq!(cache.segment_of(&1), None);
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      assert_eq!(cache.freq_of(&1), Some(0));
+      assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+      cache.get(&1);
+      assert_eq!(cache.freq_of(&1), Some(1));
+      assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 0));
+      // the second access reaches the threshold
+      cache.get(&1);
+      assert_eq!(cache.freq_of(&1), Some(2));
+      assert_eq!(cache.segment_of(&1), Some(Segment::Lru));
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (1, 1));
+      // reads do not count as accesses
+      cache.freq_of(&2);
+      cache.segment_of(&2);
+      assert_eq!(cache.freq_of(&2), Some(0));
+      cache.get(&1);
+      assert_eq!(cache.freq_of(&1), Some(2));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_set_freq() {
+      let mut cache = LRUkCache::with_capacity_freq(6, 3);
+      // fcfo:(1,10,0) (2,20,1) (3,30,2) (4,40,2) lru:(6,60,3) (5,50,3)
+      for i in 1..=6 {
+         cache.insert(i, i * 10);
+      }
+      for (k, n) in [(2, 1), (3, 2), (4, 2), (5, 3), (6, 3)] {
+         for _ in 0..n {
+            cache.get(&k);
+         }
+      }
+      assert_eq!(cache.freq(), 3);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (4, 2));
+
+      // lowering promotes 3 and 4 to the cold end, oldest last
+      cache.set_freq(2);
+      assert_eq!(cache.freq(), 2);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 4));
+      assert_eq!(cache.segment_of(&3), Some(Segment::Lru));
+      assert_eq!(cache.segment_of(&2), Some(Segment::Fcfo));
+      cache.validate();
+      // fcfo:(1,10,0) (2,20,1) lru:(6,60,3) (5,50,3) (4,40,2) (3,30,2)
+      assert_eq!(cache.evict_to(2), vec![(1, 10), (2, 20), (3, 30), (4, 40)]);
+      cache.validate();
+
+      let mut cache = LRUkCache::with_capacity_freq(4, 1);
+      // fcfo:(4,40,0) lru:(3,30,1) (2,20,1) (1,10,1)
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+         if i < 4 {
+            cache.get(&i);
+         }
+      }
+      // raising demotes the protected entries to the newest end, least recently used first
+      cache.set_freq(2);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (4, 0));
+      cache.validate();
+      // fcfo:(4,40,0) (1,10,1) (2,20,1) lru:(3,30,2)
+      cache.get(&3);
+      assert_eq!(cache.segment_of(&3), Some(Segment::Lru));
+      assert_eq!(cache.evict_to(1), vec![(4, 40), (1, 10), (2, 20)]);
+      // an unchanged threshold is a no-op
+      cache.set_freq(2);
+      assert_eq!(cache.lru_len(), 1);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_set_freq_zero() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 2);
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.get(&2);
+      cache.set_freq(0);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (0, 2));
+      cache.insert(3, 30);
+      assert_eq!(cache.segment_of(&3), Some(Segment::Lru));
+      cache.validate();
+      // raising it again sends everyone back to probation
+      cache.set_freq(1);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 1));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_iter() {
+      let mut cache = LRUkCache::with_capacity_freq(5, 2);
+      // fcfo:(2,20,1) (4,40,0) (5,50,0) lru:(3,30,2) (1,10,2)
+      for i in 1..=5 {
+         cache.insert(i, i * 10);
+      }
+      for k in [1, 2, 3, 1, 3] {
+         cache.get(&k);
+      }
+      assert_eq!(
+         cache.iter_probation().collect::<Vec<_>>(),
+         vec![(&2, &20, 1), (&4, &40, 0), (&5, &50, 0)]
+      );
+      assert_eq!(
+         cache.iter_protected().collect::<Vec<_>>(),
+         vec![(&3, &30, 2), (&1, &10, 2)]
+      );
+      assert_eq!(
+         cache.iter().map(|(k, _, _)| *k).collect::<Vec<_>>(),
+         vec![3, 1, 2, 4, 5]
+      );
+      assert_eq!(cache.iter().next_back(), Some((&5, &50, 0)));
+      assert_eq!(cache.iter().size_hint(), (5, Some(5)));
+      // iterating promotes nothing
+      assert_eq!(cache.fcfo_len(), 3);
+      assert_eq!(cache.freq_of(&2), Some(1));
+      // 1 becomes the most recently used protected entry
+      cache.get(&1);
+      assert_eq!(
+         cache
+            .iter_protected()
+            .map(|(k, _, _)| *k)
+            .collect::<Vec<_>>(),
+         vec![1, 3]
+      );
+      cache.validate();
+   }
+
+   #[test]
+   fn test_lru_k_scan() {
+      // the hot pages 1 and 2 are referenced twice, then a long sequential scan follows
+      let mut cache = LruK::with_capacity_k(4, 2);
+      let mut lru = crate::lru::LRUCache::with_capacity(4);
+      for k in [1, 2, 1, 2] {
+         cache.insert(k, k * 10);
+         lru.insert(k, k * 10);
+      }
+      for k in 100..200 {
+         cache.insert(k, k * 10);
+         lru.insert(k, k * 10);
+         cache.validate();
+      }
+      // plain LRU lost the hot pages, LRU-2 kept them
+      assert!(!lru.contains_key(&1) && !lru.contains_key(&2));
+      assert_eq!(cache.get(&1), Some(&10));
+      assert_eq!(cache.get(&2), Some(&20));
+      assert_eq!(cache.len(), 4);
+      assert!(cache.contains_key(&199));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_lru_k_victim_order() {
+      let mut cache = LruK::with_capacity_k(3, 2);
+      // t1 insert 1, t2 insert 2, t3 insert 3, t4 get 1, t5 get 3, t6 get 1
+      for k in 1..=3 {
+         cache.insert(k, k * 10);
+      }
+      cache.get(&1);
+      cache.get(&3);
+      cache.get(&1);
+      assert_eq!(cache.history_len(&1), Some(2));
+      assert_eq!(cache.peek(&2), Some(&20));
+      // 2 has a single access, then 3 (2nd last t3) before 1 (2nd last t4)
+      assert_eq!(cache.pop_victim(), Some((2, 20)));
+      assert_eq!(cache.pop_victim(), Some((3, 30)));
+      assert_eq!(cache.remove(&1), Some(10));
+      assert!(cache.is_empty());
+      cache.validate();
+   }
+
+   #[test]
+   fn test_lru_k_correlated_period() {
+      let mut cache = LruK::with_capacity_k(2, 2);
+      cache.set_correlated_period(2);
+      // t1 insert 1, t2 get 1: a correlated burst, 1 still has a single access
+      cache.insert(1, 10);
+      cache.get(&1);
+      assert_eq!(cache.history_len(&1), Some(1));
+      // t3 insert 2, t4 get 2 collapse as well, t5 get 1 is far enough from t2 to count
+      cache.insert(2, 20);
+      cache.get(&2);
+      cache.get(&1);
+      assert_eq!(cache.history_len(&1), Some(2));
+      assert_eq!(cache.history_len(&2), Some(1));
+      // 2 only has a collapsed history and is evicted first
+      cache.insert(3, 30);
+      assert!(cache.contains_key(&1));
+      assert!(!cache.contains_key(&2));
+      cache.validate();
+
+      let mut cache = LruK::with_capacity_k(0, 2);
+      assert_eq!(cache.insert(1, 10), None);
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_ghost() {
+      let run = |ghost_cap| {
+         let mut cache = LRUkCache::with_capacity_freq(2, 2);
+         cache.set_ghost_capacity(ghost_cap);
+         // fcfo:(1,10,1) (2,20,0) then a scan evicts 1 and 2
+         cache.insert(1, 10);
+         cache.get(&1);
+         cache.insert(2, 20);
+         cache.insert(3, 30);
+         cache.insert(4, 40);
+         assert_eq!(cache.freq_of(&1), None);
+         cache.validate();
+         // re-insert 1 and access it once more
+         cache.insert(1, 11);
+         cache.get(&1);
+         cache.validate();
+         cache.segment_of(&1)
+      };
+      assert_eq!(run(0), Some(Segment::Fcfo));
+      assert_eq!(run(2), Some(Segment::Lru));
+   }
+
+   #[test]
+   fn test_ghost_capacity() {
+      let mut cache = LRUkCache::with_capacity_freq(1, 1);
+      cache.set_ghost_capacity(2);
+      // 1 is promoted, then evicted by 2, 2 by 3, 3 by 4
+      cache.insert(1, 10);
+      cache.get(&1);
+      for k in 2..=4 {
+         cache.insert(k, k * 10);
+      }
+      assert_eq!(cache.ghost_len(), 2);
+      cache.validate();
+      // 1 fell out of the history and starts over
+      cache.insert(1, 11);
+      assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+      // a remembered protected key goes straight to the protected segment
+      cache.get(&1);
+      cache.insert(5, 50);
+      cache.insert(1, 13);
+      assert_eq!(cache.segment_of(&1), Some(Segment::Lru));
+      assert_eq!(cache.ghost_len(), 2);
+      cache.validate();
+      // shrinking keeps the most recently evicted keys
+      cache.set_ghost_capacity(1);
+      assert_eq!(cache.ghost_len(), 1);
+      cache.set_ghost_capacity(0);
+      assert_eq!(cache.ghost_len(), 0);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_count_writes_as_access() {
+      for count in [false, true] {
+         let mut cache = LRUkCache::with_capacity_freq(3, 3);
+         cache.set_count_writes_as_access(count);
+         // fresh insert
+         cache.insert(1, 10);
+         assert_eq!(cache.freq_of(&1), Some(count as u32));
+         // overwrite in fcfo
+         assert_eq!(cache.insert(1, 11), Some(10));
+         assert_eq!(cache.freq_of(&1), Some(2 * count as u32));
+         cache.validate();
+         // overwrite in lru: 1 and 2 are protected, 2 is the most recently used
+         cache.insert(2, 20);
+         while cache.segment_of(&1) == Some(Segment::Fcfo) {
+            cache.get(&1);
+         }
+         while cache.segment_of(&2) == Some(Segment::Fcfo) {
+            cache.get(&2);
+         }
+         assert_eq!(cache.insert(1, 12), Some(11));
+         assert_eq!(cache.freq_of(&1), Some(3));
+         let protected = cache
+            .iter_protected()
+            .map(|(k, _, _)| *k)
+            .collect::<Vec<_>>();
+         if count {
+            assert_eq!(protected, vec![1, 2]);
+         } else {
+            assert_eq!(protected, vec![2, 1]);
+         }
+         cache.validate();
+      }
+
+      // with a threshold of one a counted write promotes on insert
+      let mut cache = LRUkCache::with_capacity_freq(2, 1);
+      cache.set_count_writes_as_access(true);
+      cache.insert(1, 10);
+      assert_eq!(cache.segment_of(&1), Some(Segment::Lru));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_segment_capacities() {
+      let mut cache = LRUkCache::with_segment_capacities(2, 2, 1);
+      assert_eq!(cache.segment_capacities(), Some((2, 2)));
+      // fcfo:(3,30) (4,40) lru:(2,20) (1,10)
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+         if i <= 2 {
+            cache.get(&i);
+         }
+      }
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 2));
+      // promoting 3 overflows the protected segment and demotes 1 with its value
+      // fcfo:(4,40,0) (1,10,0) lru:(3,30,1) (2,20,1)
+      cache.get(&3);
+      assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+      assert_eq!(cache.freq_of(&1), Some(0));
+      assert_eq!(
+         cache.iter_probation().collect::<Vec<_>>(),
+         vec![(&4, &40, 0), (&1, &10, 0)]
+      );
+      assert_eq!(cache.len(), 4);
+      cache.validate();
+      // a burst of new keys only evicts from the probation segment
+      for i in 5..=9 {
+         cache.insert(i, i * 10);
+         cache.validate();
+      }
+      assert_eq!(
+         cache.iter().map(|(k, _, _)| *k).collect::<Vec<_>>(),
+         vec![3, 2, 8, 9]
+      );
+      // a burst of promotions never squeezes the probation segment below its budget
+      for i in 10..=13 {
+         cache.insert(i, i * 10);
+         cache.get(&i);
+         cache.validate();
+      }
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 2));
+      assert_eq!(
+         cache.iter().map(|(k, _, _)| *k).collect::<Vec<_>>(),
+         vec![13, 12, 10, 11]
+      );
+      // lowering the threshold respects the budgets as well
+      let mut cache = LRUkCache::with_segment_capacities(3, 1, 3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+         cache.get(&i);
+      }
+      cache.set_freq(1);
+      assert_eq!((cache.fcfo_len(), cache.lru_len()), (2, 1));
+      assert_eq!(cache.len(), 3);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_contains_key_and_capacity() {
+      let mut cache = LRUkCache::with_capacity_freq(2, 1);
+      assert_eq!(cache.capacity(), 2);
+      assert_eq!(
+         LRUkCache::<i32, i32>::unbounded_with_freq(1).capacity(),
+         usize::MAX
+      );
+      assert_eq!(
+         LRUkCache::<i32, i32>::with_segment_capacities(2, 3, 1).capacity(),
+         5
+      );
+      cache.insert(1, 10);
+      assert!(cache.contains_key(&1));
+      assert!(!cache.contains_key(&2));
+      // checking membership is not an access
+      assert_eq!(cache.freq_of(&1), Some(0));
+      cache.insert(2, 20);
+      cache.insert(3, 30);
+      assert!(!cache.contains_key(&1));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_peek() {
+      let mut cache = LRUkCache::with_capacity_freq(2, 2);
+      // fcfo:(1,10,0) (2,20,1)
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.get(&2);
+      for _ in 0..3 {
+         assert_eq!(cache.peek(&1), Some(&10));
+      }
+      assert_eq!(cache.peek(&3), None);
+      assert_eq!(cache.freq_of(&1), Some(0));
+      assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+      // 1 is still the oldest probation entry
+      cache.insert(3, 30);
+      assert!(!cache.contains_key(&1));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_clear() {
+      use std::rc::Rc;
+
+      let value = Rc::new(0);
+      let mut cache = LRUkCache::with_capacity_freq(4, 1);
+      cache.set_ghost_capacity(4);
+      for i in 0..6 {
+         cache.insert(i, Rc::clone(&value));
+         if i % 2 == 0 {
+            cache.get(&i);
+         }
+      }
+      assert_eq!(Rc::strong_count(&value), 5);
+      assert!(cache.ghost_len() > 0);
+      cache.clear();
+      // every node was dropped
+      assert_eq!(Rc::strong_count(&value), 1);
+      assert!(cache.is_empty());
+      assert_eq!(
+         (cache.fcfo_len(), cache.lru_len(), cache.ghost_len()),
+         (0, 0, 0)
+      );
+      cache.validate();
+      // the cache is usable afterwards
+      cache.insert(1, Rc::clone(&value));
+      cache.get(&1);
+      assert_eq!(cache.segment_of(&1), Some(Segment::Lru));
+      assert_eq!(cache.capacity(), 4);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_peek_mut() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 2);
+      // fcfo:(2,20,0) (3,30,0) lru:(1,10,2)
+      for i in 1..=3 {
+         cache.insert(i, i * 10);
+      }
+      cache.get(&1);
+      cache.get(&1);
+      // peeking 2 freq-many times neither counts nor promotes it
+      for _ in 0..cache.freq() {
+         *cache.peek_mut(&2).unwrap() += 1;
+         assert!(cache.peek(&2).is_some());
+      }
+      assert_eq!(cache.peek_mut(&4), None);
+      assert_eq!(cache.freq_of(&2), Some(0));
+      assert_eq!(cache.peek(&2), Some(&22));
+      // 2 is evicted from fcfo before any protected entry
+      assert_eq!(cache.evict_to(2), vec![(2, 22)]);
+      assert_eq!(cache.evict_to(1), vec![(3, 30)]);
+      assert!(cache.contains_key(&1));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_freq_zero() {
+      for segment_caps in [false, true] {
+         let mut cache = if segment_caps {
+            LRUkCache::with_segment_capacities(2, 3, 0)
+         } else {
+            LRUkCache::with_capacity_freq(5, 0)
+         };
+         cache.set_ghost_capacity(4);
+         // zero promotes on insert, the probation segment stays empty
+         for i in 0..200u32 {
+            let k = i * 7 % 11;
+            match i % 4 {
+               0 | 1 => {
+                  cache.insert(k, i);
+               }
+               2 => {
+                  cache.get(&k);
+               }
+               _ => {
+                  cache.remove(&(k + 1));
+               }
+            }
+            assert_eq!(cache.fcfo_len(), 0);
+            cache.validate();
+         }
+         assert!(cache.len() <= 5);
+         let len = cache.len();
+         assert_eq!(cache.evict_to(0).len(), len);
+         assert!(cache.is_empty());
+         cache.validate();
+      }
+   }
+
+   #[test]
+   fn test_disuse_with_unstable_hash() {
+      use std::cell::Cell;
+      use std::hash::Hasher;
+      use std::rc::Rc;
+
+      // a key whose hash changes whenever the shared salt does
+      #[derive(Debug)]
+      struct Unstable {
+         id: u32,
+         salt: Rc<Cell<u64>>,
+      }
+
+      impl PartialEq for Unstable {
+         fn eq(&self, other: &Self) -> bool {
+            self.id == other.id
+         }
+      }
+
+      impl Eq for Unstable {}
+
+      impl Hash for Unstable {
+         fn hash<H: Hasher>(&self, state: &mut H) {
+            self.id.hash(state);
+            self.salt.get().hash(state);
+         }
+      }
+
+      let salt = Rc::new(Cell::new(0));
+      let key = |id| Unstable {
+         id,
+         salt: Rc::clone(&salt),
+      };
+      let mut cache = LRUkCache::with_capacity_freq(3, 1);
+      for id in 0..3 {
+         cache.insert(key(id), id);
+      }
+      cache.get(&key(0));
+      // stored keys can no longer be relied on to find their own entries
+      salt.set(1);
+      for id in 3..10 {
+         cache.insert(key(id), id);
+         salt.set(salt.get() + 1);
+         assert_eq!(cache.len(), 3);
+         assert_eq!(cache.fcfo_len() + cache.lru_len(), 3);
+      }
+      // victims still leave the map and the lists together
+      let evicted = cache.evict_to(0);
+      assert_eq!(
+         evicted.iter().map(|(k, v)| (k.id, *v)).collect::<Vec<_>>(),
+         vec![(8, 8), (9, 9), (0, 0)]
+      );
+      assert!(cache.is_empty());
+      assert!(cache.map.is_empty());
+   }
+
+   #[test]
+   fn test_eviction_preference() {
+      let victim = |preference, touch_again| {
+         let mut cache = LRUkCache::with_capacity_freq(3, 1);
+         cache.set_eviction_preference(preference);
+         // t1 insert 1, t2 get 1, t3 insert 2, t4 insert 3: fcfo:(2,20) (3,30) lru:(1,10)
+         cache.insert(1, 10);
+         cache.get(&1);
+         cache.insert(2, 20);
+         cache.insert(3, 30);
+         if touch_again {
+            // t5 get 1: the protected entry is now the freshest
+            cache.get(&1);
+         }
+         cache.validate();
+         cache.evict_to(2)[0].0
+      };
+      use EvictionPreference::*;
+      assert_eq!(
+         LRUkCache::<i32, i32>::default().eviction_preference(),
+         ProbationFirst
+      );
+      // the protected entry is the stalest
+      assert_eq!(victim(ProbationFirst, false), 2);
+      assert_eq!(victim(ProtectedFirst, false), 1);
+      assert_eq!(victim(GlobalLru, false), 1);
+      // the oldest probation entry is the stalest
+      assert_eq!(victim(ProbationFirst, true), 2);
+      assert_eq!(victim(ProtectedFirst, true), 1);
+      assert_eq!(victim(GlobalLru, true), 2);
+
+      // insert evicts with the preference as well
+      let mut cache = LRUkCache::with_capacity_freq(2, 1);
+      cache.set_eviction_preference(ProtectedFirst);
+      cache.insert(1, 10);
+      cache.get(&1);
+      cache.insert(2, 20);
+      cache.insert(3, 30);
+      assert!(!cache.contains_key(&1));
+      assert_eq!(cache.clone().eviction_preference(), ProtectedFirst);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_probation_by_access() {
+      let survivor = |by_access| {
+         let mut cache = LRUkCache::with_capacity_freq(2, 3);
+         cache.set_probation_by_access(by_access);
+         // 1 is older but was just accessed, 2 was never touched
+         cache.insert(1, 10);
+         cache.insert(2, 20);
+         cache.get(&1);
+         assert_eq!(cache.segment_of(&1), Some(Segment::Fcfo));
+         cache.insert(3, 30);
+         cache.validate();
+         cache.contains_key(&1)
+      };
+      assert!(!survivor(false));
+      assert!(survivor(true));
+
+      // the order follows every access below the threshold
+      let mut cache = LRUkCache::with_capacity_freq(4, 3);
+      cache.set_probation_by_access(true);
+      for i in 1..=4 {
+         cache.insert(i, i * 10);
+      }
+      for k in [2, 1, 3] {
+         cache.get(&k);
+      }
+      assert_eq!(
+         cache
+            .iter_probation()
+            .map(|(k, _, _)| *k)
+            .collect::<Vec<_>>(),
+         vec![4, 2, 1, 3]
+      );
+      assert_eq!(cache.evict_to(2), vec![(4, 40), (2, 20)]);
+      cache.validate();
+   }
+
+   #[test]
+   fn test_stats() {
+      let mut cache = LRUkCache::with_capacity_freq(3, 2);
+      // fcfo:(1,10,1) (2,20,0) (3,30,0)
+      for i in 1..=3 {
+         cache.insert(i, i * 10);
+      }
+      cache.get(&1);
+      // fcfo:(2,20,0) (3,30,0) lru:(1,10,2)
+      cache.get(&1);
+      cache.get(&1);
+      cache.get(&4);
+      // fcfo:(3,30,0) (4,40,0) lru:(1,10,2) evicted:(2,20)
+      cache.insert(4, 40);
+      cache.peek(&3);
+      assert_eq!(
+         cache.stats(),
+         &Stats {
+            fcfo_hits: 2,
+            lru_hits: 1,
+            misses: 1,
+            promotions: 1,
+            fcfo_evictions: 1,
+            lru_evictions: 0,
+         }
+      );
+      cache.evict_to(0);
+      assert_eq!(cache.stats().fcfo_evictions, 3);
+      assert_eq!(cache.stats().lru_evictions, 1);
+      // explicit removals are not evictions
+      cache.insert(5, 50);
+      cache.remove(&5);
+      assert_eq!(cache.stats().fcfo_evictions, 3);
+      cache.reset_stats();
+      assert_eq!(cache.stats(), &Stats::default());
+      cache.validate();
    }
 }
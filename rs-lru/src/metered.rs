@@ -0,0 +1,211 @@
+use crate::Cache;
+use std::hash::Hash;
+
+/// Counters describing how a [`MeteredCache`] has been used since it was created or its metrics
+/// were last reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+   /// `get` calls that found the entry.
+   pub hits: u64,
+   /// `get` calls that found nothing.
+   pub misses: u64,
+   /// `insert` calls for a key that was not cached.
+   pub inserts: u64,
+   /// `insert` calls that replaced the value of a cached key.
+   pub replacements: u64,
+   /// `remove` calls that removed an entry.
+   pub removes: u64,
+   /// `remove` calls that found nothing.
+   pub remove_misses: u64,
+   /// Entries that left the cache to make room for an insert, including a new entry the cache
+   /// declined to keep.
+   pub evictions: u64,
+}
+
+impl CacheMetrics {
+   /// Returns the share of `get` calls that hit, zero before the first one.
+   pub fn hit_rate(&self) -> f64 {
+      let gets = self.hits + self.misses;
+      if gets == 0 {
+         0.0
+      } else {
+         self.hits as f64 / gets as f64
+      }
+   }
+}
+
+/// Wraps any [`Cache`] and counts what happens to it. Every call is forwarded unchanged, the
+/// only extra work is bumping the counters. Evictions are not reported by the trait, they are
+/// derived from how much `len` grew across an insert.
+///
+/// ```
+/// use rs_lru::{Cache, LRUCache, MeteredCache};
+///
+/// let mut cache = MeteredCache::new(LRUCache::with_capacity(1));
+/// cache.insert(1, "a");
+/// cache.get(&1);
+/// cache.get(&2);
+/// cache.insert(2, "b");
+/// let metrics = cache.metrics();
+/// assert_eq!((metrics.hits, metrics.misses, metrics.evictions), (1, 1, 1));
+/// assert_eq!(metrics.hit_rate(), 0.5);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MeteredCache<C> {
+   inner: C,
+   metrics: CacheMetrics,
+}
+
+impl<C> MeteredCache<C> {
+   pub fn new(inner: C) -> Self {
+      Self {
+         inner,
+         metrics: CacheMetrics::default(),
+      }
+   }
+
+   /// Returns a snapshot of the counters.
+   pub fn metrics(&self) -> CacheMetrics {
+      self.metrics
+   }
+
+   pub fn reset_metrics(&mut self) {
+      self.metrics = CacheMetrics::default();
+   }
+
+   pub fn get_ref(&self) -> &C {
+      &self.inner
+   }
+
+   /// Returns the wrapped cache. Calls made through it are not counted.
+   pub fn get_mut(&mut self) -> &mut C {
+      &mut self.inner
+   }
+
+   pub fn into_inner(self) -> C {
+      self.inner
+   }
+}
+
+impl<K: Hash + Eq, V, C: Cache<K, V>> Cache<K, V> for MeteredCache<C> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let value = self.inner.get(k);
+      if value.is_some() {
+         self.metrics.hits += 1;
+      } else {
+         self.metrics.misses += 1;
+      }
+      value
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      let before = self.inner.len();
+      let old = self.inner.insert(k, v);
+      let expected = if old.is_some() {
+         self.metrics.replacements += 1;
+         before
+      } else {
+         self.metrics.inserts += 1;
+         before + 1
+      };
+      self.metrics.evictions += expected.saturating_sub(self.inner.len()) as u64;
+      old
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let value = self.inner.remove(k);
+      if value.is_some() {
+         self.metrics.removes += 1;
+      } else {
+         self.metrics.remove_misses += 1;
+      }
+      value
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      self.inner.contains_key(k)
+   }
+
+   fn len(&self) -> usize {
+      self.inner.len()
+   }
+
+   fn capacity(&self) -> usize {
+      self.inner.capacity()
+   }
+
+   fn clear(&mut self) {
+      self.inner.clear()
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.inner.for_each_entry(f)
+   }
+
+   fn is_empty(&self) -> bool {
+      self.inner.is_empty()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{LRUCache, LRUkCache};
+
+   // Three slots, two keys evicted along the way
+   fn workload<C: Cache<u32, u32>>(cache: &mut MeteredCache<C>) {
+      for k in 0..3 {
+         assert_eq!(cache.insert(k, k), None);
+      }
+      assert_eq!(cache.get(&0), Some(&0));
+      assert_eq!(cache.get(&0), Some(&0));
+      assert_eq!(cache.get(&1), Some(&1));
+      assert_eq!(cache.get(&9), None);
+      assert_eq!(cache.insert(0, 10), Some(0));
+      assert_eq!(cache.insert(3, 3), None);
+      assert_eq!(cache.insert(4, 4), None);
+      assert_eq!(cache.remove(&4), Some(4));
+      assert_eq!(cache.remove(&4), None);
+      assert_eq!(cache.get(&0), Some(&10));
+      assert_eq!(cache.len(), 2);
+      // both policies keep the key read most
+      assert!(!cache.contains_key(&1) && !cache.contains_key(&2));
+   }
+
+   #[test]
+   fn test_metrics() {
+      let expected = CacheMetrics {
+         hits: 4,
+         misses: 1,
+         inserts: 5,
+         replacements: 1,
+         removes: 1,
+         remove_misses: 1,
+         evictions: 2,
+      };
+      let mut lru = MeteredCache::new(LRUCache::with_capacity(3));
+      workload(&mut lru);
+      assert_eq!(lru.metrics(), expected);
+      assert_eq!(lru.metrics().hit_rate(), 0.8);
+
+      let mut lru_k = MeteredCache::new(LRUkCache::with_capacity_freq(3, 2));
+      workload(&mut lru_k);
+      assert_eq!(lru_k.metrics(), expected);
+      assert_eq!(lru_k.get_ref().stats().promotions, 1);
+   }
+
+   #[test]
+   fn test_rejected_insert_and_reset() {
+      let mut cache = MeteredCache::new(LRUCache::with_capacity(0));
+      assert_eq!(cache.metrics().hit_rate(), 0.0);
+      assert_eq!(cache.insert(1, 1), None);
+      assert_eq!(cache.metrics().evictions, 1);
+      // calls through the inner cache go uncounted
+      cache.get_mut().insert(2, 2);
+      assert_eq!(cache.metrics().inserts, 1);
+      cache.clear();
+      cache.reset_metrics();
+      assert_eq!(cache.metrics(), CacheMetrics::default());
+      assert!(cache.into_inner().is_empty());
+   }
+}
@@ -0,0 +1,105 @@
+use crate::policy::{Entries, EvictionPolicy, Handle, PolicyCache};
+use std::hash::Hash;
+
+/// Most recently used: hits move to the front like with [`Lru`](crate::policy::Lru), and the
+/// front entry is the victim.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Mru;
+
+impl EvictionPolicy for Mru {
+   type Meta = ();
+
+   fn on_hit<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, ()>, h: Handle<'a>) {
+      entries.move_to_front(h);
+   }
+
+   fn choose_victim<'a, K, V>(
+      &mut self,
+      entries: &mut Entries<'a, K, V, ()>,
+   ) -> Option<Handle<'a>> {
+      entries.front()
+   }
+}
+
+/// A most recently used cache: hits move to the front like in [`LRUCache`](crate::LRUCache), but
+/// the victim is the entry used last instead of the one used longest ago. That is the right
+/// choice for cyclic scans over a data set slightly larger than the cache, where LRU always
+/// evicts the key that is needed next.
+///
+/// ```
+/// use rs_lru::{Cache, MruCache};
+///
+/// let mut cache = MruCache::with_capacity(2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// // 2 was used last and makes room
+/// cache.insert(3, "c");
+/// assert_eq!(cache.get(&2), None);
+/// assert_eq!(cache.get(&1), Some(&"a"));
+/// ```
+pub type MruCache<K, V> = PolicyCache<K, V, Mru>;
+
+impl<K: Hash + Eq, V> PolicyCache<K, V, Mru> {
+   /// Removes and returns the most recently used entry, the next victim.
+   pub fn pop_mru(&mut self) -> Option<(K, V)> {
+      self.pop_victim()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{Cache, LRUCache};
+
+   #[test]
+   fn test_evicts_most_recent() {
+      let mut cache = MruCache::with_capacity(3);
+      for i in 0..3 {
+         cache.insert(i, i);
+      }
+      cache.get(&0);
+      // 0 is the most recent now
+      assert_eq!(cache.insert(3, 3), None);
+      assert!(!cache.contains_key(&0));
+      // the new entry is the next victim, not its own
+      assert_eq!(cache.peek(&3), Some(&3));
+      cache.insert(4, 4);
+      assert!(!cache.contains_key(&3));
+      assert_eq!(cache.len(), 3);
+      // overwriting counts as a use
+      assert_eq!(cache.insert(1, 10), Some(1));
+      assert_eq!(cache.pop_mru(), Some((1, 10)));
+      assert_eq!(cache.remove(&2), Some(2));
+      cache.validate();
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(MruCache::with_capacity(0).insert(1, 1), None);
+   }
+
+   #[test]
+   fn test_cyclic_scan() {
+      const N: u32 = 10;
+      let mut mru = MruCache::with_capacity(N as usize);
+      let mut lru = LRUCache::with_capacity(N as usize);
+      let (mut mru_hits, mut lru_hits) = (0, 0);
+      for _ in 0..20 {
+         for k in 0..=N {
+            match mru.get(&k) {
+               Some(_) => mru_hits += 1,
+               None => {
+                  mru.insert(k, k);
+               }
+            }
+            match lru.get(&k) {
+               Some(_) => lru_hits += 1,
+               None => {
+                  lru.insert(k, k);
+               }
+            }
+         }
+         mru.validate();
+      }
+      assert_eq!(lru_hits, 0);
+      assert!(mru_hits > 20 * (N - 2), "only {} hits", mru_hits);
+   }
+}
@@ -0,0 +1,593 @@
+//! A cache core with a pluggable eviction policy.
+//!
+//! [`PolicyCache`] owns the map and the list of entries, the part every cache in this crate
+//! otherwise writes again, and asks an [`EvictionPolicy`] where entries go and which one leaves.
+//! The policy only sees the entries through [`Entries`] and [`Handle`]s, so writing one takes no
+//! `unsafe` code:
+//!
+//! ```
+//! use rs_lru::policy::{Entries, EvictionPolicy, Handle, PolicyCache};
+//! use rs_lru::Cache;
+//!
+//! /// Evicts the newest entry.
+//! #[derive(Default)]
+//! struct Newest;
+//!
+//! impl EvictionPolicy for Newest {
+//!    type Meta = ();
+//!
+//!    fn choose_victim<'a, K, V>(
+//!       &mut self,
+//!       entries: &mut Entries<'a, K, V, ()>,
+//!    ) -> Option<Handle<'a>> {
+//!       entries.front()
+//!    }
+//! }
+//!
+//! let mut cache: PolicyCache<_, _, Newest> = PolicyCache::with_capacity(2);
+//! cache.insert(1, "a");
+//! cache.insert(2, "b");
+//! cache.insert(3, "c");
+//! assert_eq!(cache.get(&2), None);
+//! assert_eq!(cache.get(&1), Some(&"a"));
+//! ```
+//!
+//! [`LRUCache`](crate::LRUCache) is this core with the [`Lru`] policy, plus the handles and the
+//! ordered index that need the key and value types.
+
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, Node, NonNullNode};
+use crate::lru::{PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CacheLookup, CachePush};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr::NonNull;
+
+pub use crate::lru::{Lru, LruMeta};
+
+/// Decides where the entries of a [`PolicyCache`] go and which one is evicted.
+///
+/// New entries are linked at the front before `on_insert` is called. Every callback gets the
+/// entries of the cache, which it may reorder and whose per-entry `Meta` it may update.
+pub trait EvictionPolicy {
+   /// State the policy keeps next to every entry, created with `Default` on insert.
+   type Meta: Default;
+
+   /// Called once a new entry is linked at the front.
+   fn on_insert<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, Self::Meta>, h: Handle<'a>) {
+      let _ = (entries, h);
+   }
+
+   /// Called when an entry is read through `get`, or its value replaced through `insert`.
+   fn on_hit<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, Self::Meta>, h: Handle<'a>) {
+      let _ = (entries, h);
+   }
+
+   /// Called right before an entry leaves the cache, evicted or removed. It is still linked.
+   fn on_remove<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, Self::Meta>, h: Handle<'a>) {
+      let _ = (entries, h);
+   }
+
+   /// Picks the entry to evict from a full cache, possibly reordering the entries on the way.
+   /// Only called while the cache has entries, returning `None` then rejects the new entry
+   /// instead.
+   fn choose_victim<'a, K, V>(
+      &mut self,
+      entries: &mut Entries<'a, K, V, Self::Meta>,
+   ) -> Option<Handle<'a>>;
+}
+
+/// Points at an entry of the cache whose [`Entries`] handed it out. It cannot outlive the
+/// callback it was handed to, so it always points at a live entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle<'a> {
+   node: NonNull<()>,
+   // invariant, so handles of two callbacks never mix
+   marker: PhantomData<fn(&'a ()) -> &'a ()>,
+}
+
+/// The entries of a [`PolicyCache`] as a policy sees them: a list it can walk and reorder, front
+/// to back.
+pub struct Entries<'a, K, V, M> {
+   pub(crate) list: &'a mut List<Item<K, V, M>>,
+}
+
+impl<'a, K, V, M> Entries<'a, K, V, M> {
+   pub fn len(&self) -> usize {
+      self.list.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.list.is_empty()
+   }
+
+   pub fn front(&self) -> Option<Handle<'a>> {
+      self.list.begin_node().map(handle)
+   }
+
+   pub fn back(&self) -> Option<Handle<'a>> {
+      self.list.end_node().map(handle)
+   }
+
+   /// Returns the entry behind `h`, towards the back.
+   pub fn next(&self, h: Handle<'a>) -> Option<Handle<'a>> {
+      self.list.next_node(node(h)).map(handle)
+   }
+
+   /// Returns the entry ahead of `h`, towards the front.
+   pub fn prev(&self, h: Handle<'a>) -> Option<Handle<'a>> {
+      self.list.prev_node(node(h)).map(handle)
+   }
+
+   pub fn move_to_front(&mut self, h: Handle<'a>) {
+      self.list.move_to_front(node(h));
+   }
+
+   pub fn move_to_back(&mut self, h: Handle<'a>) {
+      self.list.move_to_back(node(h));
+   }
+
+   pub fn key(&self, h: Handle<'a>) -> &K {
+      // SAFETY: handles only live as long as the callback, every entry stays linked during it
+      unsafe { &node::<K, V, M>(h).as_ref().element.key }
+   }
+
+   pub fn meta(&self, h: Handle<'a>) -> &M {
+      // SAFETY: as in `key`
+      unsafe { &node::<K, V, M>(h).as_ref().element.meta }
+   }
+
+   pub fn meta_mut(&mut self, h: Handle<'a>) -> &mut M {
+      // SAFETY: as in `key`, and `self` is borrowed mutably
+      unsafe { &mut node::<K, V, M>(h).as_mut().element.meta }
+   }
+}
+
+pub(crate) fn handle<'a, T>(node: NonNullNode<T>) -> Handle<'a> {
+   Handle {
+      node: node.cast(),
+      marker: PhantomData,
+   }
+}
+
+pub(crate) fn node<K, V, M>(h: Handle<'_>) -> NonNullNode<Item<K, V, M>> {
+   h.node.cast::<Node<Item<K, V, M>>>()
+}
+
+/// First in, first out: hits change nothing, the oldest entry is evicted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fifo;
+
+impl EvictionPolicy for Fifo {
+   type Meta = ();
+
+   fn choose_victim<'a, K, V>(
+      &mut self,
+      entries: &mut Entries<'a, K, V, ()>,
+   ) -> Option<Handle<'a>> {
+      entries.back()
+   }
+}
+
+/// A FIFO cache on the policy core.
+pub type FifoCache<K, V> = PolicyCache<K, V, Fifo>;
+
+#[derive(Clone)]
+pub(crate) struct Item<K, V, M> {
+   pub(crate) key: K,
+   pub(crate) value: V,
+   pub(crate) meta: M,
+}
+
+impl<K, V, M> Keyed for Item<K, V, M> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// A cache whose eviction is decided by the policy `P`.
+///
+/// ```
+/// use rs_lru::{Cache, FifoCache};
+///
+/// let mut cache = FifoCache::with_capacity(2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// // hits do not save the oldest entry
+/// cache.get(&1);
+/// cache.insert(3, "c");
+/// assert_eq!(cache.get(&1), None);
+/// ```
+pub struct PolicyCache<K, V, P: EvictionPolicy> {
+   pub(crate) map: KeyMap<Item<K, V, P::Meta>>,
+   pub(crate) list: List<Item<K, V, P::Meta>>,
+   pub(crate) cap: usize,
+   pub(crate) policy: P,
+}
+
+impl<K: Hash + Eq, V, P: EvictionPolicy + Default> PolicyCache<K, V, P> {
+   /// Creates a cache holding at most `cap` entries. A capacity of zero caches nothing: every
+   /// new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self::with_policy(cap, P::default())
+   }
+
+   /// Creates a cache that never evicts on insert. Its `capacity()` is `usize::MAX`.
+   pub fn unbounded() -> Self {
+      Self::with_capacity(usize::MAX)
+   }
+}
+
+impl<K: Hash + Eq, V, P: EvictionPolicy> PolicyCache<K, V, P> {
+   /// Creates a cache holding at most `cap` entries, evicting as `policy` decides.
+   pub fn with_policy(cap: usize, policy: P) -> Self {
+      Self {
+         map: HashMap::new(),
+         list: List::new(),
+         cap,
+         policy,
+      }
+   }
+
+   pub fn policy(&self) -> &P {
+      &self.policy
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   pub fn contains_key<Q>(&self, k: &Q) -> bool
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns the value and tells the policy about the hit.
+   pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.find(k)?;
+      self.hit(node);
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Returns the value without telling the policy.
+   pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.find(k)?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.find(k)?;
+      Some(self.unlink(node).value)
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. A new entry the cache
+   /// rejects, for a capacity of zero or a policy without a victim, comes back itself.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if let Some(mut node) = self.find(&k) {
+         let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+         self.hit(node);
+         return PushResult::Replaced(ret);
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let mut evicted = PushResult::Fit;
+      if self.map.len() >= self.cap {
+         match self.pop_victim() {
+            Some((victim, value)) => evicted = PushResult::Evicted(victim, value),
+            None => return PushResult::Evicted(k, v),
+         }
+      }
+      self.link(k, v);
+      evicted
+   }
+
+   /// Removes and returns the entry the policy picks as the victim.
+   pub fn pop_victim(&mut self) -> Option<(K, V)> {
+      let victim = self.victim()?;
+      let item = self.unlink(victim);
+      Some((item.key, item.value))
+   }
+
+   /// Iterates over the entries from the front to the back, without telling the policy.
+   pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+      self.list.iter().map(|item| (&item.key, &item.value))
+   }
+
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.list.clear();
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      self.list.validate();
+      assert_eq!(self.list.len(), self.map.len());
+      assert!(self.map.len() <= self.cap);
+      let mut cur = self.list.begin_node();
+      while let Some(node) = cur {
+         let key = unsafe { &node.as_ref().element.key };
+         assert_eq!(self.map.get(KeyWrapper::from_ref(key)), Some(&node));
+         cur = self.list.next_node(node);
+      }
+   }
+
+   pub(crate) fn find<Q>(&self, k: &Q) -> Option<NonNullNode<Item<K, V, P::Meta>>>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      self.map.get(KeyWrapper::from_ref(k)).copied()
+   }
+
+   pub(crate) fn meta<Q>(&self, k: &Q) -> Option<&P::Meta>
+   where
+      K: Borrow<Q>,
+      Q: Hash + Eq + ?Sized,
+   {
+      let node = self.find(k)?;
+      Some(unsafe { &node.as_ref().element.meta })
+   }
+
+   pub(crate) fn hit(&mut self, node: NonNullNode<Item<K, V, P::Meta>>) {
+      let mut entries = Entries {
+         list: &mut self.list,
+      };
+      self.policy.on_hit(&mut entries, handle(node));
+   }
+
+   // The entry the policy would evict, `None` when it rejects the new entry instead
+   pub(crate) fn victim(&mut self) -> Option<NonNullNode<Item<K, V, P::Meta>>> {
+      if self.list.is_empty() {
+         return None;
+      }
+      let mut entries = Entries {
+         list: &mut self.list,
+      };
+      self.policy.choose_victim(&mut entries).map(node)
+   }
+
+   // Link a key known to be absent at the front, leaving its place to the policy
+   pub(crate) fn link(&mut self, k: K, v: V) -> NonNullNode<Item<K, V, P::Meta>> {
+      self.list.push_front(Item {
+         key: k,
+         value: v,
+         meta: P::Meta::default(),
+      });
+      let node = self.list.begin_node().unwrap();
+      self.map.insert(KeyRef(node), node);
+      let mut entries = Entries {
+         list: &mut self.list,
+      };
+      self.policy.on_insert(&mut entries, handle(node));
+      node
+   }
+
+   pub(crate) fn unlink(&mut self, node: NonNullNode<Item<K, V, P::Meta>>) -> Item<K, V, P::Meta> {
+      let mut entries = Entries {
+         list: &mut self.list,
+      };
+      self.policy.on_remove(&mut entries, handle(node));
+      key_ref::forget(&mut self.map, node);
+      self.list.remove_node(node)
+   }
+}
+
+// SAFETY: the map points into `list`, which the cache owns like a `Vec` of its items would
+unsafe impl<K: Send, V: Send, P: EvictionPolicy + Send> Send for PolicyCache<K, V, P> where
+   P::Meta: Send
+{
+}
+unsafe impl<K: Sync, V: Sync, P: EvictionPolicy + Sync> Sync for PolicyCache<K, V, P> where
+   P::Meta: Sync
+{
+}
+
+impl<K: Hash + Eq, V, P: EvictionPolicy + Default> Default for PolicyCache<K, V, P> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V, P: EvictionPolicy> Cache<K, V> for PolicyCache<K, V, P> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      PolicyCache::get(self, k)
+   }
+
+   /// Replacing the value of a cached key counts as a hit.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      PolicyCache::remove(self, k)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      PolicyCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      PolicyCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      PolicyCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      PolicyCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.list.iter().for_each(|item| f(&item.key, &item.value))
+   }
+}
+
+impl<K: Hash + Eq, V, P: EvictionPolicy> CachePush<K, V> for PolicyCache<K, V, P> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      PolicyCache::push(self, k, v)
+   }
+}
+
+impl<K, V, P, Q> CacheLookup<K, V, Q> for PolicyCache<K, V, P>
+where
+   K: Hash + Eq + Borrow<Q>,
+   P: EvictionPolicy,
+   Q: Hash + Eq + ?Sized,
+{
+   fn get_borrowed(&mut self, k: &Q) -> Option<&V> {
+      self.get(k)
+   }
+
+   fn remove_borrowed(&mut self, k: &Q) -> Option<V> {
+      self.remove(k)
+   }
+
+   fn contains_key_borrowed(&self, k: &Q) -> bool {
+      self.contains_key(k)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_borrowed_lookup() {
+      let mut cache: PolicyCache<String, u32, Lru> = PolicyCache::with_capacity(2);
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      assert_eq!(cache.peek("a"), Some(&1));
+      // the peek left "a" the victim, the get saves it
+      assert_eq!(cache.get("a"), Some(&1));
+      cache.insert(String::from("c"), 3);
+      cache.validate();
+      assert!(cache.contains_key("a") && !cache.contains_key("b"));
+      assert_eq!(cache.get_borrowed("c"), Some(&3));
+      assert_eq!(cache.remove_borrowed("c"), Some(3));
+      assert_eq!(cache.remove("c"), None);
+      assert!(!cache.contains_key_borrowed("c"));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_same_as_lru_cache() {
+      let mut cache: PolicyCache<_, _, Lru> = PolicyCache::with_capacity(8);
+      let mut lru = crate::LRUCache::with_capacity(8);
+      let mut seed = 11u32;
+      for _ in 0..5000 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 20;
+         match (seed >> 8) % 4 {
+            0 | 1 => assert_eq!(cache.get(&k), lru.get(&k)),
+            2 => assert_eq!(cache.insert(k, seed), Cache::insert(&mut lru, k, seed)),
+            _ => assert_eq!(cache.remove(&k), lru.remove(&k)),
+         }
+         cache.validate();
+      }
+      assert!(cache.iter().eq(lru.iter()));
+   }
+
+   #[test]
+   fn test_fifo() {
+      let mut cache = FifoCache::with_capacity(3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+      }
+      // neither reads nor overwrites reorder
+      cache.get(&1);
+      assert_eq!(cache.insert(1, 10), Some(1));
+      cache.insert(4, 4);
+      cache.validate();
+      assert!(!cache.contains_key(&1));
+      assert_eq!(cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(), [4, 3, 2]);
+      assert_eq!(cache.pop_victim(), Some((2, 2)));
+      assert_eq!(cache.peek(&3), Some(&3));
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(cache.pop_victim(), None);
+      assert_eq!(FifoCache::with_capacity(0).insert(1, 1), None);
+   }
+
+   // Skips referenced entries without clearing them, and counts what leaves
+   #[derive(Default)]
+   struct SecondChance {
+      removed: usize,
+   }
+
+   impl EvictionPolicy for SecondChance {
+      type Meta = bool;
+
+      fn on_hit<'a, K, V>(&mut self, entries: &mut Entries<'a, K, V, bool>, h: Handle<'a>) {
+         *entries.meta_mut(h) = true;
+      }
+
+      fn on_remove<'a, K, V>(&mut self, _: &mut Entries<'a, K, V, bool>, _: Handle<'a>) {
+         self.removed += 1;
+      }
+
+      fn choose_victim<'a, K, V>(
+         &mut self,
+         entries: &mut Entries<'a, K, V, bool>,
+      ) -> Option<Handle<'a>> {
+         let mut cur = entries.back();
+         while let Some(h) = cur {
+            if !entries.meta(h) {
+               return Some(h);
+            }
+            cur = entries.prev(h);
+         }
+         // everything was referenced, fall back to the oldest entry
+         entries.back()
+      }
+   }
+
+   #[test]
+   fn test_custom_policy() {
+      let mut cache: PolicyCache<_, _, SecondChance> = PolicyCache::with_capacity(3);
+      for i in 1..=3 {
+         cache.insert(i, i);
+      }
+      cache.get(&1);
+      cache.insert(4, 4);
+      cache.validate();
+      // 1 was referenced, 2 was not
+      assert!(cache.contains_key(&1));
+      assert!(!cache.contains_key(&2));
+      cache.remove(&3);
+      assert_eq!(cache.policy().removed, 2);
+   }
+}
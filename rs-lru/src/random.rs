@@ -0,0 +1,284 @@
+use crate::lru::{PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem;
+
+struct Slot<V> {
+   value: V,
+   // the position of the key in `keys`
+   index: usize,
+}
+
+/// A cache that evicts a uniformly random entry, the usual baseline when comparing policies and
+/// the cheapest one to keep: a hit touches nothing but the map. The keys are kept a second time
+/// in a vector to pick the victim from, so they have to be `Clone`.
+///
+/// The victims come from a xorshift generator. Seed it with [`RandomCache::with_seed`] to get
+/// the same evictions on every run.
+///
+/// ```
+/// use rs_lru::{Cache, RandomCache};
+///
+/// let mut cache = RandomCache::with_seed(2, 42);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// cache.insert(3, "c");
+/// assert_eq!(cache.len(), 2);
+/// assert!(cache.contains_key(&3));
+/// ```
+pub struct RandomCache<K, V> {
+   map: HashMap<K, Slot<V>>,
+   keys: Vec<K>,
+   cap: usize,
+   state: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> RandomCache<K, V> {
+   /// Creates a cache holding at most `cap` entries, seeded from the process' random state. A
+   /// capacity of zero caches nothing: every new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self::with_seed(cap, RandomState::new().build_hasher().finish())
+   }
+
+   /// Creates a cache holding at most `cap` entries whose victims are picked by a generator
+   /// seeded with `seed`.
+   pub fn with_seed(cap: usize, seed: u64) -> Self {
+      Self {
+         map: HashMap::new(),
+         keys: Vec::new(),
+         cap,
+         // xorshift never leaves zero
+         state: if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+         } else {
+            seed
+         },
+      }
+   }
+
+   /// Creates a cache that never evicts on insert. Its `capacity()` is `usize::MAX`.
+   pub fn unbounded() -> Self {
+      Self::with_capacity(usize::MAX)
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(k)
+   }
+
+   /// Returns the value, exactly like `get`: no policy state is kept for hits.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      self.map.get(k).map(|slot| &slot.value)
+   }
+
+   /// Removes and returns a uniformly random entry.
+   pub fn pop_random(&mut self) -> Option<(K, V)> {
+      if self.keys.is_empty() {
+         return None;
+      }
+      let index = (self.next_random() % self.keys.len() as u64) as usize;
+      let k = self.keys.swap_remove(index);
+      self.relocate(index);
+      let slot = self.map.remove(&k).unwrap();
+      Some((k, slot.value))
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. A cache with a capacity
+   /// of zero hands back every new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if let Some(slot) = self.map.get_mut(&k) {
+         return PushResult::Replaced(mem::replace(&mut slot.value, v));
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let mut evicted = PushResult::Fit;
+      if self.map.len() >= self.cap {
+         if let Some((victim, value)) = self.pop_random() {
+            evicted = PushResult::Evicted(victim, value);
+         }
+      }
+      let index = self.keys.len();
+      self.keys.push(k.clone());
+      self.map.insert(k, Slot { value: v, index });
+      evicted
+   }
+
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.keys.clear();
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      assert_eq!(self.map.len(), self.keys.len());
+      assert!(self.map.len() <= self.cap);
+      for (index, k) in self.keys.iter().enumerate() {
+         assert_eq!(self.map[k].index, index);
+      }
+   }
+
+   // xorshift64
+   fn next_random(&mut self) -> u64 {
+      let mut x = self.state;
+      x ^= x << 13;
+      x ^= x >> 7;
+      x ^= x << 17;
+      self.state = x;
+      x
+   }
+
+   // Point the key swapped into `index` by a `swap_remove` at its new position
+   fn relocate(&mut self, index: usize) {
+      if let Some(k) = self.keys.get(index) {
+         self.map.get_mut(k).unwrap().index = index;
+      }
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> Default for RandomCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> Cache<K, V> for RandomCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      self.map.get(k).map(|slot| &slot.value)
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let slot = self.map.remove(k)?;
+      self.keys.swap_remove(slot.index);
+      self.relocate(slot.index);
+      Some(slot.value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      RandomCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      RandomCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      RandomCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      RandomCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.map.iter().for_each(|(k, slot)| f(k, &slot.value))
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for RandomCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      RandomCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_recorded_victims() {
+      let mut cache = RandomCache::with_seed(4, 7);
+      let mut victims = Vec::new();
+      for k in 0..16 {
+         let before: Vec<_> = cache.keys.clone();
+         cache.insert(k, k);
+         if let Some(&victim) = before.iter().find(|k| !cache.contains_key(k)) {
+            victims.push(victim);
+         }
+         cache.validate();
+      }
+      assert_eq!(victims, [3, 0, 5, 6, 2, 1, 8, 4, 11, 9, 12, 7]);
+      // the same seed picks the same victims again
+      let mut other = RandomCache::with_seed(4, 7);
+      for k in 0..16 {
+         other.insert(k, k);
+      }
+      assert_eq!(other.keys, cache.keys);
+   }
+
+   #[test]
+   fn test_bounded() {
+      let mut cache = RandomCache::with_seed(8, 1);
+      let mut seed = 3u32;
+      for _ in 0..5000 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 32;
+         match (seed >> 8) % 4 {
+            0 | 1 => {
+               let hit = cache.get(&k).is_some();
+               assert_eq!(hit, cache.contains_key(&k));
+            }
+            2 => {
+               cache.insert(k, k);
+               assert_eq!(cache.peek(&k), Some(&k));
+            }
+            _ => {
+               cache.remove(&k);
+               assert!(!cache.contains_key(&k));
+            }
+         }
+         assert!(cache.len() <= cache.capacity());
+         cache.validate();
+      }
+   }
+
+   #[test]
+   fn test_remove_keeps_keys_consistent() {
+      let mut cache = RandomCache::with_seed(usize::MAX, 9);
+      for k in 0..10 {
+         cache.insert(k, k * 10);
+      }
+      // the last key fills the hole left by the first
+      assert_eq!(cache.remove(&0), Some(0));
+      assert_eq!(cache.keys[0], 9);
+      cache.validate();
+      assert_eq!(cache.remove(&9), Some(90));
+      assert_eq!(cache.remove(&9), None);
+      cache.validate();
+      // removing the last key moves nothing
+      let last = *cache.keys.last().unwrap();
+      cache.remove(&last);
+      cache.validate();
+      while let Some((k, v)) = cache.pop_random() {
+         assert_eq!(v, k * 10);
+         cache.validate();
+      }
+      assert!(cache.is_empty());
+      assert_eq!(cache.insert(1, 1), None);
+      assert_eq!(cache.insert(1, 2), Some(1));
+      cache.clear();
+      cache.validate();
+      assert_eq!(RandomCache::with_capacity(0).insert(1, 1), None);
+   }
+}
@@ -0,0 +1,353 @@
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, NonNullNode};
+use crate::lru::{PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+struct Item<K, V> {
+   key: K,
+   value: V,
+   protected: bool,
+}
+
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// A segmented LRU cache. New keys enter the probationary segment and a hit there promotes them
+/// to the protected segment. When the protected segment overflows its least recently used entry
+/// is demoted back to the front of the probationary segment instead of leaving the cache, so only
+/// the probationary segment ever evicts.
+///
+/// Unlike [`LRUkCache`](crate::LRUkCache) both segments have a fixed capacity of their own.
+///
+/// ```
+/// use rs_lru::{Cache, SlruCache};
+///
+/// let mut cache = SlruCache::with_capacities(2, 1);
+/// cache.insert("hot", 1);
+/// cache.get(&"hot");
+/// // cold keys only churn the probationary segment
+/// for key in ["a", "b", "c"] {
+///    cache.insert(key, 0);
+/// }
+/// assert_eq!(cache.get(&"hot"), Some(&1));
+/// assert_eq!((cache.probation_len(), cache.protected_len()), (2, 1));
+/// ```
+pub struct SlruCache<K, V> {
+   map: KeyMap<Item<K, V>>,
+   probation: List<Item<K, V>>,
+   protected: List<Item<K, V>>,
+   probation_cap: usize,
+   protected_cap: usize,
+}
+
+impl<K: Hash + Eq, V> SlruCache<K, V> {
+   /// Creates a cache whose segments hold at most `probation` and `protected` entries. A
+   /// probationary capacity of zero caches nothing: every new key is rejected. A protected
+   /// capacity of zero makes the cache a plain LRU over the probationary segment.
+   pub fn with_capacities(probation: usize, protected: usize) -> Self {
+      Self {
+         map: HashMap::new(),
+         probation: List::new(),
+         protected: List::new(),
+         probation_cap: probation,
+         protected_cap: protected,
+      }
+   }
+
+   /// Returns the total capacity of both segments.
+   pub fn capacity(&self) -> usize {
+      self.probation_cap.saturating_add(self.protected_cap)
+   }
+
+   /// Returns the (probation, protected) capacities.
+   pub fn capacities(&self) -> (usize, usize) {
+      (self.probation_cap, self.protected_cap)
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   pub fn probation_len(&self) -> usize {
+      self.probation.len()
+   }
+
+   pub fn protected_len(&self) -> usize {
+      self.protected.len()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns whether the entry lives in the protected segment, `None` if it is not cached.
+   pub fn is_protected(&self, k: &K) -> Option<bool> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { node.as_ref().element.protected })
+   }
+
+   /// Returns the value without promoting or moving the entry.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Returns the key the next new entry would evict once the probationary segment is full.
+   pub fn probation_victim(&self) -> Option<&K> {
+      let node = self.probation.end_node()?;
+      Some(unsafe { &node.as_ref().element.key })
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. A cache without a
+   /// probationary segment hands back every new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         let mut node = node;
+         let ret = unsafe { mem::replace(&mut node.as_mut().element.value, v) };
+         if unsafe { node.as_ref().element.protected } {
+            self.protected.move_to_front(node);
+         } else {
+            self.probation.move_to_front(node);
+         }
+         return PushResult::Replaced(ret);
+      }
+      if self.probation_cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      let mut evicted = PushResult::Fit;
+      if self.probation.len() >= self.probation_cap {
+         let victim = self.unlink(self.probation.end_node().unwrap());
+         evicted = PushResult::Evicted(victim.key, victim.value);
+      }
+      self.probation.push_front(Item {
+         key: k,
+         value: v,
+         protected: false,
+      });
+      let node = self.probation.begin_node().unwrap();
+      self.map.insert(KeyRef(node), node);
+      evicted
+   }
+
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.probation.clear();
+      self.protected.clear();
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      self.probation.validate();
+      self.protected.validate();
+      assert!(self.probation.len() <= self.probation_cap);
+      assert!(self.protected.len() <= self.protected_cap);
+      assert_eq!(self.map.len(), self.probation.len() + self.protected.len());
+      for (list, protected) in [(&self.probation, false), (&self.protected, true)] {
+         let mut cur = list.begin_node();
+         while let Some(node) = cur {
+            let item = unsafe { &node.as_ref().element };
+            assert_eq!(item.protected, protected);
+            assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+            cur = list.next_node(node);
+         }
+      }
+   }
+
+   fn touch(&mut self, mut node: NonNullNode<Item<K, V>>) {
+      if unsafe { node.as_ref().element.protected } {
+         self.protected.move_to_front(node);
+         return;
+      }
+      self
+         .protected
+         .splice_front(self.protected.begin_node(), &mut self.probation, node);
+      unsafe { node.as_mut().element.protected = true };
+      // the demoted entry takes the place the promoted one left, nothing is evicted
+      if self.protected.len() > self.protected_cap {
+         let mut tail = self.protected.end_node().unwrap();
+         self
+            .probation
+            .splice_front(self.probation.begin_node(), &mut self.protected, tail);
+         unsafe { tail.as_mut().element.protected = false };
+      }
+   }
+
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      key_ref::forget(&mut self.map, node);
+      if unsafe { node.as_ref().element.protected } {
+         self.protected.remove_node(node)
+      } else {
+         self.probation.remove_node(node)
+      }
+   }
+}
+
+// SAFETY: the map only points into the two segments, owned by the cache
+unsafe impl<K: Send, V: Send> Send for SlruCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for SlruCache<K, V> {}
+
+impl<K: Hash + Eq, V> Default for SlruCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries, a fifth of them on probation.
+   fn default() -> Self {
+      let probation = DEFAULT_CAPACITY / 5;
+      Self::with_capacities(probation, DEFAULT_CAPACITY - probation)
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for SlruCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      self.touch(node);
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Replacing the value of a cached key refreshes it within its segment but does not promote
+   /// it, only reads do.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      Some(self.unlink(node).value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      SlruCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      SlruCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      SlruCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      SlruCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self
+         .protected
+         .iter()
+         .chain(self.probation.iter())
+         .for_each(|item| f(&item.key, &item.value))
+   }
+}
+
+impl<K: Hash + Eq, V> CachePush<K, V> for SlruCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      SlruCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_promote_and_demote() {
+      let mut cache = SlruCache::with_capacities(2, 2);
+      cache.insert(1, 1);
+      cache.insert(2, 2);
+      assert_eq!(cache.is_protected(&1), Some(false));
+      cache.get(&1);
+      cache.get(&2);
+      assert_eq!((cache.probation_len(), cache.protected_len()), (0, 2));
+      cache.insert(3, 3);
+      // protected is full: promoting 3 demotes 1, the least recently used protected entry
+      cache.get(&3);
+      cache.validate();
+      assert_eq!(cache.is_protected(&1), Some(false));
+      assert_eq!(cache.is_protected(&3), Some(true));
+      assert_eq!((cache.probation_len(), cache.protected_len()), (1, 2));
+      // a hit brings the demoted entry back, demoting 2 in turn
+      cache.get(&1);
+      cache.validate();
+      assert_eq!(cache.is_protected(&2), Some(false));
+      assert_eq!(cache.is_protected(&1), Some(true));
+   }
+
+   #[test]
+   fn test_demotion_cascade() {
+      let mut cache = SlruCache::with_capacities(2, 1);
+      cache.insert(1, 1);
+      cache.get(&1);
+      cache.insert(2, 2);
+      cache.insert(3, 3);
+      assert_eq!(cache.len(), 3);
+      // promoting 2 demotes 1 to the front of probation, ahead of 3
+      cache.get(&2);
+      cache.validate();
+      assert_eq!(cache.is_protected(&1), Some(false));
+      // the next miss evicts 3, the demoted entry survives it
+      assert_eq!(cache.probation_victim(), Some(&3));
+      cache.insert(4, 4);
+      assert!(!cache.contains_key(&3));
+      assert!(cache.contains_key(&1));
+      // and goes with the miss after that
+      assert_eq!(cache.push(5, 5), PushResult::Evicted(1, 1));
+      assert!(!cache.contains_key(&1));
+      cache.validate();
+   }
+
+   #[test]
+   fn test_bounded_by_segments() {
+      let mut cache = SlruCache::with_capacities(3, 4);
+      let mut seed = 5u32;
+      for _ in 0..2000 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 16;
+         match (seed >> 8) % 4 {
+            0 | 1 => {
+               cache.get(&k);
+            }
+            2 => {
+               cache.insert(k, k);
+            }
+            _ => {
+               cache.remove(&k);
+            }
+         }
+         assert!(cache.len() <= cache.capacity());
+         cache.validate();
+      }
+      // overwrites keep the segment
+      cache.clear();
+      cache.insert(1, 1);
+      assert_eq!(cache.insert(1, 2), Some(1));
+      assert_eq!(cache.is_protected(&1), Some(false));
+      assert_eq!(cache.peek(&1), Some(&2));
+
+      let mut lru = SlruCache::with_capacities(2, 0);
+      lru.insert(1, 1);
+      lru.insert(2, 2);
+      lru.get(&1);
+      lru.insert(3, 3);
+      lru.validate();
+      assert!(!lru.contains_key(&2));
+      assert_eq!(SlruCache::with_capacities(0, 4).insert(1, 1), None);
+      // a cache without probation hands the new entry straight back
+      assert_eq!(
+         SlruCache::with_capacities(0, 4).push(1, 1),
+         PushResult::Evicted(1, 1)
+      );
+   }
+}
@@ -0,0 +1,271 @@
+//! Trivial caches bounding what any policy can achieve: [`UnboundedCache`] keeps everything and
+//! [`NoopCache`] keeps nothing. Both are handy as drop-in parameters for code generic over
+//! [`Cache`].
+
+use crate::lru::PushResult;
+use crate::{Cache, CacheLookup, CachePush};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+
+/// A `HashMap` that never evicts, the perfect cache every policy is measured against. Its
+/// `capacity()` is `usize::MAX`.
+///
+/// ```
+/// use rs_lru::testing::UnboundedCache;
+/// use rs_lru::Cache;
+///
+/// let mut cache = UnboundedCache::new();
+/// for k in 0..1000 {
+///    cache.insert(k, k);
+/// }
+/// assert_eq!(cache.len(), 1000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnboundedCache<K, V> {
+   map: HashMap<K, V>,
+}
+
+impl<K: Hash + Eq, V> UnboundedCache<K, V> {
+   pub fn new() -> Self {
+      Self {
+         map: HashMap::new(),
+      }
+   }
+
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      self.map.get(k)
+   }
+
+   pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+      self.map.iter()
+   }
+}
+
+impl<K: Hash + Eq, V> Default for UnboundedCache<K, V> {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for UnboundedCache<K, V> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      self.map.get(k)
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      self.map.insert(k, v)
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      self.map.remove(k)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(k)
+   }
+
+   fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   fn capacity(&self) -> usize {
+      usize::MAX
+   }
+
+   fn clear(&mut self) {
+      self.map.clear()
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.map.iter().for_each(|(k, v)| f(k, v))
+   }
+}
+
+impl<K: Hash + Eq + Borrow<Q>, V, Q: Hash + Eq + ?Sized> CacheLookup<K, V, Q>
+   for UnboundedCache<K, V>
+{
+   fn get_borrowed(&mut self, k: &Q) -> Option<&V> {
+      self.map.get(k)
+   }
+
+   fn remove_borrowed(&mut self, k: &Q) -> Option<V> {
+      self.map.remove(k)
+   }
+
+   fn contains_key_borrowed(&self, k: &Q) -> bool {
+      self.map.contains_key(k)
+   }
+}
+
+impl<K: Hash + Eq, V> CachePush<K, V> for UnboundedCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      match self.map.get_mut(&k) {
+         Some(old) => PushResult::Replaced(mem::replace(old, v)),
+         None => {
+            self.map.insert(k, v);
+            PushResult::Fit
+         }
+      }
+   }
+}
+
+/// A cache that stores nothing: every `get` misses and every insert is dropped on the spot, the
+/// baseline of running without a cache at all. Its `capacity()` is zero.
+///
+/// ```
+/// use rs_lru::testing::NoopCache;
+/// use rs_lru::Cache;
+///
+/// let mut cache = NoopCache::new();
+/// assert_eq!(cache.insert(1, "a"), None);
+/// assert_eq!(cache.get(&1), None);
+/// ```
+pub struct NoopCache<K, V> {
+   // no K or V is ever owned, so neither affects auto traits
+   marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> NoopCache<K, V> {
+   pub fn new() -> Self {
+      Self {
+         marker: PhantomData,
+      }
+   }
+}
+
+impl<K, V> Default for NoopCache<K, V> {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl<K, V> Clone for NoopCache<K, V> {
+   fn clone(&self) -> Self {
+      Self::new()
+   }
+}
+
+impl<K, V> Debug for NoopCache<K, V> {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("NoopCache")
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for NoopCache<K, V> {
+   fn get(&mut self, _: &K) -> Option<&V> {
+      None
+   }
+
+   fn insert(&mut self, _: K, _: V) -> Option<V> {
+      None
+   }
+
+   fn remove(&mut self, _: &K) -> Option<V> {
+      None
+   }
+
+   fn contains_key(&self, _: &K) -> bool {
+      false
+   }
+
+   fn len(&self) -> usize {
+      0
+   }
+
+   fn capacity(&self) -> usize {
+      0
+   }
+
+   fn clear(&mut self) {}
+
+   fn for_each_entry(&self, _: &mut dyn FnMut(&K, &V)) {}
+}
+
+impl<K: Hash + Eq + Borrow<Q>, V, Q: Hash + Eq + ?Sized> CacheLookup<K, V, Q> for NoopCache<K, V> {
+   fn get_borrowed(&mut self, _: &Q) -> Option<&V> {
+      None
+   }
+
+   fn remove_borrowed(&mut self, _: &Q) -> Option<V> {
+      None
+   }
+
+   fn contains_key_borrowed(&self, _: &Q) -> bool {
+      false
+   }
+}
+
+impl<K: Hash + Eq, V> CachePush<K, V> for NoopCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      PushResult::Evicted(k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{LRUCache, MeteredCache};
+   use std::collections::HashSet;
+
+   // Zipf-ish reads over 64 keys, filling misses
+   fn hits<C: Cache<u32, u32>>(cache: C) -> u64 {
+      let mut cache = MeteredCache::new(cache);
+      let mut seed = 11u32;
+      for _ in 0..4000 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let r = (seed >> 16) % 64;
+         let k = r * r / 64;
+         if cache.get(&k).is_none() {
+            cache.insert(k, k);
+         }
+         assert!(cache.len() <= cache.capacity());
+      }
+      cache.metrics().hits
+   }
+
+   #[test]
+   fn test_bounds() {
+      let none = hits(NoopCache::new());
+      let lru = hits(LRUCache::with_capacity(8));
+      let all = hits(UnboundedCache::new());
+      assert_eq!(none, 0);
+      assert!(none < lru && lru < all, "{none} {lru} {all}");
+      // the perfect cache only misses each key once
+      let distinct = (0..64).map(|r| r * r / 64).collect::<HashSet<_>>();
+      assert_eq!(all, 4000 - distinct.len() as u64);
+   }
+
+   #[test]
+   fn test_full_trait() {
+      let mut cache = UnboundedCache::new();
+      assert_eq!(cache.insert(String::from("a"), 1), None);
+      assert!(matches!(
+         cache.push(String::from("a"), 2),
+         PushResult::Replaced(1)
+      ));
+      assert!(matches!(cache.push(String::from("b"), 3), PushResult::Fit));
+      assert_eq!(cache.get_borrowed("a"), Some(&2));
+      assert!(cache.contains_key_borrowed("b"));
+      assert_eq!(cache.remove_borrowed("b"), Some(3));
+      assert_eq!(cache.remove(&String::from("a")), Some(2));
+      assert!(cache.is_empty());
+      cache.insert(String::from("c"), 4);
+      cache.clear();
+      assert_eq!(cache.len(), 0);
+
+      let mut noop = NoopCache::new();
+      assert!(matches!(noop.push(1, 1), PushResult::Evicted(1, 1)));
+      assert!(!noop.contains_key(&1));
+      assert_eq!(noop.remove(&1), None);
+      assert_eq!(
+         CacheLookup::<u32, u32, u32>::get_borrowed(&mut noop, &1),
+         None
+      );
+      assert!(noop.is_empty());
+      assert_eq!(format!("{:?}", noop.clone()), "NoopCache");
+   }
+}
@@ -0,0 +1,378 @@
+use crate::lru::{LRUCache, PushResult, DEFAULT_CAPACITY};
+use crate::slru::SlruCache;
+use crate::{Cache, CachePush};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+// Offsets giving each of the four rows of the sketch its own mix of the 64-bit hash
+const SEEDS: [u64; 4] = [
+   0xc3a5_c85c_97cb_3127,
+   0xb492_b66f_be98_f273,
+   0x9ae1_6a3b_2f90_404f,
+   0xcbf2_9ce4_8422_2325,
+];
+
+/// A count-min sketch of 4-bit counters, sixteen to a word. Once `sample` increments have been
+/// recorded every counter is halved, so old popularity fades and the counters never saturate
+/// for long.
+struct FrequencySketch {
+   table: Vec<u64>,
+   additions: usize,
+   sample: usize,
+}
+
+impl FrequencySketch {
+   // One word per expected entry, rounded up to a power of two for masking
+   fn with_capacity(cap: usize) -> Self {
+      let len = cap.clamp(8, 1 << 24).next_power_of_two();
+      Self {
+         table: vec![0; len],
+         additions: 0,
+         sample: cap.max(1).saturating_mul(10),
+      }
+   }
+
+   // The word and the shift of the counter used by `row`. Every row runs the offset hash through
+   // the splitmix64 finalizer, so two keys colliding in one row rarely collide in the others.
+   fn slot(&self, hash: u64, row: usize) -> (usize, u32) {
+      let mut h = hash.wrapping_add(SEEDS[row]);
+      h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+      h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+      h ^= h >> 31;
+      let word = (h as usize) & (self.table.len() - 1);
+      let counter = (h >> 60) as u32;
+      (word, counter * 4)
+   }
+
+   fn frequency(&self, hash: u64) -> u8 {
+      (0..SEEDS.len())
+         .map(|row| {
+            let (word, shift) = self.slot(hash, row);
+            ((self.table[word] >> shift) & 0xf) as u8
+         })
+         .min()
+         .unwrap()
+   }
+
+   // Only the counters holding the estimate are raised, the others already overcount the key
+   // because of collisions
+   fn increment(&mut self, hash: u64) {
+      let min = self.frequency(hash);
+      if min == 0xf {
+         return;
+      }
+      for row in 0..SEEDS.len() {
+         let (word, shift) = self.slot(hash, row);
+         if ((self.table[word] >> shift) & 0xf) as u8 == min {
+            self.table[word] += 1 << shift;
+         }
+      }
+      self.additions += 1;
+      if self.additions >= self.sample {
+         self.reset();
+      }
+   }
+
+   fn reset(&mut self) {
+      for word in &mut self.table {
+         *word = (*word >> 1) & 0x7777_7777_7777_7777;
+      }
+      self.additions /= 2;
+   }
+
+   fn clear(&mut self) {
+      self.table.iter_mut().for_each(|word| *word = 0);
+      self.additions = 0;
+   }
+}
+
+/// A W-TinyLFU cache. New entries land in a small LRU window. Whatever the window evicts is a
+/// candidate for the main segmented LRU, and once that is full the candidate only gets in if a
+/// frequency sketch of recent requests rates it above the entry it would evict. Bursts of keys
+/// that are never requested again therefore die in the window instead of flushing the hot set.
+///
+/// The window takes 1% of the capacity (at least one entry), the main cache the rest, a fifth of
+/// it on probation.
+///
+/// Keys are hashed into the sketch with a [`RandomState`], so which candidates win a close call
+/// differs from run to run. Pass a fixed hasher to [`TinyLfuCache::with_hasher`] to get the same
+/// admissions on every run.
+///
+/// ```
+/// use rs_lru::{Cache, TinyLfuCache};
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::BuildHasherDefault;
+///
+/// let mut cache = TinyLfuCache::with_hasher(100, BuildHasherDefault::<DefaultHasher>::default());
+/// for _ in 0..3 {
+///    for k in 0..50 {
+///       cache.get(&k).copied().or_else(|| cache.insert(k, k));
+///    }
+/// }
+/// // a burst of one-hit wonders, a plain LRU cache would keep none of the hot keys
+/// for k in 1000..2000 {
+///    cache.insert(k, k);
+/// }
+/// assert!((0..50).all(|k| cache.contains_key(&k)));
+/// ```
+pub struct TinyLfuCache<K, V, S = RandomState> {
+   window: LRUCache<K, V>,
+   main: SlruCache<K, V>,
+   sketch: FrequencySketch,
+   hasher: S,
+   cap: usize,
+}
+
+impl<K: Hash + Eq, V> TinyLfuCache<K, V> {
+   /// Creates a cache holding at most `cap` entries, sizing the sketch from the capacity. A
+   /// capacity of zero caches nothing: every new key is rejected.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self::with_hasher(cap, RandomState::new())
+   }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> TinyLfuCache<K, V, S> {
+   /// Creates a cache holding at most `cap` entries whose sketch hashes the keys with `hasher`.
+   pub fn with_hasher(cap: usize, hasher: S) -> Self {
+      let window = if cap == 0 { 0 } else { (cap / 100).max(1) };
+      let main = cap - window;
+      let probation = if main == 0 { 0 } else { (main / 5).max(1) };
+      Self {
+         window: LRUCache::with_capacity(window),
+         main: SlruCache::with_capacities(probation, main - probation),
+         sketch: FrequencySketch::with_capacity(cap),
+         hasher,
+         cap,
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn len(&self) -> usize {
+      self.window.len() + self.main.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.len() == 0
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.window.contains_key(k) || self.main.contains_key(k)
+   }
+
+   /// Returns the value without recording a request.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      self.window.peek(k).or_else(|| self.main.peek(k))
+   }
+
+   /// Returns the sketch's estimate of how often `k` was requested recently, at most 15.
+   pub fn frequency(&self, k: &K) -> u8 {
+      self.sketch.frequency(self.hasher.hash_one(k))
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room: the window's victim if
+   /// the main cache turns it away, or the entry the main cache evicts to let it in.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      self.record(&k);
+      if self.main.contains_key(&k) {
+         return self.main.push(k, v);
+      }
+      match self.window.push(k, v) {
+         PushResult::Evicted(k, v) => self.admit(k, v),
+         pushed => pushed,
+      }
+   }
+
+   /// Drops every entry and forgets the recorded frequencies.
+   pub fn clear(&mut self) {
+      self.window.clear();
+      self.main.clear();
+      self.sketch.clear();
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      self.window.validate();
+      self.main.validate();
+      assert!(self.len() <= self.cap);
+      for (k, _) in self.window.iter() {
+         assert!(!self.main.contains_key(k));
+      }
+   }
+
+   fn record(&mut self, k: &K) {
+      let hash = self.hasher.hash_one(k);
+      self.sketch.increment(hash);
+   }
+
+   // Decide whether the window's victim may replace the main cache's victim, handing back the
+   // one that leaves
+   fn admit(&mut self, k: K, v: V) -> PushResult<K, V> {
+      let (probation, _) = self.main.capacities();
+      if self.main.probation_len() >= probation {
+         let Some(victim) = self.main.probation_victim() else {
+            // nothing is admitted into a main cache without room at all
+            return PushResult::Evicted(k, v);
+         };
+         if self.frequency(&k) <= self.frequency(victim) {
+            return PushResult::Evicted(k, v);
+         }
+      }
+      self.main.push(k, v)
+   }
+}
+
+impl<K: Hash + Eq, V> Default for TinyLfuCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Cache<K, V> for TinyLfuCache<K, V, S> {
+   fn get(&mut self, k: &K) -> Option<&V> {
+      self.record(k);
+      if self.window.contains_key(k) {
+         return Cache::get(&mut self.window, k);
+      }
+      self.main.get(k)
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      match self.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         _ => None,
+      }
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      match self.window.remove(k) {
+         Some(v) => Some(v),
+         None => self.main.remove(k),
+      }
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      TinyLfuCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      TinyLfuCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      TinyLfuCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      TinyLfuCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.window.iter().for_each(|(k, v)| f(k, v));
+      self.main.for_each_entry(f)
+   }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> CachePush<K, V> for TinyLfuCache<K, V, S> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      TinyLfuCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::collections::hash_map::DefaultHasher;
+   use std::hash::BuildHasherDefault;
+
+   type Fixed = BuildHasherDefault<DefaultHasher>;
+
+   #[test]
+   fn test_sketch() {
+      let mut sketch = FrequencySketch::with_capacity(64);
+      for _ in 0..5 {
+         sketch.increment(42);
+      }
+      sketch.increment(7);
+      assert_eq!(sketch.frequency(42), 5);
+      assert_eq!(sketch.frequency(7), 1);
+      // counters saturate at 15
+      for _ in 0..30 {
+         sketch.increment(42);
+      }
+      assert_eq!(sketch.frequency(42), 15);
+      // enough increments halve everything
+      for h in 1000..1000 + sketch.sample as u64 {
+         sketch.increment(h);
+      }
+      assert!(sketch.frequency(42) <= 8);
+      assert!(sketch.additions < sketch.sample);
+      sketch.clear();
+      assert_eq!(sketch.frequency(42), 0);
+   }
+
+   #[test]
+   fn test_burst_does_not_displace_hot_set() {
+      // more hot keys than the protected segment holds, so some of them wait on probation
+      let mut cache = TinyLfuCache::with_hasher(100, Fixed::default());
+      let hot = 0..90;
+      for _ in 0..4 {
+         for k in hot.clone() {
+            if cache.get(&k).is_none() {
+               cache.insert(k, k);
+            }
+         }
+      }
+      let on_probation: Vec<_> = hot
+         .clone()
+         .filter(|k| cache.main.is_protected(k) == Some(false))
+         .collect();
+      assert_eq!(on_probation.len(), cache.main.capacities().0);
+      let (probation, protected) = cache.main.capacities();
+      let mut slru = SlruCache::with_capacities(probation, protected);
+      for k in hot.clone() {
+         slru.insert(k, k);
+      }
+      for k in 1000..2000 {
+         cache.insert(k, k);
+         slru.insert(k, k);
+      }
+      cache.validate();
+      // only the sketch keeps them, admitting every candidate would flush probation
+      assert!(on_probation.iter().all(|k| cache.contains_key(k)));
+      assert!(on_probation.iter().all(|k| !slru.contains_key(k)));
+   }
+
+   #[test]
+   fn test_hot_newcomer_is_admitted() {
+      let mut cache = TinyLfuCache::with_hasher(50, Fixed::default());
+      for k in 0..50 {
+         cache.insert(k, k);
+      }
+      cache.validate();
+      assert!(cache.main.probation_len() > 0);
+      // a few touches rate the newcomer above the cold residents
+      for _ in 0..4 {
+         if cache.get(&500).is_none() {
+            cache.insert(500, 500);
+            // pushing it out of the window is what submits it for admission
+            cache.insert(501, 501);
+         }
+      }
+      assert!(cache.contains_key(&500));
+      assert!(cache.frequency(&500) >= 3);
+      cache.validate();
+
+      assert_eq!(cache.remove(&500), Some(500));
+      // a key in the window is replaced in place
+      cache.insert(600, 1);
+      assert_eq!(cache.insert(600, 2), Some(1));
+      assert_eq!(cache.peek(&600), Some(&2));
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(TinyLfuCache::with_capacity(0).insert(1, 1), None);
+   }
+}
@@ -0,0 +1,377 @@
+use crate::key_ref::{self, KeyMap, KeyRef, KeyWrapper, Keyed};
+use crate::list::{List, NonNullNode};
+use crate::lru::{LRUCache, PushResult, DEFAULT_CAPACITY};
+use crate::{Cache, CachePush};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+struct Item<K, V> {
+   key: K,
+   value: V,
+   // lives in `am` rather than `a1in`
+   hot: bool,
+}
+
+impl<K, V> Keyed for Item<K, V> {
+   type Key = K;
+
+   fn key(&self) -> &K {
+      &self.key
+   }
+}
+
+/// A 2Q cache (Johnson and Shasha). First-time keys enter the `A1in` FIFO and leave it without
+/// being promoted, however often they are hit there. Keys evicted from `A1in` are remembered in
+/// the `A1out` ghost queue, and a key that comes back while still remembered is admitted to the
+/// `Am` LRU, the only place hot entries live. A one-pass scan therefore only churns `A1in`.
+///
+/// ```
+/// use rs_lru::{Cache, TwoQCache};
+///
+/// let mut cache = TwoQCache::with_capacity(4);
+/// cache.insert(0, "hot");
+/// for k in 1..=4 {
+///    cache.insert(k, "cold");
+/// }
+/// // 0 was pushed out of A1in but is remembered, coming back makes it hot
+/// cache.insert(0, "hot");
+/// for k in 100..200 {
+///    cache.insert(k, "scan");
+/// }
+/// assert_eq!(cache.get(&0), Some(&"hot"));
+/// ```
+pub struct TwoQCache<K, V> {
+   map: KeyMap<Item<K, V>>,
+   a1in: List<Item<K, V>>,
+   am: List<Item<K, V>>,
+   a1out: LRUCache<K, ()>,
+   cap: usize,
+   kin: usize,
+}
+
+impl<K: Hash + Eq, V> TwoQCache<K, V> {
+   /// Creates a cache holding at most `cap` entries, with `A1in` sized to a quarter of the
+   /// capacity and `A1out` remembering half as many keys as the cache holds.
+   pub fn with_capacity(cap: usize) -> Self {
+      Self::with_fractions(cap, 0.25, 0.5)
+   }
+
+   /// Creates a cache holding at most `cap` entries. `A1in` may keep `kin * cap` entries before
+   /// `Am` has to give up space, and `A1out` remembers up to `kout * cap` evicted keys. A
+   /// capacity of zero caches nothing: every new key is rejected.
+   ///
+   /// # Panics
+   ///
+   /// Panics unless both fractions are within `0.0..=1.0`.
+   pub fn with_fractions(cap: usize, kin: f64, kout: f64) -> Self {
+      assert!(
+         (0.0..=1.0).contains(&kin),
+         "kin must be a fraction, got {}",
+         kin
+      );
+      assert!(
+         (0.0..=1.0).contains(&kout),
+         "kout must be a fraction, got {}",
+         kout
+      );
+      Self {
+         map: HashMap::new(),
+         a1in: List::new(),
+         am: List::new(),
+         a1out: LRUCache::with_capacity((cap as f64 * kout) as usize),
+         cap,
+         kin: (cap as f64 * kin) as usize,
+      }
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.cap
+   }
+
+   pub fn len(&self) -> usize {
+      self.map.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.map.is_empty()
+   }
+
+   /// Returns the number of entries in the `A1in` FIFO.
+   pub fn a1in_len(&self) -> usize {
+      self.a1in.len()
+   }
+
+   /// Returns the number of entries in the `Am` LRU.
+   pub fn am_len(&self) -> usize {
+      self.am.len()
+   }
+
+   /// Returns the number of evicted keys remembered by `A1out`.
+   pub fn ghost_len(&self) -> usize {
+      self.a1out.len()
+   }
+
+   pub fn contains_key(&self, k: &K) -> bool {
+      self.map.contains_key(KeyWrapper::from_ref(k))
+   }
+
+   /// Returns whether the entry lives in `Am`, `None` if it is not cached.
+   pub fn is_hot(&self, k: &K) -> Option<bool> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { node.as_ref().element.hot })
+   }
+
+   /// Returns the value without counting an access.
+   pub fn peek(&self, k: &K) -> Option<&V> {
+      let node = self.map.get(KeyWrapper::from_ref(k))?;
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   /// Like `insert`, but also hands back the entry evicted to make room. `A1out` remembers a
+   /// copy of the key of a victim from `A1in`. A cache with a capacity of zero hands back every
+   /// new entry.
+   pub fn push(&mut self, k: K, v: V) -> PushResult<K, V>
+   where
+      K: Clone,
+   {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return PushResult::Replaced(self.overwrite(node, v));
+      }
+      if self.cap == 0 {
+         return PushResult::Evicted(k, v);
+      }
+      match self.admit(k, v) {
+         Some(victim) => {
+            if !victim.hot {
+               self.a1out.insert(victim.key.clone(), ());
+            }
+            PushResult::Evicted(victim.key, victim.value)
+         }
+         None => PushResult::Fit,
+      }
+   }
+
+   /// Drops every entry and the remembered keys.
+   pub fn clear(&mut self) {
+      self.map.clear();
+      self.a1in.clear();
+      self.am.clear();
+      self.a1out.clear();
+   }
+
+   #[cfg(test)]
+   pub(crate) fn validate(&self) {
+      self.a1in.validate();
+      self.am.validate();
+      assert!(self.map.len() <= self.cap);
+      assert_eq!(self.map.len(), self.a1in.len() + self.am.len());
+      for (list, hot) in [(&self.a1in, false), (&self.am, true)] {
+         let mut cur = list.begin_node();
+         while let Some(node) = cur {
+            let item = unsafe { &node.as_ref().element };
+            assert_eq!(item.hot, hot);
+            assert_eq!(self.map.get(KeyWrapper::from_ref(&item.key)), Some(&node));
+            assert!(!self.a1out.contains_key(&item.key));
+            cur = list.next_node(node);
+         }
+      }
+   }
+
+   fn overwrite(&mut self, mut node: NonNullNode<Item<K, V>>, v: V) -> V {
+      if unsafe { node.as_ref().element.hot } {
+         self.am.move_to_front(node);
+      }
+      unsafe { mem::replace(&mut node.as_mut().element.value, v) }
+   }
+
+   // Link a new key, returning the entry evicted for it. The caller remembers the key of a
+   // victim from A1in in A1out.
+   fn admit(&mut self, k: K, v: V) -> Option<Item<K, V>> {
+      let hot = self.a1out.remove(&k).is_some();
+      let victim = (self.map.len() >= self.cap).then(|| self.reclaim());
+      let item = Item {
+         key: k,
+         value: v,
+         hot,
+      };
+      let node = if hot {
+         self.am.push_front(item);
+         self.am.begin_node()
+      } else {
+         self.a1in.push_front(item);
+         self.a1in.begin_node()
+      }
+      .unwrap();
+      self.map.insert(KeyRef(node), node);
+      victim
+   }
+
+   // Free one slot: A1in gives it up while it is over its share or Am is empty
+   fn reclaim(&mut self) -> Item<K, V> {
+      let node = if self.a1in.len() > self.kin || self.am.is_empty() {
+         self.a1in.end_node()
+      } else {
+         self.am.end_node()
+      };
+      self.unlink(node.unwrap())
+   }
+
+   fn unlink(&mut self, node: NonNullNode<Item<K, V>>) -> Item<K, V> {
+      key_ref::forget(&mut self.map, node);
+      if unsafe { node.as_ref().element.hot } {
+         self.am.remove_node(node)
+      } else {
+         self.a1in.remove_node(node)
+      }
+   }
+}
+
+// SAFETY: the map only points into `a1in` and `am`, both owned by the cache
+unsafe impl<K: Send, V: Send> Send for TwoQCache<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for TwoQCache<K, V> {}
+
+impl<K: Hash + Eq, V> Default for TwoQCache<K, V> {
+   /// Creates a cache holding up to [`DEFAULT_CAPACITY`] entries.
+   fn default() -> Self {
+      Self::with_capacity(DEFAULT_CAPACITY)
+   }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V> for TwoQCache<K, V> {
+   /// Hits in `Am` move the entry to its front, hits in `A1in` leave the FIFO order alone.
+   fn get(&mut self, k: &K) -> Option<&V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      if unsafe { node.as_ref().element.hot } {
+         self.am.move_to_front(node);
+      }
+      Some(unsafe { &node.as_ref().element.value })
+   }
+
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      if let Some(&node) = self.map.get(KeyWrapper::from_ref(&k)) {
+         return Some(self.overwrite(node, v));
+      }
+      if self.cap == 0 {
+         return None;
+      }
+      if let Some(victim) = self.admit(k, v) {
+         if !victim.hot {
+            self.a1out.insert(victim.key, ());
+         }
+      }
+      None
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let node = *self.map.get(KeyWrapper::from_ref(k))?;
+      Some(self.unlink(node).value)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      TwoQCache::contains_key(self, k)
+   }
+
+   fn len(&self) -> usize {
+      TwoQCache::len(self)
+   }
+
+   fn capacity(&self) -> usize {
+      TwoQCache::capacity(self)
+   }
+
+   fn clear(&mut self) {
+      TwoQCache::clear(self)
+   }
+
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self
+         .am
+         .iter()
+         .chain(self.a1in.iter())
+         .for_each(|item| f(&item.key, &item.value))
+   }
+}
+
+impl<K: Hash + Eq + Clone, V> CachePush<K, V> for TwoQCache<K, V> {
+   fn push(&mut self, k: K, v: V) -> PushResult<K, V> {
+      TwoQCache::push(self, k, v)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_queues() {
+      let mut cache = TwoQCache::with_fractions(4, 0.5, 0.5);
+      for i in 0..4 {
+         cache.insert(i, i);
+      }
+      // hits in A1in do not change its FIFO order
+      cache.get(&0);
+      cache.insert(4, 4);
+      assert!(!cache.contains_key(&0));
+      assert_eq!(cache.ghost_len(), 1);
+      // a remembered key comes back hot
+      cache.insert(0, 0);
+      cache.validate();
+      assert_eq!(cache.is_hot(&0), Some(true));
+      assert_eq!(cache.ghost_len(), 1);
+      assert_eq!((cache.a1in_len(), cache.am_len()), (3, 1));
+      // A1in is over its share of two, so it keeps giving up entries
+      cache.insert(5, 5);
+      cache.insert(6, 6);
+      assert_eq!((cache.a1in_len(), cache.am_len()), (3, 1));
+      // the ghost queue only remembers two keys
+      assert_eq!(cache.ghost_len(), 2);
+      assert!(!cache.a1out.contains_key(&1));
+      cache.insert(1, 1);
+      assert_eq!(cache.is_hot(&1), Some(false));
+      cache.validate();
+
+      assert_eq!(cache.remove(&0), Some(0));
+      assert_eq!(cache.insert(6, 60), Some(6));
+      cache.clear();
+      assert!(cache.is_empty());
+      assert_eq!(cache.ghost_len(), 0);
+      assert_eq!(TwoQCache::with_capacity(0).insert(1, 1), None);
+   }
+
+   #[test]
+   fn test_scan_resistance() {
+      let mut cache = TwoQCache::with_capacity(20);
+      let hot = 0..5;
+      for k in hot.clone() {
+         cache.insert(k, k);
+      }
+      // push the hot keys through A1in, then bring them back into Am
+      for k in 100..120 {
+         cache.insert(k, k);
+      }
+      for k in hot.clone() {
+         assert_eq!(cache.insert(k, k), None);
+         assert_eq!(cache.is_hot(&k), Some(true));
+      }
+      let mut lru = crate::LRUCache::with_capacity(20);
+      for k in hot.clone() {
+         lru.insert(k, k);
+      }
+      for k in 1000..2000 {
+         cache.insert(k, k);
+         lru.insert(k, k);
+         cache.validate();
+      }
+      for k in hot {
+         assert_eq!(cache.get(&k), Some(&k));
+         assert_eq!(lru.get(&k), None);
+      }
+   }
+
+   #[test]
+   #[should_panic(expected = "kin must be a fraction")]
+   fn test_bad_fraction() {
+      TwoQCache::<u8, u8>::with_fractions(4, 1.5, 0.5);
+   }
+}
@@ -0,0 +1,202 @@
+use crate::lru::PushResult;
+use crate::{Cache, CachePush};
+use std::hash::Hash;
+
+/// A small, fast L1 cache in front of a larger L2 cache, itself a [`Cache`]. New entries go to
+/// L1 and whatever L1 evicts spills into L2 instead of being dropped. A hit in L2 moves the entry
+/// back up into L1, spilling L1's victim in turn, so a key normally lives in one tier only.
+///
+/// L1 has to report its evictions through [`CachePush`]. Promoting an L2 hit needs an owned
+/// copy of the key, so the `Cache` impl asks for `K: Clone`.
+///
+/// ```
+/// use rs_lru::{Cache, LRUCache, LRUkCache, TwoTierCache};
+///
+/// let l2 = LRUkCache::with_capacity_freq(8, 2);
+/// let mut cache = TwoTierCache::new(LRUCache::with_capacity(1), l2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// // 1 was spilled, not dropped
+/// assert!(cache.l2().contains_key(&1));
+/// assert_eq!(cache.get(&1), Some(&"a"));
+/// assert!(cache.l1().contains_key(&1) && cache.l2().contains_key(&2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TwoTierCache<C1, C2> {
+   l1: C1,
+   l2: C2,
+}
+
+impl<C1, C2> TwoTierCache<C1, C2> {
+   pub fn new(l1: C1, l2: C2) -> Self {
+      Self { l1, l2 }
+   }
+
+   pub fn l1(&self) -> &C1 {
+      &self.l1
+   }
+
+   pub fn l2(&self) -> &C2 {
+      &self.l2
+   }
+
+   pub fn into_inner(self) -> (C1, C2) {
+      (self.l1, self.l2)
+   }
+
+   // Put the entry into L1 and whatever L1 lets go of into L2. Returns the value L1 replaced.
+   fn push_l1<K: Hash + Eq, V>(&mut self, k: K, v: V) -> Option<V>
+   where
+      C1: CachePush<K, V>,
+      C2: Cache<K, V>,
+   {
+      match self.l1.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         PushResult::Evicted(k, v) => {
+            self.l2.insert(k, v);
+            None
+         }
+         PushResult::Fit => None,
+      }
+   }
+}
+
+impl<K, V, C1, C2> Cache<K, V> for TwoTierCache<C1, C2>
+where
+   K: Hash + Eq + Clone,
+   C1: CachePush<K, V>,
+   C2: Cache<K, V>,
+{
+   fn get(&mut self, k: &K) -> Option<&V> {
+      if !self.l1.contains_key(k) {
+         let v = self.l2.remove(k)?;
+         self.push_l1(k.clone(), v);
+         // an L1 that keeps nothing spilled it right back
+         if !self.l1.contains_key(k) {
+            return self.l2.get(k);
+         }
+      }
+      self.l1.get(k)
+   }
+
+   /// Writes go to L1. A copy of the key in L2 is dropped and its value returned if L1 held none.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      let stale = self.l2.remove(&k);
+      self.push_l1(k, v).or(stale)
+   }
+
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let l1 = self.l1.remove(k);
+      let l2 = self.l2.remove(k);
+      l1.or(l2)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      self.l1.contains_key(k) || self.l2.contains_key(k)
+   }
+
+   fn len(&self) -> usize {
+      self.l1.len() + self.l2.len()
+   }
+
+   fn capacity(&self) -> usize {
+      self.l1.capacity().saturating_add(self.l2.capacity())
+   }
+
+   fn clear(&mut self) {
+      self.l1.clear();
+      self.l2.clear();
+   }
+
+   /// Visits L1, then L2.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.l1.for_each_entry(f);
+      self.l2.for_each_entry(f);
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{LRUCache, LRUkCache};
+
+   fn tiers(l1: usize, l2: usize) -> TwoTierCache<LRUCache<u32, u32>, LRUkCache<u32, u32>> {
+      TwoTierCache::new(
+         LRUCache::with_capacity(l1),
+         LRUkCache::with_capacity_freq(l2, 2),
+      )
+   }
+
+   #[test]
+   fn test_spill() {
+      let mut cache = tiers(2, 4);
+      for k in 0..5 {
+         assert_eq!(cache.insert(k, k), None);
+      }
+      // L1 holds the two newest keys, the rest spilled in eviction order
+      assert!(cache.l1().contains_key(&3) && cache.l1().contains_key(&4));
+      assert_eq!(
+         cache.l2().iter().map(|(k, _, _)| *k).collect::<Vec<_>>(),
+         [0, 1, 2]
+      );
+      assert_eq!(cache.len(), 5);
+      // once L2 is full too, entries leave for good
+      for k in 5..10 {
+         cache.insert(k, k);
+      }
+      assert_eq!(cache.len(), 6);
+      assert!(!cache.contains_key(&0));
+      cache.l1().validate();
+      cache.l2().validate();
+   }
+
+   #[test]
+   fn test_promote_on_l2_hit() {
+      let mut cache = tiers(2, 4);
+      for k in 0..4 {
+         cache.insert(k, k * 10);
+      }
+      assert!(cache.l2().contains_key(&0));
+      assert_eq!(cache.get(&0), Some(&0));
+      // 0 moved up, L1's least recently used entry moved down
+      assert!(cache.l1().contains_key(&0) && !cache.l2().contains_key(&0));
+      assert!(cache.l2().contains_key(&2) && !cache.l1().contains_key(&2));
+      assert_eq!(cache.len(), 4);
+      assert_eq!(cache.get(&9), None);
+
+      // an L1 without room sends the entry straight back
+      let mut cache = tiers(0, 4);
+      cache.insert(1, 1);
+      assert_eq!(cache.get(&1), Some(&1));
+      assert!(cache.l1().is_empty());
+      assert_eq!(cache.capacity(), 4);
+   }
+
+   #[test]
+   fn test_key_in_both_tiers() {
+      let mut l1 = LRUCache::with_capacity(2);
+      let mut l2 = LRUkCache::with_capacity_freq(4, 2);
+      l1.insert(1, 10);
+      l2.insert(1, 1);
+      let mut cache = TwoTierCache::new(l1, l2);
+      // L1 answers first
+      assert_eq!(cache.get(&1), Some(&10));
+      assert_eq!(cache.remove(&1), Some(10));
+      assert!(!cache.l1().contains_key(&1) && !cache.l2().contains_key(&1));
+      assert_eq!(cache.get(&1), None);
+      // reinserting does not resurrect anything
+      assert_eq!(cache.insert(1, 11), None);
+      assert_eq!(cache.get(&1), Some(&11));
+      assert!(!cache.l2().contains_key(&1));
+
+      // a write while the key sits in L2 replaces that copy
+      cache.insert(2, 2);
+      cache.insert(3, 3);
+      assert!(cache.l2().contains_key(&1));
+      assert_eq!(cache.insert(1, 12), Some(11));
+      assert!(!cache.l2().contains_key(&1));
+      assert_eq!(cache.get(&1), Some(&12));
+      cache.clear();
+      assert!(cache.is_empty());
+   }
+}
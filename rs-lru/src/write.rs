@@ -0,0 +1,355 @@
+use crate::lru::PushResult;
+use crate::{Cache, CachePush};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::mem;
+
+/// The store behind a [`WriteCache`].
+pub trait WriteSink<K, V> {
+   type Error;
+
+   fn write(&mut self, k: &K, v: &V) -> Result<(), Self::Error>;
+}
+
+/// When a [`WriteCache`] writes to its sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WriteMode {
+   /// Every insert is written before it is cached.
+   Through,
+   /// Inserts only mark the entry dirty. It is written when it leaves the cache or on `flush`.
+   Back,
+}
+
+/// Makes a cache take part in the writes to the store behind it. In [`WriteMode::Through`]
+/// every insert reaches the sink right away, in [`WriteMode::Back`] the sink only sees an entry
+/// once it is evicted, flushed or cleared, so repeated writes to a hot key cost one store write.
+///
+/// An entry is dirty while the sink has not accepted its latest value. A failed write never
+/// loses it: a cached entry stays dirty, an evicted one is kept aside, and
+/// [`flush`](WriteCache::flush) retries both. Evictions are captured through [`CachePush`], the
+/// dirty keys are tracked in a set of their own, so the keys have to be `Clone`.
+///
+/// ```
+/// use rs_lru::write::{WriteCache, WriteSink};
+/// use rs_lru::{Cache, LRUCache};
+/// use std::collections::HashMap;
+/// use std::convert::Infallible;
+///
+/// struct Store(HashMap<u32, String>);
+///
+/// impl WriteSink<u32, String> for Store {
+///    type Error = Infallible;
+///
+///    fn write(&mut self, k: &u32, v: &String) -> Result<(), Infallible> {
+///       self.0.insert(*k, v.clone());
+///       Ok(())
+///    }
+/// }
+///
+/// let mut cache = WriteCache::write_back(LRUCache::with_capacity(2), Store(HashMap::new()));
+/// cache.insert(1, String::from("a"));
+/// cache.insert(1, String::from("b"));
+/// assert!(cache.sink().0.is_empty());
+/// cache.flush().unwrap();
+/// assert_eq!(cache.sink().0[&1], "b");
+/// ```
+pub struct WriteCache<K, V, C, S> {
+   cache: C,
+   sink: S,
+   mode: WriteMode,
+   dirty: HashSet<K>,
+   // dirty entries that left the cache while the sink was failing
+   pending: Vec<(K, V)>,
+}
+
+impl<K, V, C, S> WriteCache<K, V, C, S>
+where
+   K: Hash + Eq + Clone,
+   C: CachePush<K, V>,
+   S: WriteSink<K, V>,
+{
+   pub fn new(cache: C, sink: S, mode: WriteMode) -> Self {
+      Self {
+         cache,
+         sink,
+         mode,
+         dirty: HashSet::new(),
+         pending: Vec::new(),
+      }
+   }
+
+   pub fn write_through(cache: C, sink: S) -> Self {
+      Self::new(cache, sink, WriteMode::Through)
+   }
+
+   pub fn write_back(cache: C, sink: S) -> Self {
+      Self::new(cache, sink, WriteMode::Back)
+   }
+
+   pub fn mode(&self) -> WriteMode {
+      self.mode
+   }
+
+   /// Inserts like `insert`, but reports a failed write-through. The entry is cached either way,
+   /// marked dirty when the sink refused it, and the value it replaced is dropped on error.
+   pub fn try_insert(&mut self, k: K, v: V) -> Result<Option<V>, S::Error> {
+      let (old, result) = self.put(k, v);
+      result.map(|()| old)
+   }
+
+   /// Writes every dirty entry to the sink and clears the dirty flags, retrying the entries
+   /// that failed to be written on their way out first. Entries the sink refuses stay dirty, the
+   /// first error is returned once all of them have been tried. The cached entries are walked
+   /// with `for_each_entry`, so flushing counts as an access to none of them.
+   pub fn flush(&mut self) -> Result<(), S::Error> {
+      let mut first = None;
+      for (k, v) in mem::take(&mut self.pending) {
+         if let Err(e) = self.sink.write(&k, &v) {
+            first.get_or_insert(e);
+            self.pending.push((k, v));
+         }
+      }
+      let (dirty, sink) = (&mut self.dirty, &mut self.sink);
+      self.cache.for_each_entry(&mut |k, v| {
+         if dirty.contains(k) {
+            match sink.write(k, v) {
+               Ok(()) => {
+                  dirty.remove(k);
+               }
+               Err(e) => {
+                  first.get_or_insert(e);
+               }
+            }
+         }
+      });
+      match first {
+         Some(e) => Err(e),
+         None => Ok(()),
+      }
+   }
+
+   pub fn is_dirty(&self, k: &K) -> bool {
+      self.dirty.contains(k) || self.pending.iter().any(|(key, _)| key == k)
+   }
+
+   /// Returns how many entries the sink has not accepted yet, cached or not.
+   pub fn dirty_len(&self) -> usize {
+      self.dirty.len() + self.pending.len()
+   }
+
+   pub fn cache(&self) -> &C {
+      &self.cache
+   }
+
+   pub fn sink(&self) -> &S {
+      &self.sink
+   }
+
+   pub fn sink_mut(&mut self) -> &mut S {
+      &mut self.sink
+   }
+
+   /// Returns the cache and the sink. Dirty entries are not written.
+   pub fn into_inner(self) -> (C, S) {
+      (self.cache, self.sink)
+   }
+
+   // Cache the entry, writing it through first in that mode. Returns the replaced value and the
+   // outcome of the write.
+   fn put(&mut self, k: K, v: V) -> (Option<V>, Result<(), S::Error>) {
+      let mut result = Ok(());
+      match self.mode {
+         WriteMode::Through => match self.sink.write(&k, &v) {
+            Ok(()) => {
+               self.dirty.remove(&k);
+            }
+            Err(e) => {
+               self.dirty.insert(k.clone());
+               result = Err(e);
+            }
+         },
+         WriteMode::Back => {
+            // marked before the push, so an entry the cache declines is written right away
+            self.dirty.insert(k.clone());
+         }
+      }
+      let old = match self.cache.push(k, v) {
+         PushResult::Replaced(old) => Some(old),
+         PushResult::Evicted(k, v) => {
+            self.spill(k, v);
+            None
+         }
+         PushResult::Fit => None,
+      };
+      (old, result)
+   }
+
+   // Write back an entry leaving the cache if the sink has not seen it
+   fn spill(&mut self, k: K, v: V) {
+      if self.dirty.remove(&k) && self.sink.write(&k, &v).is_err() {
+         self.pending.push((k, v));
+      }
+   }
+}
+
+impl<K, V, C, S> Cache<K, V> for WriteCache<K, V, C, S>
+where
+   K: Hash + Eq + Clone,
+   C: CachePush<K, V>,
+   S: WriteSink<K, V>,
+{
+   fn get(&mut self, k: &K) -> Option<&V> {
+      self.cache.get(k)
+   }
+
+   /// A write-through the sink refuses still caches the entry, dirty. Use
+   /// [`try_insert`](WriteCache::try_insert) to see the error.
+   fn insert(&mut self, k: K, v: V) -> Option<V> {
+      self.put(k, v).0
+   }
+
+   /// Removing a dirty entry hands its unwritten value to the caller instead of the sink.
+   fn remove(&mut self, k: &K) -> Option<V> {
+      let v = self.cache.remove(k)?;
+      self.dirty.remove(k);
+      Some(v)
+   }
+
+   fn contains_key(&self, k: &K) -> bool {
+      self.cache.contains_key(k)
+   }
+
+   fn len(&self) -> usize {
+      self.cache.len()
+   }
+
+   fn capacity(&self) -> usize {
+      self.cache.capacity()
+   }
+
+   /// Writes back the dirty entries, then drops every entry.
+   fn clear(&mut self) {
+      for k in mem::take(&mut self.dirty) {
+         if let Some(v) = self.cache.remove(&k) {
+            if self.sink.write(&k, &v).is_err() {
+               self.pending.push((k, v));
+            }
+         }
+      }
+      self.cache.clear();
+   }
+
+   /// Entries kept aside after a failed write-back are not visited, they are no longer cached.
+   fn for_each_entry(&self, f: &mut dyn FnMut(&K, &V)) {
+      self.cache.for_each_entry(f)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::LRUCache;
+
+   #[derive(Default)]
+   struct Recorder {
+      writes: Vec<(u32, u32)>,
+      failing: bool,
+   }
+
+   impl WriteSink<u32, u32> for Recorder {
+      type Error = u32;
+
+      fn write(&mut self, k: &u32, v: &u32) -> Result<(), u32> {
+         if self.failing {
+            return Err(*k);
+         }
+         self.writes.push((*k, *v));
+         Ok(())
+      }
+   }
+
+   #[test]
+   fn test_write_through() {
+      let mut cache = WriteCache::write_through(LRUCache::with_capacity(2), Recorder::default());
+      assert_eq!(cache.insert(1, 10), None);
+      assert_eq!(cache.insert(1, 11), Some(10));
+      cache.insert(2, 20);
+      cache.insert(3, 30);
+      assert_eq!(cache.sink().writes, [(1, 10), (1, 11), (2, 20), (3, 30)]);
+      // the clean evictee is not written again
+      assert!(!cache.contains_key(&1));
+      assert_eq!(cache.dirty_len(), 0);
+      cache.flush().unwrap();
+      assert_eq!(cache.sink().writes.len(), 4);
+      assert_eq!(cache.get(&3), Some(&30));
+   }
+
+   #[test]
+   fn test_write_back() {
+      let mut cache = WriteCache::write_back(LRUCache::with_capacity(2), Recorder::default());
+      cache.insert(1, 10);
+      cache.insert(1, 11);
+      cache.insert(2, 20);
+      assert!(cache.sink().writes.is_empty());
+      assert!(cache.is_dirty(&1) && cache.is_dirty(&2));
+      // evicting 1 writes its latest value
+      cache.insert(3, 30);
+      assert_eq!(cache.sink().writes, [(1, 11)]);
+      assert!(!cache.is_dirty(&1));
+
+      cache.flush().unwrap();
+      let mut writes = cache.sink().writes.clone();
+      writes.sort();
+      assert_eq!(writes, [(1, 11), (2, 20), (3, 30)]);
+      assert_eq!(cache.dirty_len(), 0);
+      // clean entries leave silently and a second flush has nothing to do
+      cache.insert(4, 40);
+      cache.flush().unwrap();
+      assert_eq!(cache.sink().writes.len(), 4);
+
+      // removing hands the unwritten value back, clearing writes the rest
+      cache.insert(5, 50);
+      cache.insert(4, 41);
+      assert_eq!(cache.remove(&5), Some(50));
+      assert!(!cache.is_dirty(&5));
+      cache.clear();
+      assert_eq!(cache.sink().writes.last(), Some(&(4, 41)));
+      assert_eq!(cache.sink().writes.len(), 5);
+      assert!(cache.is_empty());
+   }
+
+   #[test]
+   fn test_sink_errors_keep_entries_dirty() {
+      let mut cache = WriteCache::write_back(LRUCache::with_capacity(2), Recorder::default());
+      cache.insert(1, 10);
+      cache.insert(2, 20);
+      cache.sink_mut().failing = true;
+      assert!(cache.flush().is_err());
+      assert!(cache.is_dirty(&1) && cache.is_dirty(&2));
+      // the evictee cannot be written, it is kept aside instead
+      cache.insert(3, 30);
+      assert!(!cache.contains_key(&1));
+      assert!(cache.is_dirty(&1));
+      assert_eq!(cache.dirty_len(), 3);
+
+      cache.sink_mut().failing = false;
+      cache.flush().unwrap();
+      assert_eq!(cache.sink().writes[0], (1, 10));
+      assert_eq!(cache.sink().writes.len(), 3);
+      assert_eq!(cache.dirty_len(), 0);
+
+      let mut cache = WriteCache::write_through(LRUCache::with_capacity(2), Recorder::default());
+      cache.insert(1, 10);
+      cache.sink_mut().failing = true;
+      assert_eq!(cache.try_insert(1, 11), Err(1));
+      // cached all the same, and dirty until the sink takes it
+      assert_eq!(cache.get(&1), Some(&11));
+      assert_eq!(cache.insert(1, 12), Some(11));
+      assert!(cache.is_dirty(&1));
+      cache.sink_mut().failing = false;
+      assert_eq!(cache.try_insert(2, 20), Ok(None));
+      cache.flush().unwrap();
+      assert_eq!(cache.sink().writes, [(1, 10), (2, 20), (1, 12)]);
+      assert!(!cache.is_dirty(&1));
+   }
+}
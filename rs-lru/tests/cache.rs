@@ -0,0 +1,348 @@
+use rs_lru::{Cache, CacheExt, LRUCache, LRUkCache};
+
+// Exercises the trait-level contract of a cache holding at least two entries
+fn generic<C: Cache<u32, String>>(c: &mut C) {
+   assert!(c.is_empty());
+   assert_eq!(c.len(), 0);
+   assert!(c.capacity() >= 2);
+   assert!(!c.contains_key(&1));
+
+   assert_eq!(c.insert(1, String::from("a")), None);
+   assert_eq!(c.insert(2, String::from("b")), None);
+   assert_eq!(c.insert(1, String::from("c")), Some(String::from("a")));
+   assert_eq!(c.len(), 2);
+   assert!(!c.is_empty());
+   assert!(c.contains_key(&1) && c.contains_key(&2));
+   assert_eq!(c.get(&1), Some(&String::from("c")));
+
+   assert_eq!(c.remove(&2), Some(String::from("b")));
+   assert_eq!(c.remove(&2), None);
+   assert!(!c.contains_key(&2));
+   assert_eq!(c.len(), 1);
+
+   c.clear();
+   assert!(c.is_empty());
+   assert!(!c.contains_key(&1));
+   assert_eq!(c.get(&1), None);
+
+   // a full cache never grows past its capacity
+   let cap = c.capacity().min(64) as u32;
+   for k in 0..cap * 2 {
+      c.insert(k, k.to_string());
+      assert!(c.len() <= c.capacity());
+   }
+}
+
+#[test]
+fn test_lru_through_trait() {
+   let mut cache = LRUCache::with_capacity(4);
+   generic(&mut cache);
+   assert_eq!(Cache::len(&cache), 4);
+   assert_eq!(Cache::capacity(&cache), 4);
+   generic(&mut LRUCache::unbounded());
+}
+
+#[test]
+fn test_lru_k_through_trait() {
+   let mut cache = LRUkCache::with_capacity_freq(4, 2);
+   generic(&mut cache);
+   assert_eq!(Cache::len(&cache), 4);
+   // clearing drops both segments
+   cache.get(&7);
+   Cache::clear(&mut cache);
+   assert_eq!((cache.fcfo_len(), cache.lru_len()), (0, 0));
+   generic(&mut LRUkCache::default());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_is_emtpy_forwards() {
+   let mut cache = LRUCache::with_capacity(2);
+   assert!(cache.is_emtpy());
+   cache.insert(1, 1);
+   assert!(!cache.is_emtpy());
+}
+
+mod dyn_cache {
+   use rs_lru::{Cache, LRUCache, LRUkCache};
+
+   fn build(policy: &str, cap: usize) -> Box<dyn Cache<u32, String>> {
+      match policy {
+         "lru" => Box::new(LRUCache::with_capacity(cap)),
+         "lru-k" => Box::new(LRUkCache::with_capacity_freq(cap, 2)),
+         _ => unreachable!(),
+      }
+   }
+
+   #[test]
+   fn test_boxed_caches() {
+      let mut caches: Vec<Box<dyn Cache<u32, String>>> = vec![build("lru", 8), build("lru-k", 8)];
+      for cache in &mut caches {
+         // goes through the Box impl
+         super::generic(cache);
+         cache.clear();
+      }
+      let mut results = Vec::new();
+      for cache in &mut caches {
+         let mut seen = Vec::new();
+         for k in 0..8 {
+            assert_eq!(cache.insert(k, k.to_string()), None);
+         }
+         for k in (0..8).rev() {
+            seen.push(cache.get(&k).cloned());
+         }
+         assert_eq!(cache.insert(3, String::from("x")), Some(String::from("3")));
+         seen.push(cache.remove(&3));
+         seen.push(cache.remove(&3));
+         results.push((seen, cache.len(), cache.capacity(), cache.contains_key(&3)));
+         cache.clear();
+         assert!(cache.is_empty());
+      }
+      // both policies agree on a workload that never evicts
+      assert_eq!(results[0], results[1]);
+      assert_eq!(results[0].1, 7);
+   }
+
+   #[test]
+   fn test_mut_ref_cache() {
+      let mut lru = LRUCache::with_capacity(4);
+      let mut by_ref: &mut LRUCache<u32, String> = &mut lru;
+      super::generic(&mut by_ref);
+      let mut boxed: Box<dyn Cache<u32, String>> = Box::new(LRUkCache::with_capacity_freq(4, 2));
+      super::generic(&mut &mut *boxed);
+   }
+}
+
+#[test]
+fn test_borrowed_lookups() {
+   use rs_lru::{CacheLookup, FifoCache};
+
+   let mut caches: Vec<Box<dyn CacheLookup<String, u64, str>>> = vec![
+      Box::new(LRUCache::with_capacity(4)),
+      Box::new(LRUkCache::with_capacity_freq(4, 2)),
+      Box::new(FifoCache::with_capacity(4)),
+   ];
+   for cache in &mut caches {
+      cache.insert(String::from("a"), 1);
+      cache.insert(String::from("b"), 2);
+      assert_eq!(cache.get_borrowed("a"), Some(&1));
+      assert_eq!(cache.get_borrowed("c"), None);
+      assert!(cache.contains_key_borrowed("b"));
+      assert_eq!(cache.remove_borrowed("b"), Some(2));
+      assert_eq!(cache.remove_borrowed("b"), None);
+      assert!(!cache.contains_key_borrowed("b"));
+      assert_eq!(cache.len(), 1);
+   }
+   // borrowed hits count like owned ones
+   let mut lru_k = LRUkCache::with_capacity_freq(4, 2);
+   lru_k.insert(String::from("a"), 1);
+   lru_k.get_borrowed("a");
+   lru_k.get_borrowed("z");
+   assert_eq!((lru_k.stats().fcfo_hits, lru_k.stats().misses), (1, 1));
+   assert_eq!(lru_k.peek("a"), Some(&1));
+}
+
+// Only the trait is known here, so the combinators cannot lean on the concrete type
+fn combinators<C: Cache<u32, String>>(c: &mut C) {
+   let mut calls = 0;
+   let mut build = |k: u32| {
+      calls += 1;
+      k.to_string()
+   };
+   assert_eq!(
+      c.get_or_insert_with(1, || build(1)).cloned(),
+      Some(String::from("1"))
+   );
+   assert_eq!(
+      c.get_or_insert_with(1, || build(1)).cloned(),
+      Some(String::from("1"))
+   );
+   assert_eq!(calls, 1);
+
+   let failed: Result<Option<&String>, &str> = c.try_get_or_insert_with(2, || Err("boom"));
+   assert_eq!(failed, Err("boom"));
+   assert!(!c.contains_key(&2));
+   let built: Result<_, ()> = c.try_get_or_insert_with(2, || Ok(String::from("two")));
+   assert_eq!(built, Ok(Some(&String::from("two"))));
+   let hit: Result<_, ()> = c.try_get_or_insert_with(2, || unreachable!());
+   assert_eq!(hit.unwrap().cloned(), Some(String::from("two")));
+
+   c.insert_all((3..5).map(|k| (k, k.to_string())));
+   assert_eq!(c.len(), 4);
+   assert_eq!(c.get_cloned(&3), Some(String::from("3")));
+   assert_eq!(c.get_cloned(&9), None);
+   assert_eq!(c.replace(3, String::from("x")), Some(String::from("3")));
+   assert_eq!(c.replace(9, String::from("9")), None);
+   assert_eq!(c.get_cloned(&3), Some(String::from("x")));
+}
+
+#[test]
+fn test_combinators() {
+   combinators(&mut LRUCache::with_capacity(8));
+   combinators(&mut LRUkCache::with_capacity_freq(8, 2));
+   let mut boxed: Box<dyn Cache<u32, String>> = Box::new(LRUCache::with_capacity(8));
+   combinators(&mut boxed);
+
+   // nothing is kept, so there is nothing to return
+   let mut empty = LRUCache::with_capacity(0);
+   assert_eq!(empty.get_or_insert_with(1, || 1), None);
+}
+
+fn warm<C: Cache<u32, u32>>(mut c: C) -> usize {
+   for k in 0..c.capacity().min(16) as u32 {
+      c.insert(k, k * k);
+   }
+   assert_eq!(c.get(&3), Some(&9));
+   c.len()
+}
+
+#[test]
+fn test_warm_by_value() {
+   let mut lru = LRUCache::with_capacity(4);
+   assert_eq!(warm(&mut lru), 4);
+   // the borrow warmed the caller's cache
+   assert_eq!(lru.peek(&2), Some(&4));
+   assert_eq!(warm(Box::new(LRUkCache::with_capacity_freq(8, 2))), 8);
+   assert_eq!(warm(LRUCache::unbounded()), 16);
+   // forwarding nests
+   assert_eq!(warm(&mut &mut lru), 4);
+}
+
+fn total<C: Cache<u32, u32> + ?Sized>(c: &C) -> u32 {
+   let mut sum = 0;
+   c.for_each_entry(&mut |_, v| sum += v);
+   sum
+}
+
+fn keys<C: Cache<u32, u32>>(c: &C) -> Vec<u32> {
+   let mut keys = Vec::new();
+   c.for_each_entry(&mut |k, _| keys.push(*k));
+   keys
+}
+
+#[test]
+fn test_for_each_entry() {
+   let mut lru = LRUCache::with_capacity(3);
+   let mut lru_k = LRUkCache::with_capacity_freq(3, 2);
+   for k in 1..=4 {
+      lru.insert(k, k * 10);
+      lru_k.insert(k, k * 10);
+   }
+   assert_eq!(total(&lru), 90);
+   assert_eq!(total(&lru_k), 90);
+
+   lru.get(&2);
+   lru_k.get(&3);
+   lru_k.get(&3);
+   // visiting counts no access
+   assert_eq!(keys(&lru), [2, 4, 3]);
+   assert_eq!(keys(&lru), [2, 4, 3]);
+   // protected first, then probation from the next victim
+   assert_eq!(keys(&lru_k), [3, 2, 4]);
+
+   let caches: Vec<Box<dyn Cache<u32, u32>>> = vec![Box::new(lru), Box::new(lru_k)];
+   assert!(caches.iter().all(|c| total(&**c) == 90));
+}
+
+#[test]
+fn test_for_each_entry_matches_len() {
+   use rs_lru::testing::{NoopCache, UnboundedCache};
+   use rs_lru::*;
+
+   let mut caches: Vec<Box<dyn Cache<u32, u32>>> = vec![
+      Box::new(ArcCache::with_capacity(8)),
+      Box::new(ClockCache::with_capacity(8)),
+      Box::new(LFUCache::with_capacity(8)),
+      Box::new(LirsCache::with_capacity(8)),
+      Box::new(LruK::with_capacity_k(8, 2)),
+      Box::new(MruCache::with_capacity(8)),
+      Box::new(FifoCache::with_capacity(8)),
+      Box::new(RandomCache::with_seed(8, 3)),
+      Box::new(SlruCache::with_capacities(3, 5)),
+      Box::new(TinyLfuCache::with_capacity(8)),
+      Box::new(TwoQCache::with_capacity(8)),
+      Box::new(MeteredCache::new(LRUCache::with_capacity(8))),
+      Box::new(TwoTierCache::new(
+         LRUCache::with_capacity(2),
+         LRUCache::with_capacity(6),
+      )),
+      Box::new(UnboundedCache::new()),
+      Box::new(NoopCache::new()),
+   ];
+   for cache in &mut caches {
+      let mut seed = 7u32;
+      for _ in 0..500 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 24;
+         match (seed >> 8) % 4 {
+            0 | 1 => {
+               if cache.get(&k).is_none() {
+                  cache.insert(k, k);
+               }
+            }
+            2 => {
+               cache.insert(k, k);
+            }
+            _ => {
+               cache.remove(&k);
+            }
+         }
+         let mut seen = 0;
+         cache.for_each_entry(&mut |k, v| {
+            assert_eq!(k, v);
+            seen += 1;
+         });
+         assert_eq!(seen, cache.len());
+      }
+   }
+}
+
+#[test]
+fn test_push_accounts_for_every_entry() {
+   use rs_lru::*;
+   use std::collections::HashSet;
+
+   // the eviction preference must not add a second victim to the overflowing segment's
+   let mut segmented = LRUkCache::with_segment_capacities(1, 3, 1);
+   segmented.set_eviction_preference(EvictionPreference::ProtectedFirst);
+   segmented.set_ghost_capacity(4);
+   let mut caches: Vec<Box<dyn CachePush<u32, u32>>> = vec![
+      Box::new(ArcCache::with_capacity(8)),
+      Box::new(ClockCache::with_capacity(8)),
+      Box::new(LFUCache::with_capacity(8)),
+      Box::new(LirsCache::with_capacity(8)),
+      Box::new(LruK::with_capacity_k(8, 2)),
+      Box::new(LRUkCache::with_capacity_freq(8, 2)),
+      Box::new(LRUkCache::with_segment_capacities(3, 5, 1)),
+      Box::new(segmented),
+      Box::new(MruCache::with_capacity(8)),
+      Box::new(FifoCache::with_capacity(8)),
+      Box::new(RandomCache::with_seed(8, 3)),
+      Box::new(SlruCache::with_capacities(3, 5)),
+      Box::new(TinyLfuCache::with_capacity(8)),
+      Box::new(TwoQCache::with_capacity(8)),
+   ];
+   for cache in &mut caches {
+      // the keys the cache must hold, going only by what push reported
+      let mut held = HashSet::new();
+      let mut seed = 11u32;
+      for _ in 0..500 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         let k = (seed >> 16) % 24;
+         if (seed >> 8) & 1 == 0 && cache.get(&k).is_some() {
+            continue;
+         }
+         match cache.push(k, k) {
+            PushResult::Fit => assert!(held.insert(k)),
+            PushResult::Replaced(old) => assert_eq!(old, k),
+            PushResult::Evicted(victim, value) => {
+               assert_eq!(victim, value);
+               held.insert(k);
+               assert!(held.remove(&victim));
+            }
+         }
+         assert_eq!(held.len(), cache.len());
+         assert!(held.iter().all(|k| cache.contains_key(k)));
+      }
+   }
+}
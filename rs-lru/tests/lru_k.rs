@@ -0,0 +1,47 @@
+use rs_lru::{Cache, LRUkCache};
+
+#[test]
+fn test_scan_resistance() {
+   let mut cache = LRUkCache::with_capacity_freq(3, 2);
+   cache.insert(1, 10);
+   cache.get(&1);
+   cache.get(&1);
+   // a scan of one-off keys only evicts from the probation segment
+   for i in 100..110 {
+      assert_eq!(cache.insert(i, i), None);
+   }
+   assert_eq!(cache.len(), 3);
+   assert_eq!(cache.get(&1), Some(&10));
+   assert_eq!(cache.get(&100), None);
+   assert_eq!(cache.get(&109), Some(&109));
+}
+
+#[test]
+fn test_public_surface() {
+   let mut cache: LRUkCache<String, i32> = LRUkCache::default();
+   assert!(cache.is_empty());
+   assert_eq!(cache.insert(String::from("a"), 1), None);
+   assert_eq!(cache.insert(String::from("a"), 2), Some(1));
+   assert_eq!(cache.get(&String::from("a")), Some(&2));
+   assert_eq!(cache.get(&String::from("a")), Some(&2));
+   cache.insert(String::from("b"), 3);
+   assert_eq!(cache.replace_no_promote(&String::from("b"), 4), Some(3));
+
+   let snapshot = cache.clone();
+   assert_eq!(snapshot, cache);
+   assert_eq!(
+      format!("{:?}", snapshot),
+      r#"LRUkCache { cap: 128, freq: 2, fcfo: [("b", 4, 0)], lru: [("a", 2, 2)] }"#
+   );
+
+   assert_eq!(cache.remove(&String::from("a")), Some(2));
+   assert_eq!(
+      cache.pop_entry(&String::from("b")),
+      Some((String::from("b"), 4))
+   );
+   assert!(cache.is_empty());
+
+   let (cap, freq, entries) = snapshot.into_parts();
+   let mut rebuilt = LRUkCache::from_parts(cap, freq, entries);
+   assert_eq!(rebuilt.evict_to(0).len(), 2);
+}
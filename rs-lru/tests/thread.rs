@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rs_lru::{Cache, LRUCache, LRUkCache, List};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_auto_traits() {
+   assert_send_sync::<List<String>>();
+   assert_send_sync::<LRUCache<u64, Arc<Vec<u8>>>>();
+   assert_send_sync::<LRUkCache<String, Arc<str>>>();
+   assert_send_sync::<rs_lru::list::Iter<'static, u8>>();
+   assert_send_sync::<rs_lru::lru::Iter<'static, u8, u8>>();
+}
+
+#[test]
+fn test_move_across_threads() {
+   let mut cache = LRUCache::with_capacity(2);
+   cache.insert(1, Arc::new(String::from("one")));
+   let handle = thread::spawn(move || {
+      cache.insert(2, Arc::new(String::from("two")));
+      cache.insert(3, Arc::new(String::from("three")));
+      cache
+   });
+   let mut cache = handle.join().unwrap();
+   assert_eq!(cache.get(&1), None);
+   assert_eq!(cache.get(&3).map(|v| v.as_str()), Some("three"));
+
+   let mut list: List<i32> = (0..4).collect();
+   let list = thread::spawn(move || {
+      list.push_back(4);
+      list
+   })
+   .join()
+   .unwrap();
+   assert_eq!(
+      list.iter().copied().collect::<Vec<_>>(),
+      vec![0, 1, 2, 3, 4]
+   );
+}
+
+#[test]
+fn test_shared_behind_mutex() {
+   let cache = Arc::new(Mutex::new(LRUkCache::with_capacity_freq(64, 1)));
+   let workers: Vec<_> = (0..4)
+      .map(|t| {
+         let cache = Arc::clone(&cache);
+         thread::spawn(move || {
+            for i in 0..16 {
+               cache.lock().unwrap().insert(t * 16 + i, i);
+            }
+         })
+      })
+      .collect();
+   for worker in workers {
+      worker.join().unwrap();
+   }
+   let cache = cache.lock().unwrap();
+   assert_eq!(cache.len(), 64);
+   // readers can share the cache directly
+   thread::scope(|s| {
+      s.spawn(|| assert_eq!(cache.peek(&0), Some(&0)));
+      s.spawn(|| assert_eq!(cache.peek(&63), Some(&15)));
+   });
+}